@@ -0,0 +1,465 @@
+//! User accounts, password hashing and bearer tokens
+//!
+//! `POST /users` registers an account and `POST /login` trades a username and
+//! password for a signed token. Task routes take an `AuthUser`, which only
+//! resolves for requests carrying `Authorization: Bearer <token>`.
+//!
+//! A token is `<user id>.<expiry>.<signature>`, where the signature is an
+//! HMAC-SHA256 of the first two parts under the server's secret. Nothing is
+//! stored server-side, so tokens stay valid until they expire.
+
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::store::{TaskStore, UserStore};
+use crate::task::Task;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::{Arc, OnceLock};
+use utoipa::ToSchema;
+
+pub use task_model::auth::LoginResponse;
+
+/// Shortest accepted password, in characters
+pub const MIN_PASSWORD_LEN: usize = 8;
+/// Longest accepted username, in characters
+pub const MAX_USERNAME_LEN: usize = 32;
+/// Longest accepted e-mail address, in bytes
+pub const MAX_EMAIL_LEN: usize = 254;
+
+/// A stored account; `password_hash` is an Argon2 PHC string
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: u32,
+    pub username: String,
+    pub password_hash: String,
+    pub created_at: DateTime<Utc>,
+    /// Where notifications are mailed, if SMTP is set up
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// An account as returned by the API, without its password hash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UserProfile {
+    pub id: u32,
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        UserProfile {
+            id: user.id,
+            username: user.username,
+            created_at: user.created_at,
+            email: user.email,
+        }
+    }
+}
+
+/// Body of `POST /users` and `POST /login`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    /// Only read by `POST /users`
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// Whether `email` looks enough like an address to hand to a mail server
+pub fn is_email(email: &str) -> bool {
+    email.len() <= MAX_EMAIL_LEN
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && email
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty())
+}
+
+impl Credentials {
+    /// Normalizes the username to lowercase and checks both fields
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        self.username = self.username.trim().to_lowercase();
+        let valid_chars = self
+            .username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if self.username.is_empty() || self.username.len() > MAX_USERNAME_LEN {
+            errors.push(FieldError::new(
+                "username",
+                format!("must be 1 to {} characters", MAX_USERNAME_LEN),
+            ));
+        } else if !valid_chars {
+            errors.push(FieldError::new(
+                "username",
+                "may only contain letters, digits, '_', '-' and '.'",
+            ));
+        }
+
+        if self.password.chars().count() < MIN_PASSWORD_LEN {
+            errors.push(FieldError::new(
+                "password",
+                format!("must be at least {} characters", MIN_PASSWORD_LEN),
+            ));
+        }
+
+        if let Some(email) = &mut self.email {
+            *email = email.trim().to_string();
+            if !is_email(email) {
+                errors.push(FieldError::new("email", "must be an e-mail address"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Hashes `password` with Argon2 and a fresh random salt
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+/// Whether `password` matches a hash made by `hash_password`
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// A hash no password is expected to match, checked when logging in as an
+/// unknown user so that takes as long as a wrong password
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password("not the password").unwrap_or_default())
+}
+
+/// The `auth` section of `Rocket.toml`
+///
+/// ```toml
+/// [default.auth]
+/// secret = "a long random string"
+/// token_ttl_hours = 24
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthConfig {
+    /// Key tokens are signed with; a random one is used when unset
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_token_ttl_hours")]
+    pub token_ttl_hours: u32,
+}
+
+fn default_token_ttl_hours() -> u32 {
+    24
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            secret: None,
+            token_ttl_hours: default_token_ttl_hours(),
+        }
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Issues and checks bearer tokens; managed as Rocket state
+pub struct TokenSigner {
+    key: Vec<u8>,
+    ttl: Duration,
+}
+
+impl TokenSigner {
+    pub fn new(key: impl Into<Vec<u8>>, ttl: Duration) -> Self {
+        TokenSigner {
+            key: key.into(),
+            ttl,
+        }
+    }
+
+    /// Uses the configured secret, or a random key if there is none
+    pub fn from_config(config: &AuthConfig) -> Self {
+        let ttl = Duration::hours(config.token_ttl_hours.into());
+        match &config.secret {
+            Some(secret) => TokenSigner::new(secret.as_bytes(), ttl),
+            None => {
+                warn!("no auth.secret configured; tokens will not survive a restart");
+                let mut key = vec![0; 32];
+                OsRng.fill_bytes(&mut key);
+                TokenSigner::new(key, ttl)
+            }
+        }
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    /// A token for `user_id` and the moment it expires
+    pub fn issue(&self, user_id: u32, now: DateTime<Utc>) -> (String, DateTime<Utc>) {
+        let expires_at = now + self.ttl;
+        let payload = format!("{}.{}", user_id, expires_at.timestamp());
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+        (format!("{}.{}", payload, signature), expires_at)
+    }
+
+    /// The user id in `token`, if it was signed by this key and has not expired
+    pub fn verify(&self, token: &str, now: DateTime<Utc>) -> Option<u32> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(payload).verify_slice(&signature).ok()?;
+
+        let (user_id, expires_at) = payload.split_once('.')?;
+        let expires_at: i64 = expires_at.parse().ok()?;
+        if now.timestamp() >= expires_at {
+            return None;
+        }
+        user_id.parse().ok()
+    }
+}
+
+/// Request guard for the signed-in user
+///
+/// Fails with 401 when the `Authorization` header is missing, the token is
+/// invalid or expired, or its account no longer exists.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthUser {
+    pub id: u32,
+    pub username: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let rocket = req.rocket();
+        let (Some(signer), Some(users)) = (
+            rocket.state::<TokenSigner>(),
+            rocket.state::<Arc<dyn UserStore>>(),
+        ) else {
+            let error = ApiError::Internal("authentication is not configured".to_string());
+            return Outcome::Error((Status::InternalServerError, error));
+        };
+
+        let user_id = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| signer.verify(token.trim(), Utc::now()));
+        let Some(user_id) = user_id else {
+            let error = ApiError::Unauthorized("a valid bearer token is required".to_string());
+            return Outcome::Error((Status::Unauthorized, error));
+        };
+
+        match users.get_user(user_id) {
+            Ok(Some(user)) => Outcome::Success(AuthUser {
+                id: user.id,
+                username: user.username,
+            }),
+            Ok(None) => {
+                let error = ApiError::Unauthorized("the account no longer exists".to_string());
+                Outcome::Error((Status::Unauthorized, error))
+            }
+            Err(e) => {
+                error!("failed to load user {}: {}", user_id, e);
+                Outcome::Error((Status::InternalServerError, e.into()))
+            }
+        }
+    }
+}
+
+/// Loads task `id` if `user` may see it; other users' private tasks and
+/// tasks in the trash are reported as missing rather than forbidden
+pub(crate) fn visible_task(
+    store: &dyn TaskStore,
+    id: u32,
+    user: &AuthUser,
+) -> Result<Task, ApiError> {
+    store
+        .get(id)?
+        .filter(|task| task.visible_to(user.id) && !task.is_trashed())
+        .ok_or_else(|| ApiError::task_not_found(id))
+}
+
+/// Loads task `id` if `user` may change it
+pub(crate) fn editable_task(
+    store: &dyn TaskStore,
+    id: u32,
+    user: &AuthUser,
+) -> Result<Task, ApiError> {
+    let task = visible_task(store, id, user)?;
+    if task.editable_by(user.id) {
+        Ok(task)
+    } else if task.owner_id.is_none() {
+        Err(ApiError::Forbidden(format!(
+            "task {} predates accounts and is read-only",
+            id
+        )))
+    } else {
+        Err(ApiError::Forbidden(format!(
+            "task {} is shared with you but owned by another user",
+            id
+        )))
+    }
+}
+
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    responses(
+        (status = 201, description = "The new account", body = UserProfile),
+        (status = 409, description = "The username is taken", body = ErrorBody),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[post("/users", data = "<credentials>")]
+pub fn register(
+    users: &State<Arc<dyn UserStore>>,
+    credentials: Json<Credentials>,
+) -> Result<Created<Json<UserProfile>>, ApiError> {
+    let mut credentials = credentials.into_inner();
+    credentials.validate().map_err(ApiError::Validation)?;
+
+    let hash = hash_password(&credentials.password)
+        .map_err(|e| ApiError::Internal(format!("could not hash password: {}", e)))?;
+    let user = users.insert_user(
+        &credentials.username,
+        credentials.email.as_deref(),
+        &hash,
+        Utc::now(),
+    )?;
+    Ok(Created::new(uri!(me).to_string()).body(Json(user.into())))
+}
+
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    responses(
+        (status = 200, description = "A bearer token", body = LoginResponse),
+        (status = 401, description = "Unknown username or wrong password", body = ErrorBody)
+    )
+)]
+#[post("/login", data = "<credentials>")]
+pub fn login(
+    users: &State<Arc<dyn UserStore>>,
+    signer: &State<TokenSigner>,
+    credentials: Json<Credentials>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let username = credentials.username.trim().to_lowercase();
+    let user = users.find_user(&username)?;
+    let hash = match &user {
+        Some(user) => user.password_hash.as_str(),
+        None => dummy_hash(),
+    };
+    let verified = verify_password(hash, &credentials.password);
+    let user = user
+        .filter(|_| verified)
+        .ok_or_else(|| ApiError::Unauthorized("invalid username or password".to_string()))?;
+
+    let (token, expires_at) = signer.issue(user.id, Utc::now());
+    Ok(Json(LoginResponse {
+        token,
+        token_type: "Bearer".to_string(),
+        expires_at,
+    }))
+}
+
+#[utoipa::path(
+    tag = "auth",
+    responses(
+        (status = 200, description = "The signed-in account", body = UserProfile)
+    )
+)]
+#[get("/users/me")]
+pub fn me(
+    users: &State<Arc<dyn UserStore>>,
+    user: AuthUser,
+) -> Result<Json<UserProfile>, ApiError> {
+    match users.get_user(user.id)? {
+        Some(user) => Ok(Json(user.into())),
+        None => Err(ApiError::Unauthorized(
+            "the account no longer exists".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_round_trip_until_expiry() {
+        let signer = TokenSigner::new("secret", Duration::hours(1));
+        let now = Utc::now();
+        let (token, expires_at) = signer.issue(7, now);
+
+        assert_eq!(signer.verify(&token, now), Some(7));
+        assert_eq!(signer.verify(&token, expires_at), None);
+
+        let other = TokenSigner::new("other secret", Duration::hours(1));
+        assert_eq!(other.verify(&token, now), None);
+
+        let forged = token.replacen('7', "8", 1);
+        assert_eq!(signer.verify(&forged, now), None);
+        assert_eq!(signer.verify("not a token", now), None);
+    }
+
+    #[test]
+    fn passwords_are_salted_and_verified() {
+        let first = hash_password("correct horse").unwrap();
+        let second = hash_password("correct horse").unwrap();
+        assert_ne!(first, second);
+        assert!(verify_password(&first, "correct horse"));
+        assert!(!verify_password(&first, "battery staple"));
+        assert!(!verify_password("garbage", "correct horse"));
+    }
+
+    #[test]
+    fn validates_credentials() {
+        let mut credentials = Credentials {
+            username: "  Ada ".into(),
+            password: "long enough".into(),
+            email: Some(" ada@example.com ".into()),
+        };
+        credentials.validate().unwrap();
+        assert_eq!(credentials.username, "ada");
+        assert_eq!(credentials.email.as_deref(), Some("ada@example.com"));
+
+        let errors = Credentials {
+            username: "no spaces".into(),
+            password: "short".into(),
+            email: Some("ada at example.com".into()),
+        }
+        .validate()
+        .unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["username", "password", "email"]);
+    }
+}
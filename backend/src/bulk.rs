@@ -0,0 +1,323 @@
+//! `POST /tasks/bulk`: many creates, updates and deletes in one request
+//!
+//! Operations run in order against an in-memory copy of the store, through
+//! the same code as the single-task routes, so later operations see the
+//! effect of earlier ones. Only if every one succeeds are the resulting
+//! writes handed to `TaskStore::commit` as a single storage write; otherwise
+//! the request fails with every problem listed and nothing is changed. Tasks
+//! created without an id are numbered by the store as it commits, so a
+//! create racing the batch cannot take their ids.
+
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::etag::IfMatch;
+use crate::history::Changes;
+use crate::store::{id_after, ProjectStore, StoreError, TaskStore, TaskWrite};
+use crate::task::{NewTask, Task, TaskPatch};
+use chrono::{DateTime, Utc};
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use utoipa::ToSchema;
+
+/// Most operations accepted in one request
+pub const MAX_OPERATIONS: usize = 200;
+
+/// One entry of a bulk request, tagged by `op`
+///
+/// `version`, when given, works like `If-Match` on the single-task routes.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Create {
+        task: NewTask,
+    },
+    Update {
+        id: u32,
+        #[serde(default)]
+        version: Option<u32>,
+        patch: TaskPatch,
+    },
+    Delete {
+        id: u32,
+        #[serde(default)]
+        version: Option<u32>,
+    },
+}
+
+/// Body of `POST /tasks/bulk`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct BulkRequest {
+    pub operations: Vec<BulkOperation>,
+}
+
+/// Outcome of one operation: the status the single-task route would have
+/// answered with, and the task it would have returned
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BulkResult {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<Task>,
+}
+
+/// Response of `POST /tasks/bulk`, one result per operation in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BulkResponse {
+    pub results: Vec<BulkResult>,
+}
+
+/// A staged operation, kept until the batch is committed
+enum Outcome {
+    Created(Task),
+    Updated(Task, Task),
+    Deleted(Task, Task),
+}
+
+struct StagedState {
+    tasks: Vec<Task>,
+    next_id: u32,
+    writes: Vec<TaskWrite>,
+}
+
+/// A copy of the tasks that records writes instead of making them
+struct Staged {
+    state: Mutex<StagedState>,
+}
+
+impl Staged {
+    fn new(tasks: Vec<Task>, next_id: u32) -> Self {
+        Staged {
+            state: Mutex::new(StagedState {
+                tasks,
+                next_id,
+                writes: Vec::new(),
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, StagedState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn into_writes(self) -> Vec<TaskWrite> {
+        self.state
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .writes
+    }
+
+    /// Applies `write` to the copy with the same checks as the real stores
+    fn stage(&self, mut write: TaskWrite) -> Result<bool, StoreError> {
+        let mut state = self.state();
+        let position = |tasks: &[Task], id: u32| tasks.iter().position(|task| task.id == id);
+        match &mut write {
+            TaskWrite::Insert(task) => {
+                if position(&state.tasks, task.id).is_some() {
+                    return Err(StoreError::DuplicateId(task.id));
+                }
+                state.next_id = state.next_id.max(id_after(task.id)?);
+                state.tasks.push(task.clone());
+            }
+            TaskWrite::Create(task) => {
+                task.id = state.next_id;
+                state.next_id = id_after(state.next_id)?;
+                state.tasks.push(task.clone());
+            }
+            TaskWrite::Update(task) => match position(&state.tasks, task.id) {
+                Some(index) if state.tasks[index].version + 1 == task.version => {
+                    state.tasks[index] = task.clone();
+                }
+                Some(_) => return Err(StoreError::StaleVersion(task.id)),
+                None => return Ok(false),
+            },
+            TaskWrite::Delete(id) => match position(&state.tasks, *id) {
+                Some(index) => {
+                    state.tasks.remove(index);
+                }
+                None => return Ok(false),
+            },
+        }
+        state.writes.push(write);
+        Ok(true)
+    }
+}
+
+impl TaskStore for Staged {
+    fn list(&self) -> Result<Vec<Task>, StoreError> {
+        Ok(self.state().tasks.clone())
+    }
+
+    fn get(&self, id: u32) -> Result<Option<Task>, StoreError> {
+        Ok(self
+            .state()
+            .tasks
+            .iter()
+            .find(|task| task.id == id)
+            .cloned())
+    }
+
+    /// Stages a task without an id as a `Create`, under a provisional id
+    fn insert(&self, task: NewTask, now: DateTime<Utc>) -> Result<Task, StoreError> {
+        let (id, write): (u32, fn(Task) -> TaskWrite) = match task.id {
+            Some(id) => (id, TaskWrite::Insert),
+            None => (self.state().next_id, TaskWrite::Create),
+        };
+        let task = task.into_task(id, now);
+        self.stage(write(task.clone()))?;
+        Ok(task)
+    }
+
+    fn update(&self, task: Task) -> Result<bool, StoreError> {
+        self.stage(TaskWrite::Update(task))
+    }
+
+    fn delete(&self, id: u32) -> Result<bool, StoreError> {
+        self.stage(TaskWrite::Delete(id))
+    }
+
+    fn next_id(&self) -> Result<u32, StoreError> {
+        Ok(self.state().next_id)
+    }
+
+    fn commit(&self, writes: Vec<TaskWrite>) -> Result<Vec<u32>, StoreError> {
+        let mut created = Vec::new();
+        for write in writes {
+            if matches!(write, TaskWrite::Create(_)) {
+                created.push(self.state().next_id);
+            }
+            self.stage(write)?;
+        }
+        Ok(created)
+    }
+}
+
+/// Runs one operation against the staged copy
+///
+/// `seen` holds the tasks touched so far; each task may appear only once per
+/// batch, since the batch is saved as a single version bump per task.
+fn run(
+    staged: &Staged,
+    projects: &dyn ProjectStore,
+    user: &AuthUser,
+    operation: BulkOperation,
+    seen: &mut HashSet<u32>,
+) -> Result<Outcome, ApiError> {
+    let mut claim = |id: u32| {
+        if seen.insert(id) {
+            Ok(())
+        } else {
+            Err(ApiError::Unprocessable(format!(
+                "task {} appears more than once in the batch",
+                id
+            )))
+        }
+    };
+
+    match operation {
+        BulkOperation::Create { task } => {
+            let task = crate::insert_task(staged, projects, user, task)?;
+            claim(task.id)?;
+            Ok(Outcome::Created(task))
+        }
+        BulkOperation::Update { id, version, patch } => {
+            claim(id)?;
+            let mut patch = patch;
+            patch.validate().map_err(ApiError::Validation)?;
+            let if_match = version.map_or(IfMatch(None), IfMatch::version);
+            let (before, after) = crate::save_patch(staged, projects, user, &if_match, id, patch)?;
+            Ok(Outcome::Updated(before, after))
+        }
+        BulkOperation::Delete { id, version } => {
+            claim(id)?;
+            let if_match = version.map_or(IfMatch(None), IfMatch::version);
+            let (before, after) = crate::trash_task(staged, user, &if_match, id)?;
+            Ok(Outcome::Deleted(before, after))
+        }
+    }
+}
+
+/// Applies a batch of operations all together or not at all
+///
+/// Answers 200 with one result per operation. If any operation fails, the
+/// error lists each failure by its index under `details` and takes the
+/// status of the first one.
+#[utoipa::path(
+    tag = "tasks",
+    responses(
+        (status = 200, description = "Every operation was applied", body = BulkResponse),
+        (status = "4XX", description = "Nothing was applied; `details` lists each failing operation", body = ErrorBody)
+    )
+)]
+#[post("/tasks/bulk", data = "<request>")]
+pub fn bulk_tasks(
+    store: &State<Arc<dyn TaskStore>>,
+    projects: &State<Arc<dyn ProjectStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    request: Json<BulkRequest>,
+) -> Result<Json<BulkResponse>, ApiError> {
+    let operations = request.into_inner().operations;
+    if operations.is_empty() || operations.len() > MAX_OPERATIONS {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "operations",
+            format!("must hold between 1 and {} operations", MAX_OPERATIONS),
+        )]));
+    }
+
+    let total = operations.len();
+    let staged = Staged::new(store.list()?, store.next_id()?);
+    let mut seen = HashSet::new();
+    let mut outcomes = Vec::with_capacity(total);
+    let mut failures = Vec::new();
+    for (index, operation) in operations.into_iter().enumerate() {
+        match run(&staged, projects.as_ref(), &user, operation, &mut seen) {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(e) => failures.push((index, e)),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(ApiError::BulkRejected { total, failures });
+    }
+
+    let writes = staged.into_writes();
+    let provisional: Vec<u32> = writes
+        .iter()
+        .filter_map(|write| match write {
+            TaskWrite::Create(task) => Some(task.id),
+            _ => None,
+        })
+        .collect();
+    let created = store.commit(writes)?;
+    let renumbered: HashMap<u32, u32> = provisional.into_iter().zip(created).collect();
+
+    let results = outcomes
+        .into_iter()
+        .map(|outcome| match outcome {
+            Outcome::Created(mut task) => {
+                task.id = renumbered.get(&task.id).copied().unwrap_or(task.id);
+                changes.created(&user, &task);
+                BulkResult {
+                    status: 201,
+                    task: Some(task),
+                }
+            }
+            Outcome::Updated(before, after) => {
+                changes.updated(&user, &before, &after);
+                BulkResult {
+                    status: 200,
+                    task: Some(after),
+                }
+            }
+            Outcome::Deleted(before, after) => {
+                changes.deleted(&user, &before, &after);
+                BulkResult {
+                    status: 204,
+                    task: None,
+                }
+            }
+        })
+        .collect();
+    Ok(Json(BulkResponse { results }))
+}
@@ -0,0 +1,70 @@
+//! Where background jobs get the time from
+//!
+//! Jobs read the current time through an `Arc<dyn Clock>` in Rocket's
+//! managed state, falling back to `SystemClock` when none is managed. Tests
+//! manage a `ManualClock` instead and move it forward to simulate days
+//! passing:
+//!
+//! ```
+//! use backend::clock::{Clock, ManualClock};
+//! use std::sync::Arc;
+//!
+//! let clock = Arc::new(ManualClock::new("2026-03-02T09:00:00Z".parse().unwrap()));
+//! let rocket = backend::rocket().manage(Arc::clone(&clock) as Arc<dyn Clock>);
+//! clock.advance(chrono::Duration::days(1));
+//! ```
+
+use chrono::{DateTime, Duration, Utc};
+use rocket::{Orbit, Rocket};
+use std::sync::{Arc, Mutex};
+
+/// A source of the current time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The managed clock of `rocket`, or the system clock if none is managed
+pub fn of(rocket: &Rocket<Orbit>) -> Arc<dyn Clock> {
+    rocket
+        .state::<Arc<dyn Clock>>()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(SystemClock))
+}
@@ -0,0 +1,361 @@
+//! Comments on tasks: `/tasks/<id>/comments`
+//!
+//! Anyone who can see a task can read its comments and add their own, and
+//! only the author can edit or delete a comment. Bodies are Markdown, kept as
+//! written and rendered to HTML when read; the HTML is sanitized, so a
+//! comment cannot inject scripts, styles or event handlers into a page that
+//! shows it. Writing `@username` in a comment sends that user a `mention`
+//! notification through the `Notifier`, if they can see the task. Editing a
+//! comment notifies only users it did not mention before.
+
+use crate::auth::{visible_task, AuthUser};
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::notifications::{Notification, NotificationKind, Notifier};
+use crate::store::{CommentStore, TaskStore, UserStore};
+use crate::task::Task;
+use chrono::{DateTime, SecondsFormat, Utc};
+use pulldown_cmark::{html, Options, Parser};
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Longest accepted comment body, in characters
+pub const MAX_BODY_LEN: usize = 10_000;
+
+/// A comment as stored, with its Markdown body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: u32,
+    pub task_id: u32,
+    pub author_id: u32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// A comment as returned, with its author's name and rendered body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CommentView {
+    pub id: u32,
+    pub task_id: u32,
+    pub author_id: u32,
+    /// The author's username
+    pub author: String,
+    /// Markdown, as written
+    pub body: String,
+    /// `body` rendered to sanitized HTML
+    pub html: String,
+    pub created_at: DateTime<Utc>,
+    /// When the body was last changed, if ever
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// Body of `POST /tasks/<id>/comments` and
+/// `PATCH /tasks/<id>/comments/<comment_id>`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CommentText {
+    /// Markdown
+    pub body: String,
+}
+
+impl CommentText {
+    /// Trims the body and checks its length
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        self.body = self.body.trim().to_string();
+        if self.body.is_empty() {
+            Err(vec![FieldError::new("body", "must not be empty")])
+        } else if self.body.chars().count() > MAX_BODY_LEN {
+            Err(vec![FieldError::new(
+                "body",
+                format!("must be at most {} characters", MAX_BODY_LEN),
+            )])
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Renders Markdown to HTML, dropping anything unsafe to embed in a page
+pub fn render(markdown: &str) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+    ammonia::clean(&unsafe_html)
+}
+
+/// The usernames `@mentioned` in `body`, lowercased, each once
+///
+/// An `@` only starts a mention at the start of a word, so e-mail addresses
+/// do not mention anyone; a trailing `.` ends the sentence, not the name.
+pub fn mentions(body: &str) -> Vec<String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut names: Vec<String> = Vec::new();
+    let mut previous = None;
+    for (at, c) in body.char_indices() {
+        if c == '@' && !previous.is_some_and(is_name_char) {
+            let rest = &body[at + 1..];
+            let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+            let name = rest[..end].trim_end_matches('.').to_lowercase();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        previous = Some(c);
+    }
+    names
+}
+
+/// Notifies the users `comment` mentions who can see `task`, other than its
+/// author
+///
+/// Each mention's outbox key names the comment, by id and creation time, and
+/// the user, so a user is notified once per comment however often it is
+/// edited.
+fn notify_mentions(
+    users: &dyn UserStore,
+    notifier: &Notifier,
+    author: &AuthUser,
+    task: &Task,
+    comment: &Comment,
+) -> Result<(), ApiError> {
+    for name in mentions(&comment.body) {
+        let Some(user) = users.find_user(&name)? else {
+            continue;
+        };
+        if user.id == author.id || !task.visible_to(user.id) {
+            continue;
+        }
+        notifier.notify(
+            Notification {
+                id: 0,
+                user_id: user.id,
+                task_id: task.id,
+                kind: NotificationKind::Mention,
+                message: format!("{} mentioned you on '{}'", author.username, task.title),
+                at: comment.edited_at.unwrap_or(comment.created_at),
+                key: format!(
+                    "mention:{}:{}:{}",
+                    comment.id,
+                    comment
+                        .created_at
+                        .to_rfc3339_opts(SecondsFormat::Micros, true),
+                    user.id
+                ),
+            },
+            task,
+        )?;
+    }
+    Ok(())
+}
+
+/// Looks up the authors of `comments` and renders them for the response
+fn views(users: &dyn UserStore, comments: Vec<Comment>) -> Result<Vec<CommentView>, ApiError> {
+    let mut authors: Vec<(u32, String)> = Vec::new();
+    let mut views = Vec::with_capacity(comments.len());
+    for comment in comments {
+        let author = match authors.iter().find(|(id, _)| *id == comment.author_id) {
+            Some((_, name)) => name.clone(),
+            None => {
+                let name = users.get_user(comment.author_id)?.map_or_else(
+                    || format!("user {}", comment.author_id),
+                    |user| user.username,
+                );
+                authors.push((comment.author_id, name.clone()));
+                name
+            }
+        };
+        views.push(CommentView {
+            id: comment.id,
+            task_id: comment.task_id,
+            author_id: comment.author_id,
+            author,
+            html: render(&comment.body),
+            body: comment.body,
+            created_at: comment.created_at,
+            edited_at: comment.edited_at,
+        });
+    }
+    Ok(views)
+}
+
+/// Loads comment `comment_id` on task `task_id` if `user` wrote it
+fn own_comment(
+    store: &dyn CommentStore,
+    task_id: u32,
+    comment_id: u32,
+    user: &AuthUser,
+) -> Result<Comment, ApiError> {
+    let comment = store
+        .comments(task_id)?
+        .into_iter()
+        .find(|comment| comment.id == comment_id)
+        .ok_or_else(|| ApiError::NotFound(format!("comment {} does not exist", comment_id)))?;
+    if comment.author_id == user.id {
+        Ok(comment)
+    } else {
+        Err(ApiError::Forbidden(format!(
+            "comment {} was written by another user",
+            comment_id
+        )))
+    }
+}
+
+/// Lists a task's comments, oldest first
+#[utoipa::path(
+    tag = "comments",
+    responses(
+        (status = 200, description = "The task's comments, oldest first", body = [CommentView]),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody)
+    )
+)]
+#[get("/tasks/<id>/comments")]
+pub fn list_comments(
+    store: &State<Arc<dyn CommentStore>>,
+    tasks: &State<Arc<dyn TaskStore>>,
+    users: &State<Arc<dyn UserStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Json<Vec<CommentView>>, ApiError> {
+    visible_task(tasks.as_ref(), id, &user)?;
+    Ok(Json(views(users.as_ref(), store.comments(id)?)?))
+}
+
+/// Comments on a task you can see, notifying the users it mentions
+#[utoipa::path(
+    tag = "comments",
+    responses(
+        (status = 201, description = "The new comment", body = CommentView),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[post("/tasks/<id>/comments", data = "<text>")]
+pub fn create_comment(
+    store: &State<Arc<dyn CommentStore>>,
+    tasks: &State<Arc<dyn TaskStore>>,
+    users: &State<Arc<dyn UserStore>>,
+    notifier: &State<Notifier>,
+    user: AuthUser,
+    id: u32,
+    text: Json<CommentText>,
+) -> Result<Created<Json<CommentView>>, ApiError> {
+    let mut text = text.into_inner();
+    text.validate().map_err(ApiError::Validation)?;
+    let task = visible_task(tasks.as_ref(), id, &user)?;
+
+    let comment = store.insert_comment(Comment {
+        id: 0,
+        task_id: id,
+        author_id: user.id,
+        body: text.body,
+        created_at: Utc::now(),
+        edited_at: None,
+    })?;
+    notify_mentions(users.as_ref(), notifier, &user, &task, &comment)?;
+    let view = views(users.as_ref(), vec![comment])?.remove(0);
+    Ok(Created::new(format!("/tasks/{}/comments/{}", id, view.id)).body(Json(view)))
+}
+
+/// Replaces the body of one of your comments
+#[utoipa::path(
+    tag = "comments",
+    responses(
+        (status = 200, description = "The edited comment", body = CommentView),
+        (status = 403, description = "Another user wrote the comment", body = ErrorBody),
+        (status = 404, description = "No such task or comment, or not visible to you", body = ErrorBody),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[patch("/tasks/<id>/comments/<comment_id>", data = "<text>")]
+#[allow(clippy::too_many_arguments)]
+pub fn edit_comment(
+    store: &State<Arc<dyn CommentStore>>,
+    tasks: &State<Arc<dyn TaskStore>>,
+    users: &State<Arc<dyn UserStore>>,
+    notifier: &State<Notifier>,
+    user: AuthUser,
+    id: u32,
+    comment_id: u32,
+    text: Json<CommentText>,
+) -> Result<Json<CommentView>, ApiError> {
+    let mut text = text.into_inner();
+    text.validate().map_err(ApiError::Validation)?;
+    let task = visible_task(tasks.as_ref(), id, &user)?;
+    let mut comment = own_comment(store.as_ref(), id, comment_id, &user)?;
+
+    comment.body = text.body;
+    comment.edited_at = Some(Utc::now());
+    if !store.update_comment(comment.clone())? {
+        return Err(ApiError::NotFound(format!(
+            "comment {} does not exist",
+            comment_id
+        )));
+    }
+    notify_mentions(users.as_ref(), notifier, &user, &task, &comment)?;
+    Ok(Json(views(users.as_ref(), vec![comment])?.remove(0)))
+}
+
+/// Deletes one of your comments
+#[utoipa::path(
+    tag = "comments",
+    responses(
+        (status = 204, description = "The comment is deleted"),
+        (status = 403, description = "Another user wrote the comment", body = ErrorBody),
+        (status = 404, description = "No such task or comment, or not visible to you", body = ErrorBody)
+    )
+)]
+#[delete("/tasks/<id>/comments/<comment_id>")]
+pub fn delete_comment(
+    store: &State<Arc<dyn CommentStore>>,
+    tasks: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+    id: u32,
+    comment_id: u32,
+) -> Result<Status, ApiError> {
+    visible_task(tasks.as_ref(), id, &user)?;
+    own_comment(store.as_ref(), id, comment_id, &user)?;
+    if store.delete_comment(comment_id)? {
+        Ok(Status::NoContent)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "comment {} does not exist",
+            comment_id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_safe_html() {
+        assert_eq!(
+            render("**done** by _friday_"),
+            "<p><strong>done</strong> by <em>friday</em></p>\n"
+        );
+        let html = render(
+            "<script>alert(1)</script>\n\n[x](javascript:alert(1)) <img src=x onerror=alert(1)>",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("href"));
+        assert!(!html.contains("onerror"));
+        assert!(html.contains("<img src=\"x\">"));
+    }
+
+    #[test]
+    fn finds_mentions() {
+        assert_eq!(
+            mentions("@Bob can you and @carol.w look? cc @bob. Mail me at dave@example.com"),
+            ["bob", "carol.w"]
+        );
+        assert!(mentions("no one @ all").is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+//! Backend settings read from `Rocket.toml` / `ROCKET_*` environment variables
+//!
+//! Rocket reads its own settings (`port`, `address`, `log_level`, ...); the
+//! rest land in `BackendConfig`, which is loaded and checked once when the
+//! server ignites. Every invalid setting is reported, each as its own line
+//! in the log, and the server does not start.
+//!
+//! Relative storage paths are resolved against `data_dir`. A relative
+//! `data_dir` is itself taken relative to the `Rocket.toml` that sets it, or
+//! to the working directory when it comes from the environment, so the
+//! server finds its files wherever it is started from.
+
+use crate::auth::{self, AuthConfig};
+use crate::cors::CorsConfig;
+use crate::error::FieldError;
+use crate::notifications::NotificationConfig;
+use crate::recurring::RecurringConfig;
+use crate::store::StorageConfig;
+use crate::trash::TrashConfig;
+use crate::webhooks::WebhookConfig;
+use rocket::figment::value::magic::RelativePathBuf;
+use rocket::figment::Figment;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Shortest `auth.secret` accepted, in bytes
+pub const MIN_AUTH_SECRET_LEN: usize = 32;
+
+/// The settings the backend adds to Rocket's
+#[derive(Debug, Deserialize)]
+pub struct BackendConfig {
+    /// Directory relative storage paths are resolved against; created if
+    /// missing
+    #[serde(default = "default_data_dir")]
+    pub data_dir: RelativePathBuf,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub trash: TrashConfig,
+    #[serde(default)]
+    pub recurring: RecurringConfig,
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+}
+
+fn default_data_dir() -> RelativePathBuf {
+    RelativePathBuf::from(".")
+}
+
+impl BackendConfig {
+    /// Extracts the settings from `figment`, resolves the storage paths and
+    /// prepares the data directory
+    ///
+    /// Fails with every problem found, each named by its setting.
+    pub fn load(figment: &Figment) -> Result<Self, Vec<FieldError>> {
+        let mut config: BackendConfig = figment.extract().map_err(|e| {
+            e.into_iter()
+                .map(|e| {
+                    let field = if e.path.is_empty() {
+                        "config".to_string()
+                    } else {
+                        e.path.join(".")
+                    };
+                    FieldError::new(&field, e.kind.to_string())
+                })
+                .collect::<Vec<_>>()
+        })?;
+
+        let mut errors = config.validate();
+        let data_dir = config.data_dir();
+        match prepare_dir(&data_dir) {
+            Ok(()) => config.storage = config.storage.in_dir(&data_dir),
+            Err(message) => errors.push(FieldError::new("data_dir", message)),
+        }
+        if config.storage.path().is_dir() {
+            errors.push(FieldError::new(
+                "storage.path",
+                format!(
+                    "{} is a directory, not a file",
+                    config.storage.path().display()
+                ),
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// `data_dir`, resolved against the file that set it
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.relative()
+    }
+
+    /// Checks the settings that do not touch the file system
+    fn validate(&mut self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.auth.token_ttl_hours == 0 {
+            errors.push(FieldError::new(
+                "auth.token_ttl_hours",
+                "must be at least 1",
+            ));
+        }
+        if self
+            .auth
+            .secret
+            .as_ref()
+            .is_some_and(|secret| secret.len() < MIN_AUTH_SECRET_LEN)
+        {
+            errors.push(FieldError::new(
+                "auth.secret",
+                format!("must be at least {} characters", MIN_AUTH_SECRET_LEN),
+            ));
+        }
+        if self.trash.purge_interval_minutes == 0 {
+            errors.push(FieldError::new(
+                "trash.purge_interval_minutes",
+                "must be at least 1",
+            ));
+        }
+        if self.recurring.check_interval_secs == 0 {
+            errors.push(FieldError::new(
+                "recurring.check_interval_secs",
+                "must be at least 1",
+            ));
+        }
+        if self.webhooks.max_attempts == 0 {
+            errors.push(FieldError::new(
+                "webhooks.max_attempts",
+                "must be at least 1",
+            ));
+        }
+        if self.webhooks.timeout_secs == 0 {
+            errors.push(FieldError::new(
+                "webhooks.timeout_secs",
+                "must be at least 1",
+            ));
+        }
+        if self.notifications.check_interval_secs == 0 {
+            errors.push(FieldError::new(
+                "notifications.check_interval_secs",
+                "must be at least 1",
+            ));
+        }
+        if let Some(smtp) = &self.notifications.smtp {
+            if smtp.host.trim().is_empty() {
+                errors.push(FieldError::new(
+                    "notifications.smtp.host",
+                    "must not be empty",
+                ));
+            }
+            if !auth::is_email(&smtp.from) {
+                errors.push(FieldError::new(
+                    "notifications.smtp.from",
+                    "must be an e-mail address",
+                ));
+            }
+            if smtp.timeout_secs == 0 {
+                errors.push(FieldError::new(
+                    "notifications.smtp.timeout_secs",
+                    "must be at least 1",
+                ));
+            }
+        }
+
+        for (index, origin) in self.cors.allowed_origins.iter_mut().enumerate() {
+            match normalize_origin(origin) {
+                Ok(normalized) => *origin = normalized,
+                Err(message) => errors.push(FieldError::new(
+                    &format!("cors.allowed_origins[{}]", index),
+                    message,
+                )),
+            }
+        }
+        errors
+    }
+}
+
+/// `origin` as browsers send it in `Origin`: `scheme://host[:port]`
+fn normalize_origin(origin: &str) -> Result<String, String> {
+    if origin == "*" {
+        return Ok(origin.to_string());
+    }
+    let url =
+        reqwest::Url::parse(origin).map_err(|e| format!("'{}' is not an origin: {}", origin, e))?;
+    let bare = url.path() == "/" && url.query().is_none() && url.fragment().is_none();
+    if !matches!(url.scheme(), "http" | "https") || !url.has_host() || !bare {
+        return Err(format!(
+            "'{}' is not an origin; expected scheme://host[:port] or \"*\"",
+            origin
+        ));
+    }
+    Ok(url.origin().ascii_serialization())
+}
+
+/// Creates `dir` if needed and checks that files can be written in it
+fn prepare_dir(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    let probe = dir.join(".write-check");
+    fs::write(&probe, b"")
+        .and_then(|()| fs::remove_file(&probe))
+        .map_err(|e| format!("{} is not writable: {}", dir.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_paths_and_reports_every_bad_setting() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("nested/data");
+        let figment = Figment::new()
+            .merge(("data_dir", &data_dir))
+            .merge(("storage.kind", "sqlite"))
+            .merge(("storage.path", "tasks.db"))
+            .merge((
+                "cors.allowed_origins",
+                ["https://Tasks.example.com:443", "*"],
+            ));
+
+        let config = BackendConfig::load(&figment).unwrap();
+        assert!(data_dir.is_dir());
+        assert_eq!(config.storage.path(), data_dir.join("tasks.db"));
+        assert_eq!(
+            config.cors.allowed_origins,
+            ["https://tasks.example.com", "*"]
+        );
+
+        let figment = figment
+            .merge(("auth.secret", "short"))
+            .merge(("webhooks.max_attempts", 0))
+            .merge(("cors.allowed_origins", ["https://tasks.example.com/app"]));
+        let fields: Vec<String> = BackendConfig::load(&figment)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "auth.secret",
+                "webhooks.max_attempts",
+                "cors.allowed_origins[0]"
+            ]
+        );
+
+        let figment = Figment::new().merge(("storage.kind", "mongo"));
+        let errors = BackendConfig::load(&figment).unwrap_err();
+        assert_eq!(errors[0].field, "storage.kind");
+    }
+}
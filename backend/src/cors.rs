@@ -0,0 +1,80 @@
+//! Cross-origin access for browser clients
+//!
+//! The `cors` fairing adds `Access-Control-*` headers to responses for
+//! requests whose `Origin` is listed in `cors.allowed_origins`, and answers
+//! their preflight `OPTIONS` requests with 204 itself, so no route has to.
+//! Requests from other origins get no CORS headers, which browsers treat as
+//! a refusal.
+
+use rocket::fairing::AdHoc;
+use rocket::http::{Header, Method, Status};
+use serde::Deserialize;
+use std::io::Cursor;
+
+/// Methods a cross-origin page may use
+const ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE";
+/// Request headers a cross-origin page may send
+const ALLOWED_HEADERS: &str = "Authorization, Content-Type, If-Match, Last-Event-ID";
+/// Response headers a cross-origin page may read
+const EXPOSED_HEADERS: &str = "ETag, Location";
+/// Seconds a browser may cache a preflight answer
+const PREFLIGHT_MAX_AGE: &str = "86400";
+
+/// The `cors` section of `Rocket.toml`
+///
+/// ```toml
+/// [default.cors]
+/// allowed_origins = ["https://tasks.example.com"]
+/// ```
+///
+/// Browsers on other origins may only call the API if their origin is listed;
+/// `"*"` allows any. Empty by default, which sends no CORS headers at all.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CorsConfig {
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
+
+impl CorsConfig {
+    /// Whether a request from `origin` may read the response
+    pub fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+    }
+}
+
+/// Applies the `CorsConfig` managed as Rocket state to every response
+pub fn cors() -> AdHoc {
+    AdHoc::on_response("CORS", |req, res| {
+        Box::pin(async move {
+            let (Some(config), Some(origin)) = (
+                req.rocket().state::<CorsConfig>(),
+                req.headers().get_one("Origin"),
+            ) else {
+                return;
+            };
+            if !config.allows(origin) {
+                return;
+            }
+
+            res.set_header(Header::new(
+                "Access-Control-Allow-Origin",
+                origin.to_string(),
+            ));
+            res.adjoin_header(Header::new("Vary", "Origin"));
+            let preflight = req.method() == Method::Options
+                && req.headers().contains("Access-Control-Request-Method");
+            if preflight {
+                res.set_status(Status::NoContent);
+                res.set_sized_body(0, Cursor::new(""));
+                res.remove_header("Content-Type");
+                res.set_raw_header("Access-Control-Allow-Methods", ALLOWED_METHODS);
+                res.set_raw_header("Access-Control-Allow-Headers", ALLOWED_HEADERS);
+                res.set_raw_header("Access-Control-Max-Age", PREFLIGHT_MAX_AGE);
+            } else {
+                res.set_raw_header("Access-Control-Expose-Headers", EXPOSED_HEADERS);
+            }
+        })
+    })
+}
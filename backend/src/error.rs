@@ -0,0 +1,248 @@
+//! API errors and the JSON envelope they are rendered as
+//!
+//! Every error the backend returns, whether from a handler or a catcher,
+//! has the same shape:
+//!
+//! ```json
+//! { "code": "validation_failed", "message": "...", "details": [{ "field": "title", "message": "..." }] }
+//! ```
+//!
+//! `ErrorBody` is defined in `task_model` so clients can decode it.
+
+use crate::metrics::Metrics;
+use crate::store::StoreError;
+use crate::task::TransitionError;
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+use std::fmt;
+use std::sync::Arc;
+
+pub use task_model::error::{ErrorBody, FieldError};
+
+/// Errors returned by the task API
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request could not be read at all (400)
+    BadRequest(String),
+    /// No valid credentials or bearer token were given (401)
+    Unauthorized(String),
+    /// The signed-in user may see the resource but not change it (403)
+    Forbidden(String),
+    /// No such route or resource (404)
+    NotFound(String),
+    /// The request clashes with existing data (409)
+    Conflict(String),
+    /// The requested status change is not allowed (409)
+    InvalidTransition(TransitionError),
+    /// The task cannot be done while these blockers are open (409)
+    Blocked(Vec<u32>),
+    /// The subtask or blocker link would create a cycle (409)
+    LinkCycle(String),
+    /// `If-Match` named an older version, or another write got there first (412)
+    PreconditionFailed(String),
+    /// Some operations of a bulk request failed, so none were applied; the
+    /// status is that of the first failure
+    BulkRejected {
+        total: usize,
+        failures: Vec<(usize, ApiError)>,
+    },
+    /// Some rows of an import are invalid or clash with existing tasks, so
+    /// none were imported (409)
+    ImportRejected(Vec<FieldError>),
+    /// The request body is over the configured limit (413)
+    TooLarge(String),
+    /// The body or query string does not fit this route (422)
+    Unprocessable(String),
+    /// The body parsed but failed validation (422)
+    Validation(Vec<FieldError>),
+    /// The task store failed (500); details are logged, not returned
+    Store(StoreError),
+    /// Any other server-side failure (500)
+    Internal(String),
+    /// The server cannot serve requests right now (503)
+    Unavailable(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::Unauthorized(_) => Status::Unauthorized,
+            ApiError::Forbidden(_) => Status::Forbidden,
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::Conflict(_)
+            | ApiError::InvalidTransition(_)
+            | ApiError::Blocked(_)
+            | ApiError::LinkCycle(_)
+            | ApiError::ImportRejected(_) => Status::Conflict,
+            ApiError::PreconditionFailed(_) => Status::PreconditionFailed,
+            ApiError::BulkRejected { failures, .. } => failures
+                .first()
+                .map_or(Status::UnprocessableEntity, |(_, e)| e.status()),
+            ApiError::TooLarge(_) => Status::PayloadTooLarge,
+            ApiError::Unprocessable(_) | ApiError::Validation(_) => Status::UnprocessableEntity,
+            ApiError::Store(_) | ApiError::Internal(_) => Status::InternalServerError,
+            ApiError::Unavailable(_) => Status::ServiceUnavailable,
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        match self {
+            ApiError::BadRequest(message) => ErrorBody::new("bad_request", message.clone()),
+            ApiError::Unauthorized(message) => ErrorBody::new("unauthorized", message.clone()),
+            ApiError::Forbidden(message) => ErrorBody::new("forbidden", message.clone()),
+            ApiError::NotFound(message) => ErrorBody::new("not_found", message.clone()),
+            ApiError::Conflict(message) => ErrorBody::new("conflict", message.clone()),
+            ApiError::InvalidTransition(e) => ErrorBody {
+                details: vec![FieldError::new("status", e.to_string())],
+                ..ErrorBody::new("invalid_transition", e.to_string())
+            },
+            ApiError::Blocked(ids) => ErrorBody::new(
+                "blocked",
+                format!(
+                    "blocked by open tasks {}",
+                    ids.iter()
+                        .map(u32::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ),
+            ApiError::LinkCycle(message) => ErrorBody::new("link_cycle", message.clone()),
+            ApiError::PreconditionFailed(message) => {
+                ErrorBody::new("precondition_failed", message.clone())
+            }
+            ApiError::BulkRejected { total, failures } => ErrorBody {
+                details: failures
+                    .iter()
+                    .map(|(index, e)| {
+                        FieldError::new(&format!("operations[{}]", index), e.to_string())
+                    })
+                    .collect(),
+                ..ErrorBody::new(
+                    "bulk_rejected",
+                    format!(
+                        "{} of {} operations failed; none were applied",
+                        failures.len(),
+                        total
+                    ),
+                )
+            },
+            ApiError::ImportRejected(details) => ErrorBody {
+                details: details.clone(),
+                ..ErrorBody::new(
+                    "import_rejected",
+                    format!(
+                        "{} rows cannot be imported, so none were; \
+                         send dry_run=true for the full report",
+                        details.len()
+                    ),
+                )
+            },
+            ApiError::TooLarge(message) => ErrorBody::new("payload_too_large", message.clone()),
+            ApiError::Unprocessable(message) => ErrorBody::new("invalid_body", message.clone()),
+            ApiError::Validation(details) => ErrorBody {
+                details: details.clone(),
+                ..ErrorBody::new("validation_failed", "request body failed validation")
+            },
+            ApiError::Store(_) => ErrorBody::new("storage_error", "the task store failed"),
+            ApiError::Internal(message) => ErrorBody::new("internal_error", message.clone()),
+            ApiError::Unavailable(message) => ErrorBody::new("unavailable", message.clone()),
+        }
+    }
+
+    /// Shorthand for a missing task
+    pub fn task_not_found(id: u32) -> Self {
+        ApiError::NotFound(format!("task {} does not exist", id))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Store(e) => write!(f, "{}", e),
+            ApiError::Validation(details) => {
+                write!(f, "validation failed on {} field(s)", details.len())
+            }
+            other => write!(f, "{}", other.body().message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<StoreError> for ApiError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::DuplicateId(id) => {
+                ApiError::Conflict(format!("task {} already exists", id))
+            }
+            StoreError::DuplicateUsername(name) => {
+                ApiError::Conflict(format!("username '{}' is taken", name))
+            }
+            StoreError::StaleVersion(id) => ApiError::PreconditionFailed(format!(
+                "task {} was changed by another request; fetch it and try again",
+                id
+            )),
+            e => ApiError::Store(e),
+        }
+    }
+}
+
+impl From<TransitionError> for ApiError {
+    fn from(e: TransitionError) -> Self {
+        ApiError::InvalidTransition(e)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        if let ApiError::Store(e) = &self {
+            error!("{} {}: {}", req.method(), req.uri(), e);
+            if let Some(metrics) = req.rocket().state::<Arc<Metrics>>() {
+                metrics.record_storage_error(e);
+            }
+        }
+        let mut response = (self.status(), Json(self.body())).respond_to(req)?;
+        if let ApiError::Unauthorized(_) = self {
+            response.set_raw_header("WWW-Authenticate", "Bearer");
+        }
+        Ok(response)
+    }
+}
+
+#[catch(400)]
+pub fn bad_request(_req: &Request) -> ApiError {
+    ApiError::BadRequest("the request could not be read".to_string())
+}
+
+#[catch(401)]
+pub fn unauthorized(_req: &Request) -> ApiError {
+    ApiError::Unauthorized("a valid bearer token is required".to_string())
+}
+
+#[catch(404)]
+pub fn not_found(req: &Request) -> ApiError {
+    ApiError::NotFound(format!("no route for {} {}", req.method(), req.uri()))
+}
+
+#[catch(422)]
+pub fn unprocessable(_req: &Request) -> ApiError {
+    ApiError::Unprocessable("the request body or query is not valid for this route".to_string())
+}
+
+#[catch(500)]
+pub fn internal_error(_req: &Request) -> ApiError {
+    ApiError::Internal("the server hit an unexpected error".to_string())
+}
+
+/// Catches every other status with the same envelope
+#[catch(default)]
+pub fn default_catcher(status: Status, _req: &Request) -> (Status, Json<ErrorBody>) {
+    let code = status
+        .reason_lossy()
+        .to_lowercase()
+        .replace([' ', '-'], "_");
+    (status, Json(ErrorBody::new(&code, status.reason_lossy())))
+}
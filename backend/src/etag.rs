@@ -0,0 +1,92 @@
+//! Optimistic concurrency for tasks: `ETag` out, `If-Match` in
+//!
+//! Every task carries a `version` that the store bumps on each save. Routes
+//! returning a single task send it as a strong `ETag` (`"3"`); `PUT`, `PATCH`
+//! and `DELETE` on `/tasks/<id>` accept `If-Match` with that value and answer
+//! 412 `precondition_failed` once the task has moved on, so a client never
+//! overwrites a change it has not seen.
+
+use crate::error::ApiError;
+use crate::task::Task;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+
+/// The `If-Match` header of a request, if it sent one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IfMatch(pub Option<Vec<String>>);
+
+impl IfMatch {
+    /// A precondition on `version`, as if its `ETag` had been sent
+    pub fn version(version: u32) -> Self {
+        IfMatch(Some(vec![format!("\"{}\"", version)]))
+    }
+
+    /// Fails with 412 unless the header is absent, is `*`, or lists the
+    /// current `ETag` of `task`
+    ///
+    /// Weak tags (`W/"3"`) never match, as `If-Match` requires a strong
+    /// comparison.
+    pub fn check(&self, task: &Task) -> Result<(), ApiError> {
+        let Some(tags) = &self.0 else {
+            return Ok(());
+        };
+        let current = task.etag();
+        if tags.iter().any(|tag| tag == "*" || *tag == current) {
+            Ok(())
+        } else {
+            Err(ApiError::PreconditionFailed(format!(
+                "task {} is at version {}; fetch it and try again",
+                task.id, task.version
+            )))
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let tags: Vec<String> = req
+            .headers()
+            .get("If-Match")
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        Outcome::Success(IfMatch((!tags.is_empty()).then_some(tags)))
+    }
+}
+
+/// A task sent as JSON with its `ETag`
+#[derive(Debug)]
+pub struct Tagged(pub Task);
+
+impl<'r> Responder<'r, 'static> for Tagged {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let etag = self.0.etag();
+        let mut response = Json(self.0).respond_to(req)?;
+        response.set_raw_header("ETag", etag);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn matches_only_the_current_version() {
+        let mut task = Task::new(1, "edit".into(), String::new(), Utc::now());
+        task.version = 3;
+        let header = |tags: &[&str]| IfMatch(Some(tags.iter().map(|t| t.to_string()).collect()));
+
+        assert!(IfMatch(None).check(&task).is_ok());
+        assert!(header(&["*"]).check(&task).is_ok());
+        assert!(header(&["\"2\"", "\"3\""]).check(&task).is_ok());
+        assert!(header(&["\"2\""]).check(&task).is_err());
+        assert!(header(&["W/\"3\""]).check(&task).is_err());
+    }
+}
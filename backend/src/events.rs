@@ -0,0 +1,298 @@
+//! Live feed of task changes as Server-Sent Events
+//!
+//! Handlers that create, change or delete a task `publish` it to the
+//! `EventLog` managed as Rocket state, behind an `Arc` so background jobs can
+//! publish too. `GET /tasks/events` streams those events to each subscriber,
+//! filtered to the tasks that user can see. `Notifier` publishes
+//! `notification` events too, which go only to the user notified. The log keeps the most recent
+//! events so a client reconnecting with `Last-Event-ID` is sent what it
+//! missed; if that id has already been evicted, it gets a `resync` event
+//! telling it to refetch `GET /tasks`.
+
+use crate::auth::AuthUser;
+use crate::notifications::Notification;
+use crate::task::Task;
+use chrono::{DateTime, Utc};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::{Shutdown, State};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+/// Events kept for `Last-Event-ID` resumption
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// What happened to a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Created,
+    Updated,
+    Deleted,
+    /// A user was notified about the task
+    Notification,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Created => "created",
+            EventKind::Updated => "updated",
+            EventKind::Deleted => "deleted",
+            EventKind::Notification => "notification",
+        }
+    }
+}
+
+/// One entry of the feed; `task` is the state after the change, or the last
+/// state before a delete
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TaskEvent {
+    /// Increases by one with every event, starting at 1
+    pub id: u64,
+    pub kind: EventKind,
+    pub at: DateTime<Utc>,
+    pub task: Task,
+    /// Set on `notification` events only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification: Option<Notification>,
+}
+
+impl TaskEvent {
+    /// Whether user `user_id` is sent this event: a notification only goes
+    /// to its user, anything else to whoever can see the task
+    pub fn visible_to(&self, user_id: u32) -> bool {
+        match &self.notification {
+            Some(notification) => notification.user_id == user_id,
+            None => self.task.visible_to(user_id),
+        }
+    }
+}
+
+/// Events after a given id, as far as the log still has them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backlog {
+    Events(Vec<TaskEvent>),
+    /// Some events after the id were already evicted
+    Gap,
+}
+
+struct LogState {
+    events: VecDeque<TaskEvent>,
+    next_id: u64,
+}
+
+/// Bounded, in-memory record of recent task events
+pub struct EventLog {
+    capacity: usize,
+    state: Mutex<LogState>,
+    sender: broadcast::Sender<TaskEvent>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+        EventLog {
+            capacity,
+            state: Mutex::new(LogState {
+                events: VecDeque::with_capacity(capacity),
+                next_id: 1,
+            }),
+            sender,
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, LogState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records an event and sends it to every open stream
+    pub fn publish(&self, kind: EventKind, task: &Task) -> TaskEvent {
+        self.push(kind, task, None)
+    }
+
+    /// Records a `notification` event about `task` and sends it to every open
+    /// stream
+    pub fn publish_notification(&self, notification: &Notification, task: &Task) -> TaskEvent {
+        self.push(EventKind::Notification, task, Some(notification.clone()))
+    }
+
+    fn push(&self, kind: EventKind, task: &Task, notification: Option<Notification>) -> TaskEvent {
+        let mut state = self.state();
+        let event = TaskEvent {
+            id: state.next_id,
+            kind,
+            at: Utc::now(),
+            task: task.clone(),
+            notification,
+        };
+        state.next_id += 1;
+        if state.events.len() == self.capacity {
+            state.events.pop_front();
+        }
+        state.events.push_back(event.clone());
+
+        // Sent under the lock so subscribers see events in id order. An error
+        // only means nobody is listening.
+        let _ = self.sender.send(event.clone());
+        event
+    }
+
+    /// Events with an id greater than `last_id`
+    pub fn since(&self, last_id: u64) -> Backlog {
+        let state = self.state();
+        let oldest = state.events.front().map_or(state.next_id, |event| event.id);
+        // An id from the future was issued before a restart, or made up.
+        if last_id.saturating_add(1) < oldest || last_id >= state.next_id {
+            return Backlog::Gap;
+        }
+        Backlog::Events(
+            state
+                .events
+                .iter()
+                .filter(|event| event.id > last_id)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// A receiver for events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog::new(DEFAULT_CAPACITY)
+    }
+}
+
+/// The `Last-Event-ID` header browsers send when an event stream reconnects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = req
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|id| id.trim().parse().ok());
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+fn to_sse(event: &TaskEvent) -> Event {
+    Event::json(event)
+        .event(event.kind.as_str())
+        .id(event.id.to_string())
+}
+
+/// Streams task changes visible to the signed-in user
+///
+/// Events are named `created`, `updated`, `deleted` or `notification` and
+/// carry a `TaskEvent` as JSON.
+#[utoipa::path(
+    tag = "tasks",
+    params(("Last-Event-ID" = Option<u64>, Header, description = "Resume after this event")),
+    responses(
+        (status = 200, description = "Server-Sent Events named `created`, `updated`, `deleted`, `notification` or `resync`; each data line is a TaskEvent", content_type = "text/event-stream", body = String)
+    )
+)]
+#[get("/tasks/events")]
+pub fn task_events(
+    log: &State<Arc<EventLog>>,
+    user: AuthUser,
+    last_event_id: LastEventId,
+    mut shutdown: Shutdown,
+) -> EventStream![Event + '_] {
+    // Subscribe before reading the backlog so nothing falls between the two.
+    let mut receiver = log.subscribe();
+    let backlog = last_event_id.0.map(|id| (id, log.since(id)));
+
+    EventStream! {
+        let mut last_sent = 0;
+        match backlog {
+            Some((_, Backlog::Gap)) => yield Event::data("refetch GET /tasks").event("resync"),
+            Some((id, Backlog::Events(events))) => {
+                last_sent = id;
+                for event in events {
+                    last_sent = event.id;
+                    if event.visible_to(user.id) {
+                        yield to_sse(&event);
+                    }
+                }
+            }
+            None => {}
+        }
+
+        loop {
+            let event = select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => {
+                        yield Event::data("refetch GET /tasks").event("resync");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            if event.id <= last_sent {
+                continue;
+            }
+            last_sent = event.id;
+            if event.visible_to(user.id) {
+                yield to_sse(&event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: u32) -> Task {
+        Task::new(id, format!("task {}", id), String::new(), Utc::now())
+    }
+
+    #[test]
+    fn keeps_only_the_latest_events() {
+        let log = EventLog::new(3);
+        for id in 1..=5 {
+            log.publish(EventKind::Created, &task(id));
+        }
+
+        let ids = |backlog: Backlog| match backlog {
+            Backlog::Events(events) => events.iter().map(|e| e.id).collect::<Vec<_>>(),
+            Backlog::Gap => panic!("unexpected gap"),
+        };
+        assert_eq!(ids(log.since(2)), [3, 4, 5]);
+        assert_eq!(ids(log.since(4)), [5]);
+        assert_eq!(ids(log.since(5)), Vec::<u64>::new());
+        assert_eq!(log.since(1), Backlog::Gap);
+        assert_eq!(log.since(6), Backlog::Gap);
+        assert_eq!(log.since(u64::MAX), Backlog::Gap);
+    }
+
+    #[test]
+    fn subscribers_receive_new_events() {
+        let log = EventLog::default();
+        let mut receiver = log.subscribe();
+        log.publish(EventKind::Deleted, &task(9));
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.id, 1);
+        assert_eq!(event.kind, EventKind::Deleted);
+        assert_eq!(event.task.id, 9);
+    }
+}
@@ -0,0 +1,56 @@
+//! Liveness and readiness checks for load balancers: `GET /healthz` and
+//! `GET /readyz`
+//!
+//! `/healthz` answers as long as the server is serving requests at all.
+//! `/readyz` also reads and writes the task store, through
+//! `TaskStore::check`, and answers 503 while it cannot.
+
+use crate::error::{ApiError, ErrorBody};
+use crate::metrics::Metrics;
+use crate::store::TaskStore;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Body of a passing check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Health {
+    /// `ok` or `ready`
+    pub status: &'static str,
+}
+
+/// Whether the server is up
+#[utoipa::path(
+    tag = "operations",
+    security(()),
+    responses((status = 200, description = "The server is up", body = Health))
+)]
+#[get("/healthz")]
+pub fn healthz() -> Json<Health> {
+    Json(Health { status: "ok" })
+}
+
+/// Whether the server can serve requests, i.e. the task store can be read
+/// and written
+#[utoipa::path(
+    tag = "operations",
+    security(()),
+    responses(
+        (status = 200, description = "The task store is readable and writable", body = Health),
+        (status = 503, description = "The task store failed the check", body = ErrorBody)
+    )
+)]
+#[get("/readyz")]
+pub fn readyz(
+    store: &State<Arc<dyn TaskStore>>,
+    metrics: &State<Arc<Metrics>>,
+) -> Result<Json<Health>, ApiError> {
+    store.check().map_err(|e| {
+        error!("readiness check failed: {}", e);
+        metrics.record_storage_error(&e);
+        ApiError::Unavailable("the task store cannot be read or written".to_string())
+    })?;
+    Ok(Json(Health { status: "ready" }))
+}
@@ -0,0 +1,420 @@
+//! Audit log of task changes, `GET /tasks/<id>/history` and reverts
+//!
+//! Every handler that changes a task reports it through `Changes`, which
+//! appends a `Revision` to the `HistoryStore`, publishes the matching event
+//! to the live feed and hands it to the webhook `Dispatcher`; background jobs
+//! do the same through `record`. Revisions are never rewritten or removed,
+//! so a task's history survives its deletion.
+
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorBody};
+use crate::etag::{IfMatch, Tagged};
+use crate::events::{EventKind, EventLog};
+use crate::links;
+use crate::store::{HistoryStore, TaskStore, TaskWrite};
+use crate::task::Task;
+use crate::webhooks::Dispatcher;
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// What a revision did to its task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Created,
+    Updated,
+    /// Moved to the trash
+    Deleted,
+    /// Taken back out of the trash
+    Restored,
+    /// Removed from the trash for good
+    Purged,
+    /// Restored the state of an earlier revision
+    Reverted,
+}
+
+impl From<Action> for EventKind {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Created | Action::Restored => EventKind::Created,
+            Action::Updated | Action::Reverted => EventKind::Updated,
+            Action::Deleted | Action::Purged => EventKind::Deleted,
+        }
+    }
+}
+
+/// One field that differs between two states of a task
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// One entry of a task's audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Revision {
+    pub task_id: u32,
+    /// Numbered from 1 for each task
+    pub revision: u32,
+    pub at: DateTime<Utc>,
+    /// The user who made the change
+    pub user_id: Option<u32>,
+    pub action: Action,
+    /// For `Reverted`, the revision whose state was restored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverted_to: Option<u32>,
+    /// `None` when the task was created by this revision
+    pub before: Option<Task>,
+    /// `None` when the task was purged by this revision
+    pub after: Option<Task>,
+    pub changes: Vec<FieldChange>,
+}
+
+impl Revision {
+    /// A revision recording the move from `before` to `after`, not yet numbered
+    pub fn new(
+        user_id: Option<u32>,
+        action: Action,
+        before: Option<&Task>,
+        after: Option<&Task>,
+        at: DateTime<Utc>,
+    ) -> Self {
+        let task_id = after.or(before).map_or(0, |task| task.id);
+        Revision {
+            task_id,
+            revision: 0,
+            at,
+            user_id,
+            action,
+            reverted_to: None,
+            changes: diff(before, after),
+            before: before.cloned(),
+            after: after.cloned(),
+        }
+    }
+}
+
+/// Fields that differ between two states of a task, sorted by name
+///
+/// A missing state counts as every field being `null`. `updated_at` and
+/// `version` are left out since they change with every revision.
+pub fn diff(before: Option<&Task>, after: Option<&Task>) -> Vec<FieldChange> {
+    let fields = |task: Option<&Task>| match task.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => serde_json::Map::new(),
+    };
+    let before = fields(before);
+    let after = fields(after);
+
+    let mut names: Vec<&String> = after.keys().chain(before.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter(|name| !matches!(name.as_str(), "updated_at" | "version"))
+        .filter_map(|name| {
+            let old = before.get(name).cloned().unwrap_or(Value::Null);
+            let new = after.get(name).cloned().unwrap_or(Value::Null);
+            (old != new).then(|| FieldChange {
+                field: name.clone(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+/// Request guard that records task changes in the history and event feed,
+/// and sends them to webhooks
+pub struct Changes<'r> {
+    events: &'r EventLog,
+    history: &'r dyn HistoryStore,
+    webhooks: &'r Dispatcher,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Changes<'r> {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let rocket = req.rocket();
+        match (
+            rocket.state::<Arc<EventLog>>(),
+            rocket.state::<Arc<dyn HistoryStore>>(),
+            rocket.state::<Dispatcher>(),
+        ) {
+            (Some(events), Some(history), Some(webhooks)) => Outcome::Success(Changes {
+                events: events.as_ref(),
+                history: history.as_ref(),
+                webhooks,
+            }),
+            _ => {
+                let error = ApiError::Internal("task history is not configured".to_string());
+                Outcome::Error((Status::InternalServerError, error))
+            }
+        }
+    }
+}
+
+/// Appends `revision`, publishes it to the live feed and starts its webhook
+/// deliveries
+///
+/// The task itself is already saved by now, so a failure to write the audit
+/// log is logged rather than returned.
+pub fn record(
+    events: &EventLog,
+    history: &dyn HistoryStore,
+    webhooks: &Dispatcher,
+    revision: Revision,
+) {
+    if let Some(task) = revision.after.as_ref().or(revision.before.as_ref()) {
+        let event = events.publish(revision.action.into(), task);
+        webhooks.dispatch(event.id, &revision);
+    }
+    if let Err(e) = history.append(revision) {
+        error!("failed to record task history: {}", e);
+    }
+}
+
+impl Changes<'_> {
+    fn record(&self, revision: Revision) {
+        record(self.events, self.history, self.webhooks, revision);
+    }
+
+    pub fn created(&self, user: &AuthUser, task: &Task) {
+        self.record(Revision::new(
+            Some(user.id),
+            Action::Created,
+            None,
+            Some(task),
+            task.created_at,
+        ));
+    }
+
+    pub fn updated(&self, user: &AuthUser, before: &Task, after: &Task) {
+        self.record(Revision::new(
+            Some(user.id),
+            Action::Updated,
+            Some(before),
+            Some(after),
+            after.updated_at,
+        ));
+    }
+
+    /// `after` is the task as it sits in the trash
+    pub fn deleted(&self, user: &AuthUser, before: &Task, after: &Task) {
+        self.record(Revision::new(
+            Some(user.id),
+            Action::Deleted,
+            Some(before),
+            Some(after),
+            after.updated_at,
+        ));
+    }
+
+    pub fn restored(&self, user: &AuthUser, before: &Task, after: &Task) {
+        self.record(Revision::new(
+            Some(user.id),
+            Action::Restored,
+            Some(before),
+            Some(after),
+            after.updated_at,
+        ));
+    }
+
+    fn reverted(&self, user: &AuthUser, before: Option<&Task>, after: &Task, revision: u32) {
+        self.record(Revision {
+            reverted_to: Some(revision),
+            ..Revision::new(
+                Some(user.id),
+                Action::Reverted,
+                before,
+                Some(after),
+                after.updated_at,
+            )
+        });
+    }
+}
+
+/// Loads the history of task `id` if `user` may see the task, or could see
+/// it when it was last saved
+///
+/// Revisions older than the current task belong to an earlier task that had
+/// the same id, from before purged ids were retired, and are left out.
+fn visible_history(
+    store: &dyn TaskStore,
+    history: &dyn HistoryStore,
+    id: u32,
+    user: &AuthUser,
+) -> Result<(Option<Task>, Vec<Revision>), ApiError> {
+    let current = store.get(id)?;
+    let mut revisions = history.history(id)?;
+    if let Some(task) = &current {
+        revisions.retain(|revision| revision.at >= task.created_at);
+    }
+    let visible = match &current {
+        Some(task) => task.visible_to(user.id),
+        None => revisions
+            .iter()
+            .rev()
+            .find_map(|revision| revision.after.as_ref().or(revision.before.as_ref()))
+            .is_some_and(|task| task.visible_to(user.id)),
+    };
+
+    if visible {
+        Ok((current, revisions))
+    } else {
+        Err(ApiError::task_not_found(id))
+    }
+}
+
+/// Every recorded change to task `id`, oldest first
+#[utoipa::path(
+    tag = "tasks",
+    responses(
+        (status = 200, description = "Every revision, oldest first", body = [Revision]),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody)
+    )
+)]
+#[get("/tasks/<id>/history")]
+pub fn task_history(
+    store: &State<Arc<dyn TaskStore>>,
+    history: &State<Arc<dyn HistoryStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Json<Vec<Revision>>, ApiError> {
+    let (current, revisions) = visible_history(store.as_ref(), history.as_ref(), id, &user)?;
+    if current.is_none() && revisions.is_empty() {
+        return Err(ApiError::task_not_found(id));
+    }
+    Ok(Json(revisions))
+}
+
+/// Restores task `id` to its state after `revision`
+///
+/// A trashed task is taken out of the trash and a purged one is recreated
+/// under the same id. Links to tasks that no longer exist are dropped, and
+/// the restored state must still satisfy the cycle and blocker rules.
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the task is still at this ETag")),
+    responses(
+        (status = 200, description = "The restored task", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 409, description = "The restored state breaks a link or blocker rule", body = ErrorBody),
+        (status = 412, description = "The task has changed since the If-Match version", body = ErrorBody)
+    )
+)]
+#[post("/tasks/<id>/history/<revision>/revert")]
+pub fn revert_task(
+    store: &State<Arc<dyn TaskStore>>,
+    history: &State<Arc<dyn HistoryStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+    revision: u32,
+) -> Result<Tagged, ApiError> {
+    let store = store.as_ref();
+    let (current, revisions) = visible_history(store, history.as_ref(), id, &user)?;
+    if current
+        .as_ref()
+        .is_some_and(|task| !task.editable_by(user.id))
+    {
+        return Err(ApiError::Forbidden(format!(
+            "task {} belongs to another user",
+            id
+        )));
+    }
+    match &current {
+        Some(task) => if_match.check(task)?,
+        // A purged task has no version left to match.
+        None if if_match.0.is_some() => {
+            return Err(ApiError::PreconditionFailed(format!(
+                "task {} was purged; revert it without If-Match",
+                id
+            )))
+        }
+        None => {}
+    }
+
+    let target = revisions
+        .iter()
+        .find(|entry| entry.revision == revision)
+        .ok_or_else(|| ApiError::NotFound(format!("task {} has no revision {}", id, revision)))?;
+    let snapshot = target
+        .after
+        .clone()
+        .filter(|task| !task.is_trashed())
+        .ok_or_else(|| {
+            ApiError::Conflict(format!(
+                "revision {} deleted task {}; revert to an earlier revision",
+                revision, id
+            ))
+        })?;
+    if !snapshot.editable_by(user.id) {
+        return Err(ApiError::Forbidden(format!(
+            "task {} belongs to another user",
+            id
+        )));
+    }
+
+    let now = Utc::now();
+    let mut task = snapshot;
+    links::repair_links(store, &mut task)?;
+
+    match &current {
+        Some(current) => {
+            task.version = current.version;
+            task.touch(now);
+            if !store.update(task.clone())? {
+                return Err(ApiError::task_not_found(id));
+            }
+        }
+        None => {
+            // The purged task comes back as a fresh row under its old id.
+            task.version = 1;
+            task.updated_at = now;
+            store.commit(vec![TaskWrite::Insert(task.clone())])?;
+        }
+    }
+
+    changes.reverted(&user, current.as_ref(), &task, revision);
+    Ok(Tagged(task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::Priority;
+
+    #[test]
+    fn diff_lists_changed_fields() {
+        let now = Utc::now();
+        let before = Task::new(4, "draft".into(), "old".into(), now);
+        let mut after = before.clone();
+        after.title = "final".into();
+        after.priority = Priority::High;
+        after.updated_at = now + chrono::Duration::seconds(5);
+
+        let changes = diff(Some(&before), Some(&after));
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, ["priority", "title"]);
+        assert_eq!(changes[1].before, "draft");
+        assert_eq!(changes[1].after, "final");
+
+        let deleted = diff(Some(&before), None);
+        assert!(deleted.iter().all(|change| change.after.is_null()));
+        assert!(deleted.iter().any(|change| change.field == "id"));
+    }
+}
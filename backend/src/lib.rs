@@ -0,0 +1,448 @@
+//! Task backend: a small REST API over a pluggable `TaskStore`
+
+#[macro_use]
+extern crate rocket;
+
+pub mod auth;
+pub mod bulk;
+pub mod clock;
+pub mod comments;
+pub mod config;
+pub mod cors;
+pub mod error;
+pub mod etag;
+pub mod events;
+pub mod health;
+pub mod history;
+pub mod links;
+pub mod metrics;
+pub mod notifications;
+pub mod openapi;
+pub mod projects;
+pub mod query;
+pub mod recurring;
+pub mod reminders;
+pub mod store;
+pub mod tags;
+pub mod transfer;
+pub mod trash;
+pub mod webhooks;
+
+pub use task_model::{recurrence, reminder, tag, task};
+
+use auth::{editable_task, visible_task, AuthUser, TokenSigner};
+use chrono::Utc;
+use config::BackendConfig;
+use error::{ApiError, ErrorBody};
+use etag::{IfMatch, Tagged};
+use events::EventLog;
+use history::Changes;
+use metrics::{Metrics, RequestMetrics};
+use notifications::Notifier;
+use query::{TaskPage, TaskQuery};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::{routes, Build, Rocket, State};
+use std::sync::Arc;
+use store::{ProjectStore, TaskStore};
+use task::{NewTask, Task, TaskPatch, TaskStatus, TaskUpdate};
+use webhooks::Dispatcher;
+
+/// Lists the tasks the signed-in user owns or can see through sharing
+#[utoipa::path(
+    tag = "tasks",
+    params(TaskQuery),
+    responses(
+        (status = 200, description = "One page of tasks", body = TaskPage),
+        (status = 422, description = "A query parameter is invalid", body = ErrorBody)
+    )
+)]
+#[get("/tasks?<query..>")]
+fn fetch_tasks(
+    store: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+    query: TaskQuery,
+) -> Result<Json<TaskPage>, ApiError> {
+    let today = Utc::now().date_naive();
+    let mut query = query.validate(today).map_err(ApiError::Validation)?;
+    query.viewer = Some(user.id);
+    Ok(Json(store.query(&query)?))
+}
+
+#[utoipa::path(
+    tag = "tasks",
+    responses(
+        (status = 200, description = "The task", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody)
+    )
+)]
+#[get("/tasks/<id>")]
+fn get_task(
+    store: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Tagged, ApiError> {
+    visible_task(store.as_ref(), id, &user).map(Tagged)
+}
+
+#[utoipa::path(
+    tag = "tasks",
+    responses(
+        (status = 201, description = "The new task", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 409, description = "The requested id is or was taken", body = ErrorBody),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[post("/tasks", data = "<task>")]
+fn create_task(
+    store: &State<Arc<dyn TaskStore>>,
+    projects: &State<Arc<dyn ProjectStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    task: Json<NewTask>,
+) -> Result<Created<Tagged>, ApiError> {
+    let task = insert_task(store.as_ref(), projects.as_ref(), &user, task.into_inner())?;
+    changes.created(&user, &task);
+    Ok(Created::new(uri!(get_task(task.id)).to_string()).body(Tagged(task)))
+}
+
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the task is still at this ETag")),
+    responses(
+        (status = 200, description = "The updated task", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 409, description = "The status change is not allowed, or open blockers remain", body = ErrorBody),
+        (status = 412, description = "The task has changed since the If-Match version", body = ErrorBody),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[put("/tasks/<id>", data = "<updated_task>")]
+fn update_task(
+    store: &State<Arc<dyn TaskStore>>,
+    projects: &State<Arc<dyn ProjectStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+    updated_task: Json<TaskUpdate>,
+) -> Result<Tagged, ApiError> {
+    let mut updated_task = updated_task.into_inner();
+    updated_task.validate().map_err(ApiError::Validation)?;
+
+    apply_patch(
+        store.as_ref(),
+        projects.as_ref(),
+        &changes,
+        &user,
+        &if_match,
+        id,
+        updated_task.into_patch(),
+    )
+}
+
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the task is still at this ETag")),
+    responses(
+        (status = 200, description = "The updated task", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 409, description = "The status change is not allowed, or open blockers remain", body = ErrorBody),
+        (status = 412, description = "The task has changed since the If-Match version", body = ErrorBody),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[patch("/tasks/<id>", data = "<patch>")]
+fn patch_task(
+    store: &State<Arc<dyn TaskStore>>,
+    projects: &State<Arc<dyn ProjectStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+    patch: Json<TaskPatch>,
+) -> Result<Tagged, ApiError> {
+    let mut patch = patch.into_inner();
+    patch.validate().map_err(ApiError::Validation)?;
+
+    apply_patch(
+        store.as_ref(),
+        projects.as_ref(),
+        &changes,
+        &user,
+        &if_match,
+        id,
+        patch,
+    )
+}
+
+/// Validates `task` and stores it as owned by `user`, in one of their
+/// projects if it names one
+///
+/// A chosen id must be one the store has never handed out, so a client
+/// cannot take over the id, and with it the history, of a deleted task.
+fn insert_task(
+    store: &dyn TaskStore,
+    projects: &dyn ProjectStore,
+    user: &AuthUser,
+    mut task: NewTask,
+) -> Result<Task, ApiError> {
+    task.validate().map_err(ApiError::Validation)?;
+    match task.id {
+        Some(id) if id < store.next_id()? => {
+            return Err(ApiError::Conflict(format!(
+                "task id {} is taken; leave out `id` to get a free one",
+                id
+            )))
+        }
+        _ => {}
+    }
+    projects::check_project(projects, user, task.project_id)?;
+    task.owner_id = Some(user.id);
+    Ok(store.insert(task, Utc::now())?)
+}
+
+/// Saves `patch` to task `id` and records the change
+fn apply_patch(
+    store: &dyn TaskStore,
+    projects: &dyn ProjectStore,
+    changes: &Changes<'_>,
+    user: &AuthUser,
+    if_match: &IfMatch,
+    id: u32,
+    patch: TaskPatch,
+) -> Result<Tagged, ApiError> {
+    let (before, task) = save_patch(store, projects, user, if_match, id, patch)?;
+    changes.updated(user, &before, &task);
+    Ok(Tagged(task))
+}
+
+/// Loads task `id`, applies `patch` and saves it, returning the task before
+/// and after
+///
+/// Enforces ownership, `If-Match` and the status workflow, refuses to
+/// complete a task that still has open blockers, and only moves a task into
+/// one of `user`'s projects.
+fn save_patch(
+    store: &dyn TaskStore,
+    projects: &dyn ProjectStore,
+    user: &AuthUser,
+    if_match: &IfMatch,
+    id: u32,
+    patch: TaskPatch,
+) -> Result<(Task, Task), ApiError> {
+    let before = editable_task(store, id, user)?;
+    if_match.check(&before)?;
+    if let Some(project_id) = patch.project_id.filter(|id| *id != before.project_id) {
+        projects::check_project(projects, user, project_id)?;
+    }
+    let mut task = before.clone();
+    if patch.status == Some(TaskStatus::Done) && !task.is_done() {
+        let open = links::open_blockers(store, &task)?;
+        if !open.is_empty() {
+            return Err(ApiError::Blocked(open));
+        }
+    }
+
+    task.apply(patch, Utc::now())?;
+    if store.update(task.clone())? {
+        Ok((before, task))
+    } else {
+        Err(ApiError::task_not_found(id))
+    }
+}
+
+/// Moves task `id` to the trash; see `trash::restore_task`
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the task is still at this ETag")),
+    responses(
+        (status = 204, description = "The task is in the trash"),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 412, description = "The task has changed since the If-Match version", body = ErrorBody)
+    )
+)]
+#[delete("/tasks/<id>")]
+fn delete_task(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+) -> Result<Status, ApiError> {
+    let (before, task) = trash_task(store.as_ref(), &user, &if_match, id)?;
+    changes.deleted(&user, &before, &task);
+    Ok(Status::NoContent)
+}
+
+/// Stamps task `id` as deleted, returning it before and after
+fn trash_task(
+    store: &dyn TaskStore,
+    user: &AuthUser,
+    if_match: &IfMatch,
+    id: u32,
+) -> Result<(Task, Task), ApiError> {
+    let before = editable_task(store, id, user)?;
+    if_match.check(&before)?;
+    let now = Utc::now();
+    let mut task = before.clone();
+    task.deleted_at = Some(now);
+    task.touch(now);
+    if store.update(task.clone())? {
+        Ok((before, task))
+    } else {
+        Err(ApiError::task_not_found(id))
+    }
+}
+
+/// Loads the `BackendConfig` and opens the configured stores
+///
+/// Every store, the token signer, the webhook dispatcher, the notifier and
+/// the config sections the fairings read become Rocket managed state.
+fn attach_store() -> AdHoc {
+    AdHoc::try_on_ignite("Task Store", |rocket: Rocket<Build>| async move {
+        let config = match BackendConfig::load(rocket.figment()) {
+            Ok(config) => config,
+            Err(errors) => {
+                for e in errors {
+                    error!("invalid setting `{}`: {}", e.field, e.message);
+                }
+                return Err(rocket);
+            }
+        };
+        info!("storing tasks in {}", config.storage.path().display());
+
+        let stores = config.storage.open().and_then(|tasks| {
+            Ok((
+                tasks,
+                config.storage.open_users()?,
+                config.storage.open_history()?,
+                config.storage.open_webhooks()?,
+                config.storage.open_notifications()?,
+                config.storage.open_projects()?,
+                config.storage.open_comments()?,
+            ))
+        });
+        let (tasks, users, history, webhooks, notifications, projects, comments) = match stores {
+            Ok(stores) => stores,
+            Err(e) => {
+                error!("failed to open task store {:?}: {}", config.storage, e);
+                return Err(rocket);
+            }
+        };
+        let dispatcher = match Dispatcher::new(Arc::clone(&webhooks), config.webhooks) {
+            Ok(dispatcher) => dispatcher,
+            Err(e) => {
+                error!("failed to set up webhook delivery: {}", e);
+                return Err(rocket);
+            }
+        };
+        let events = match rocket.state::<Arc<EventLog>>() {
+            Some(events) => Arc::clone(events),
+            None => {
+                error!("the event log must be managed before the store is attached");
+                return Err(rocket);
+            }
+        };
+        let notifier = Notifier::new(
+            Arc::clone(&notifications),
+            Arc::clone(&users),
+            events,
+            config.notifications.smtp.clone(),
+        );
+
+        Ok(rocket
+            .manage(tasks)
+            .manage(users)
+            .manage(history)
+            .manage(webhooks)
+            .manage(dispatcher)
+            .manage(notifications)
+            .manage(notifier)
+            .manage(projects)
+            .manage(comments)
+            .manage(TokenSigner::from_config(&config.auth))
+            .manage(config.trash)
+            .manage(config.recurring)
+            .manage(config.notifications)
+            .manage(config.cors))
+    })
+}
+
+/// Builds the task API using configuration from `Rocket.toml` and the environment
+pub fn rocket() -> Rocket<Build> {
+    rocket::build()
+        .attach(RequestMetrics)
+        .attach(attach_store())
+        .attach(trash::purge_on_timer())
+        .attach(recurring::schedule())
+        .attach(reminders::schedule())
+        .attach(cors::cors())
+        .manage(Arc::new(EventLog::default()))
+        .manage(Arc::new(Metrics::default()))
+        .mount(
+            "/",
+            routes![
+                fetch_tasks,
+                events::task_events,
+                get_task,
+                create_task,
+                bulk::bulk_tasks,
+                transfer::export_tasks,
+                transfer::import_tasks,
+                update_task,
+                patch_task,
+                delete_task,
+                links::add_subtask,
+                links::remove_subtask,
+                links::add_blocker,
+                links::remove_blocker,
+                links::task_tree,
+                history::task_history,
+                history::revert_task,
+                comments::list_comments,
+                comments::create_comment,
+                comments::edit_comment,
+                comments::delete_comment,
+                trash::trashed_tasks,
+                trash::restore_task,
+                webhooks::create_webhook,
+                webhooks::list_webhooks,
+                webhooks::get_webhook,
+                webhooks::delete_webhook,
+                webhooks::webhook_deliveries,
+                notifications::list_notifications,
+                projects::create_project,
+                projects::list_projects,
+                projects::get_project,
+                projects::delete_project,
+                tags::list_tags,
+                tags::rename_tag,
+                tags::merge_tag,
+                openapi::openapi_json,
+                openapi::docs,
+                health::healthz,
+                health::readyz,
+                metrics::metrics,
+                auth::register,
+                auth::login,
+                auth::me
+            ],
+        )
+        .register(
+            "/",
+            catchers![
+                error::bad_request,
+                error::unauthorized,
+                error::not_found,
+                error::unprocessable,
+                error::internal_error,
+                error::default_catcher
+            ],
+        )
+}
@@ -0,0 +1,443 @@
+//! Subtasks and "blocked by" dependencies between tasks
+//!
+//! Both relationships are kept acyclic: a task cannot end up as its own
+//! ancestor, and a chain of blockers cannot lead back to where it started.
+//! A task with an open blocker cannot be moved to done.
+
+use crate::auth::{editable_task, visible_task, AuthUser};
+use crate::error::{ApiError, ErrorBody};
+use crate::etag::{IfMatch, Tagged};
+use crate::history::Changes;
+use crate::store::{StoreError, TaskStore};
+use crate::task::{Task, TaskStatus};
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Id, title and status of a related task
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TaskSummary {
+    pub id: u32,
+    pub title: String,
+    pub status: TaskStatus,
+}
+
+impl From<&Task> for TaskSummary {
+    fn from(task: &Task) -> Self {
+        TaskSummary {
+            id: task.id,
+            title: task.title.clone(),
+            status: task.status,
+        }
+    }
+}
+
+/// A task with its subtasks, recursively, and the tasks blocking it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TaskTree {
+    pub task: Task,
+    pub blockers: Vec<TaskSummary>,
+    #[schema(no_recursion)]
+    pub subtasks: Vec<TaskTree>,
+}
+
+fn index(tasks: &[Task]) -> HashMap<u32, &Task> {
+    tasks.iter().map(|task| (task.id, task)).collect()
+}
+
+/// Whether making `parent` the parent of `child` would make `child` its own ancestor
+pub fn creates_parent_cycle(tasks: &[Task], child: u32, parent: u32) -> bool {
+    let tasks = index(tasks);
+    let mut seen = HashSet::new();
+    let mut current = Some(parent);
+
+    while let Some(id) = current {
+        if id == child {
+            return true;
+        }
+        if !seen.insert(id) {
+            break;
+        }
+        current = tasks.get(&id).and_then(|task| task.parent_id);
+    }
+    false
+}
+
+/// Whether letting `blocker` block `task` would close a loop of blockers
+pub fn creates_blocker_cycle(tasks: &[Task], task: u32, blocker: u32) -> bool {
+    let tasks = index(tasks);
+    let mut seen = HashSet::new();
+    let mut stack = vec![blocker];
+
+    while let Some(id) = stack.pop() {
+        if id == task {
+            return true;
+        }
+        if seen.insert(id) {
+            if let Some(current) = tasks.get(&id) {
+                stack.extend(&current.blocked_by);
+            }
+        }
+    }
+    false
+}
+
+/// Builds the subtree rooted at `root`
+///
+/// Blockers that no longer exist are left out.
+pub fn build_tree(tasks: &[Task], root: &Task) -> TaskTree {
+    let by_id = index(tasks);
+    let mut children: HashMap<u32, Vec<&Task>> = HashMap::new();
+    for task in tasks {
+        if let Some(parent) = task.parent_id {
+            children.entry(parent).or_default().push(task);
+        }
+    }
+
+    fn build(
+        task: &Task,
+        by_id: &HashMap<u32, &Task>,
+        children: &HashMap<u32, Vec<&Task>>,
+        seen: &mut HashSet<u32>,
+    ) -> TaskTree {
+        seen.insert(task.id);
+        let mut subtasks = Vec::new();
+        for child in children.get(&task.id).into_iter().flatten() {
+            if !seen.contains(&child.id) {
+                subtasks.push(build(child, by_id, children, seen));
+            }
+        }
+
+        TaskTree {
+            task: task.clone(),
+            blockers: task
+                .blocked_by
+                .iter()
+                .filter_map(|id| by_id.get(id))
+                .map(|blocker| TaskSummary::from(*blocker))
+                .collect(),
+            subtasks,
+        }
+    }
+
+    build(root, &by_id, &children, &mut HashSet::new())
+}
+
+/// Ids of the tasks blocking `task` that are not done yet
+///
+/// Blockers in the trash no longer count.
+pub fn open_blockers(store: &dyn TaskStore, task: &Task) -> Result<Vec<u32>, StoreError> {
+    let mut open = Vec::new();
+    for &id in &task.blocked_by {
+        if store
+            .get(id)?
+            .is_some_and(|blocker| !blocker.is_done() && !blocker.is_trashed())
+        {
+            open.push(id);
+        }
+    }
+    Ok(open)
+}
+
+/// Drops links to tasks that no longer exist from a task being brought back,
+/// and rejects it if it would then break the cycle or blocker rules
+pub(crate) fn repair_links(store: &dyn TaskStore, task: &mut Task) -> Result<(), ApiError> {
+    let others: Vec<Task> = store
+        .list()?
+        .into_iter()
+        .filter(|other| other.id != task.id)
+        .collect();
+    let exists = |id: u32| others.iter().any(|other| other.id == id);
+
+    task.parent_id = task.parent_id.filter(|&parent| exists(parent));
+    task.blocked_by.retain(|&blocker| exists(blocker));
+
+    if let Some(parent) = task.parent_id {
+        if creates_parent_cycle(&others, task.id, parent) {
+            return Err(ApiError::LinkCycle(format!(
+                "restoring task {} under task {} would create a cycle",
+                task.id, parent
+            )));
+        }
+    }
+    for &blocker in &task.blocked_by {
+        if creates_blocker_cycle(&others, task.id, blocker) {
+            return Err(ApiError::LinkCycle(format!(
+                "restoring task {} as blocked by task {} would create a cycle",
+                task.id, blocker
+            )));
+        }
+    }
+
+    if task.is_done() {
+        let open = open_blockers(store, task)?;
+        if !open.is_empty() {
+            return Err(ApiError::Blocked(open));
+        }
+    }
+    Ok(())
+}
+
+/// Removes every reference to a purged task from the tasks that remain
+///
+/// Its subtasks become top-level tasks and it stops blocking anything.
+/// Returns each changed task before and after the change.
+pub fn detach(store: &dyn TaskStore, id: u32) -> Result<Vec<(Task, Task)>, StoreError> {
+    let mut changed = Vec::new();
+    for before in store.list()? {
+        let mut task = before.clone();
+        let was_child = task.parent_id == Some(id);
+        let was_blocked = task.blocked_by.contains(&id);
+        if was_child || was_blocked {
+            if was_child {
+                task.parent_id = None;
+            }
+            task.blocked_by.retain(|&blocker| blocker != id);
+            task.touch(Utc::now());
+            store.update(task.clone())?;
+            changed.push((before, task));
+        }
+    }
+    Ok(changed)
+}
+
+/// Saves a task whose links changed and records the change
+fn save(
+    store: &dyn TaskStore,
+    changes: &Changes<'_>,
+    user: &AuthUser,
+    before: &Task,
+    mut task: Task,
+) -> Result<Tagged, ApiError> {
+    task.touch(Utc::now());
+    if store.update(task.clone())? {
+        changes.updated(user, before, &task);
+        Ok(Tagged(task))
+    } else {
+        Err(ApiError::task_not_found(task.id))
+    }
+}
+
+/// Makes `child` a subtask of `id`
+///
+/// The caller must be able to change `child` and see `id`.
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the subtask is still at this ETag")),
+    responses(
+        (status = 200, description = "The subtask", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 409, description = "The link would create a cycle", body = ErrorBody),
+        (status = 412, description = "The subtask has changed since the If-Match version", body = ErrorBody)
+    )
+)]
+#[put("/tasks/<id>/subtasks/<child>")]
+pub fn add_subtask(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+    child: u32,
+) -> Result<Tagged, ApiError> {
+    let store = store.as_ref();
+    visible_task(store, id, &user)?;
+    let before = editable_task(store, child, &user)?;
+    if_match.check(&before)?;
+    let mut task = before.clone();
+
+    if creates_parent_cycle(&store.list()?, child, id) {
+        return Err(ApiError::LinkCycle(format!(
+            "making task {} a subtask of task {} would create a cycle",
+            child, id
+        )));
+    }
+
+    task.parent_id = Some(id);
+    save(store, &changes, &user, &before, task)
+}
+
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the subtask is still at this ETag")),
+    responses(
+        (status = 204, description = "The subtask is detached"),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 412, description = "The subtask has changed since the If-Match version", body = ErrorBody)
+    )
+)]
+#[delete("/tasks/<id>/subtasks/<child>")]
+pub fn remove_subtask(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+    child: u32,
+) -> Result<Status, ApiError> {
+    let store = store.as_ref();
+    let before = editable_task(store, child, &user)?;
+    if_match.check(&before)?;
+    let mut task = before.clone();
+    if task.parent_id != Some(id) {
+        return Err(ApiError::NotFound(format!(
+            "task {} is not a subtask of task {}",
+            child, id
+        )));
+    }
+
+    task.parent_id = None;
+    save(store, &changes, &user, &before, task)?;
+    Ok(Status::NoContent)
+}
+
+/// Records that `id` cannot be done until `blocker` is
+///
+/// The caller must be able to change `id` and see `blocker`.
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the task is still at this ETag")),
+    responses(
+        (status = 200, description = "The blocked task", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 409, description = "The link would create a cycle", body = ErrorBody),
+        (status = 412, description = "The task has changed since the If-Match version", body = ErrorBody)
+    )
+)]
+#[put("/tasks/<id>/blockers/<blocker>")]
+pub fn add_blocker(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+    blocker: u32,
+) -> Result<Tagged, ApiError> {
+    let store = store.as_ref();
+    let before = editable_task(store, id, &user)?;
+    if_match.check(&before)?;
+    let mut task = before.clone();
+    visible_task(store, blocker, &user)?;
+
+    if task.blocked_by.contains(&blocker) {
+        return Ok(Tagged(task));
+    }
+    if creates_blocker_cycle(&store.list()?, id, blocker) {
+        return Err(ApiError::LinkCycle(format!(
+            "letting task {} block task {} would create a cycle",
+            blocker, id
+        )));
+    }
+
+    task.blocked_by.push(blocker);
+    save(store, &changes, &user, &before, task)
+}
+
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the task is still at this ETag")),
+    responses(
+        (status = 204, description = "The blocker is removed"),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 412, description = "The task has changed since the If-Match version", body = ErrorBody)
+    )
+)]
+#[delete("/tasks/<id>/blockers/<blocker>")]
+pub fn remove_blocker(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+    blocker: u32,
+) -> Result<Status, ApiError> {
+    let store = store.as_ref();
+    let before = editable_task(store, id, &user)?;
+    if_match.check(&before)?;
+    let mut task = before.clone();
+    if !task.blocked_by.contains(&blocker) {
+        return Err(ApiError::NotFound(format!(
+            "task {} is not blocked by task {}",
+            id, blocker
+        )));
+    }
+
+    task.blocked_by.retain(|&other| other != blocker);
+    save(store, &changes, &user, &before, task)?;
+    Ok(Status::NoContent)
+}
+
+/// The tree below `id`, leaving out tasks the caller cannot see
+#[utoipa::path(
+    tag = "tasks",
+    responses(
+        (status = 200, description = "The task with its blockers and subtasks", body = TaskTree),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody)
+    )
+)]
+#[get("/tasks/<id>/tree")]
+pub fn task_tree(
+    store: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Json<TaskTree>, ApiError> {
+    let store = store.as_ref();
+    let root = visible_task(store, id, &user)?;
+    let mut tasks = store.list()?;
+    tasks.retain(|task| task.visible_to(user.id) && !task.is_trashed());
+    Ok(Json(build_tree(&tasks, &root)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks() -> Vec<Task> {
+        let now = Utc::now();
+        let mut tasks: Vec<Task> = (1..=4)
+            .map(|id| Task::new(id, format!("task {}", id), String::new(), now))
+            .collect();
+        // 1 <- 2 <- 3 as parent chain, 4 blocked by 3 which is blocked by 2
+        tasks[1].parent_id = Some(1);
+        tasks[2].parent_id = Some(2);
+        tasks[3].blocked_by = vec![3];
+        tasks[2].blocked_by = vec![2];
+        tasks
+    }
+
+    #[test]
+    fn detects_parent_cycles() {
+        let tasks = tasks();
+        assert!(creates_parent_cycle(&tasks, 1, 3));
+        assert!(creates_parent_cycle(&tasks, 2, 2));
+        assert!(!creates_parent_cycle(&tasks, 3, 1));
+        assert!(!creates_parent_cycle(&tasks, 4, 3));
+    }
+
+    #[test]
+    fn detects_blocker_cycles() {
+        let tasks = tasks();
+        assert!(creates_blocker_cycle(&tasks, 2, 4));
+        assert!(creates_blocker_cycle(&tasks, 1, 1));
+        assert!(!creates_blocker_cycle(&tasks, 4, 1));
+    }
+
+    #[test]
+    fn builds_nested_tree() {
+        let tasks = tasks();
+        let tree = build_tree(&tasks, &tasks[0]);
+        assert_eq!(tree.subtasks.len(), 1);
+        assert_eq!(tree.subtasks[0].task.id, 2);
+        assert_eq!(tree.subtasks[0].subtasks[0].task.id, 3);
+        assert_eq!(tree.subtasks[0].subtasks[0].blockers[0].id, 2);
+    }
+}
@@ -0,0 +1,271 @@
+//! Prometheus metrics: `GET /metrics`
+//!
+//! The `RequestMetrics` fairing times every request and counts it under the
+//! route that answered it (`unmatched` when none did), its method and the
+//! response status. Storage failures are counted by `StoreError::kind` as
+//! they are turned into 500s, and by the readiness check and trash purge.
+//! Task counts are read from the store on each scrape.
+
+use crate::store::{StoreError, TaskStore};
+use crate::task::{Task, TaskStatus};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::{Data, Request, Response, State};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+/// Upper bounds, in seconds, of the request duration histogram buckets
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Counters collected since startup, managed as `Arc<Metrics>`
+#[derive(Default)]
+pub struct Metrics {
+    state: Mutex<MetricsState>,
+}
+
+#[derive(Default)]
+struct MetricsState {
+    /// Requests by (method, route, status)
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// Durations by (method, route)
+    durations: BTreeMap<(String, String), Histogram>,
+    /// Storage errors by `StoreError::kind`
+    storage_errors: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations at or below each of `DURATION_BUCKETS`
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+impl Metrics {
+    fn state(&self) -> MutexGuard<'_, MetricsState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Counts one answered request
+    pub fn record_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        let mut state = self.state();
+        *state
+            .requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default() += 1;
+        state
+            .durations
+            .entry((method.to_string(), route.to_string()))
+            .or_default()
+            .observe(seconds);
+    }
+
+    /// Counts one failure of a store
+    pub fn record_storage_error(&self, error: &StoreError) {
+        *self.state().storage_errors.entry(error.kind()).or_default() += 1;
+    }
+
+    /// The metrics in the Prometheus text exposition format, with task
+    /// counts from `tasks` if the store could be read
+    pub fn render(&self, tasks: Option<&[Task]>) -> String {
+        let state = self.state();
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total Requests answered, by route, method and status\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((method, route, status), count) in &state.requests {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(method),
+                escape(route),
+                status,
+                count
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds Time taken to answer requests, by route and method\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((method, route), histogram) in &state.durations {
+            let labels = format!("method=\"{}\",route=\"{}\"", escape(method), escape(route));
+            for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+
+        out.push_str(
+            "# HELP storage_errors_total Failed reads and writes of the stores, by kind\n",
+        );
+        out.push_str("# TYPE storage_errors_total counter\n");
+        for (kind, count) in &state.storage_errors {
+            let _ = writeln!(out, "storage_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+
+        if let Some(tasks) = tasks {
+            out.push_str("# HELP tasks Tasks outside the trash, by status\n");
+            out.push_str("# TYPE tasks gauge\n");
+            let live: Vec<&Task> = tasks
+                .iter()
+                .filter(|task| task.deleted_at.is_none())
+                .collect();
+            for status in TaskStatus::ALL {
+                let count = live.iter().filter(|task| task.status == status).count();
+                let _ = writeln!(out, "tasks{{status=\"{}\"}} {}", status, count);
+            }
+            out.push_str("# HELP tasks_trashed Tasks in the trash\n");
+            out.push_str("# TYPE tasks_trashed gauge\n");
+            let _ = writeln!(out, "tasks_trashed {}", tasks.len() - live.len());
+        }
+        out
+    }
+}
+
+/// Escapes a label value as the exposition format requires
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// When the current request arrived, cached on the request
+struct RequestStart(Instant);
+
+/// Records every request in the managed `Arc<Metrics>`
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(metrics) = req.rocket().state::<Arc<Metrics>>() else {
+            return;
+        };
+        let started = req.local_cache(|| RequestStart(Instant::now()));
+        let route = req.route().map_or("unmatched", |route| route.uri.path());
+        metrics.record_request(
+            req.method().as_str(),
+            route,
+            res.status().code,
+            started.0.elapsed().as_secs_f64(),
+        );
+    }
+}
+
+/// Request, task and storage error metrics in the Prometheus text format
+#[utoipa::path(
+    tag = "operations",
+    security(()),
+    responses((status = 200, description = "Prometheus text exposition format, version 0.0.4", content_type = "text/plain"))
+)]
+#[get("/metrics")]
+pub fn metrics(
+    metrics: &State<Arc<Metrics>>,
+    store: &State<Arc<dyn TaskStore>>,
+) -> (ContentType, String) {
+    let tasks = match store.list() {
+        Ok(tasks) => Some(tasks),
+        Err(e) => {
+            error!("failed to count tasks for metrics: {}", e);
+            metrics.record_storage_error(&e);
+            None
+        }
+    };
+    let content_type =
+        ContentType::new("text", "plain").with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    (content_type, metrics.render(tasks.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::NewTask;
+    use chrono::Utc;
+
+    #[test]
+    fn renders_counters_histograms_and_task_counts() {
+        let metrics = Metrics::default();
+        metrics.record_request("GET", "/tasks/<id>", 200, 0.02);
+        metrics.record_request("GET", "/tasks/<id>", 200, 3.0);
+        metrics.record_request("GET", "/tasks/<id>", 404, 0.001);
+        metrics.record_storage_error(&StoreError::Io(std::io::Error::other("disk full")));
+
+        let now = Utc::now();
+        let new_task = |status: TaskStatus| -> NewTask {
+            serde_json::from_value(serde_json::json!({ "title": "t", "status": status })).unwrap()
+        };
+        let mut trashed = new_task(TaskStatus::Done).into_task(3, now);
+        trashed.deleted_at = Some(now);
+        let tasks = [
+            new_task(TaskStatus::Todo).into_task(1, now),
+            new_task(TaskStatus::Done).into_task(2, now),
+            trashed,
+        ];
+
+        let text = metrics.render(Some(&tasks));
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            "http_requests_total{method=\"GET\",route=\"/tasks/<id>\",status=\"200\"} 2",
+            "http_requests_total{method=\"GET\",route=\"/tasks/<id>\",status=\"404\"} 1",
+            "http_request_duration_seconds_bucket{method=\"GET\",route=\"/tasks/<id>\",le=\"0.005\"} 1",
+            "http_request_duration_seconds_bucket{method=\"GET\",route=\"/tasks/<id>\",le=\"2.5\"} 2",
+            "http_request_duration_seconds_bucket{method=\"GET\",route=\"/tasks/<id>\",le=\"+Inf\"} 3",
+            "http_request_duration_seconds_count{method=\"GET\",route=\"/tasks/<id>\"} 3",
+            "storage_errors_total{kind=\"io\"} 1",
+            "tasks{status=\"todo\"} 1",
+            "tasks{status=\"in_progress\"} 0",
+            "tasks{status=\"done\"} 1",
+            "tasks_trashed 1",
+        ] {
+            assert!(lines.contains(&expected), "missing {:?} in\n{}", expected, text);
+        }
+
+        assert!(!metrics.render(None).contains("tasks{"));
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
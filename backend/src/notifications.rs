@@ -0,0 +1,261 @@
+//! Notifications to users: the outbox, the live feed and e-mail
+//!
+//! Something worth telling a user about, a reminder firing or a mention in a
+//! comment, becomes a `Notification` handed to the `Notifier` managed as Rocket state. It is
+//! appended to the `NotificationStore` outbox under a key naming what it is
+//! about, and a notification whose key is already there is dropped, so each
+//! is sent once however often it is raised. A new one is published as a
+//! `notification` event on `GET /tasks/events`, seen only by its user, and,
+//! when `notifications.smtp` is set and the user registered an e-mail
+//! address, mailed to them. `GET /notifications` lists what the signed-in
+//! user was sent.
+
+use crate::auth::AuthUser;
+use crate::error::ApiError;
+use crate::events::EventLog;
+use crate::store::{NotificationStore, StoreError, UserStore};
+use crate::task::Task;
+use chrono::{DateTime, Utc};
+use rocket::serde::json::Json;
+use rocket::tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use rocket::tokio::net::TcpStream;
+use rocket::tokio::time;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// What a notification is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// One of the task's reminders fired
+    Reminder,
+    /// Someone mentioned the user in a comment on the task
+    Mention,
+}
+
+/// A message sent to one user about one task
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Notification {
+    /// Assigned by the outbox, increasing from 1
+    pub id: u64,
+    pub user_id: u32,
+    pub task_id: u32,
+    pub kind: NotificationKind,
+    pub message: String,
+    pub at: DateTime<Utc>,
+    /// Names what the notification is about; the outbox keeps one per key
+    pub key: String,
+}
+
+/// The `notifications` section of `Rocket.toml`
+///
+/// ```toml
+/// [default.notifications]
+/// check_interval_secs = 60
+///
+/// [default.notifications.smtp]
+/// host = "localhost"
+/// port = 25
+/// from = "tasks@localhost"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotificationConfig {
+    /// How often reminders are checked for
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: u64,
+    /// Mail server to send notifications through; none sends no mail
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
+}
+
+fn default_check_interval_secs() -> u64 {
+    60
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        NotificationConfig {
+            check_interval_secs: default_check_interval_secs(),
+            smtp: None,
+        }
+    }
+}
+
+/// A plain SMTP relay, such as a local test mail server; no TLS or login
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    /// Sender address of every mail
+    pub from: String,
+    /// Longest a whole conversation with the server may take
+    #[serde(default = "default_smtp_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_smtp_port() -> u16 {
+    25
+}
+
+fn default_smtp_timeout_secs() -> u64 {
+    10
+}
+
+/// Sends notifications to the outbox, the live feed and by mail; managed as
+/// Rocket state
+///
+/// Clones share the stores and the event log.
+#[derive(Clone)]
+pub struct Notifier {
+    store: Arc<dyn NotificationStore>,
+    users: Arc<dyn UserStore>,
+    events: Arc<EventLog>,
+    smtp: Option<SmtpConfig>,
+}
+
+impl Notifier {
+    pub fn new(
+        store: Arc<dyn NotificationStore>,
+        users: Arc<dyn UserStore>,
+        events: Arc<EventLog>,
+        smtp: Option<SmtpConfig>,
+    ) -> Self {
+        Notifier {
+            store,
+            users,
+            events,
+            smtp,
+        }
+    }
+
+    /// Sends `notification` about `task` unless its key was sent before,
+    /// returning it as stored if it was sent
+    ///
+    /// Mail goes out in the background, so this must run inside the Tokio
+    /// runtime; a failure to send it is logged, not returned.
+    pub fn notify(
+        &self,
+        notification: Notification,
+        task: &Task,
+    ) -> Result<Option<Notification>, StoreError> {
+        let Some(notification) = self.store.append(notification)? else {
+            return Ok(None);
+        };
+        self.events.publish_notification(&notification, task);
+
+        if let Some(smtp) = &self.smtp {
+            match self.users.get_user(notification.user_id) {
+                Ok(Some(user)) => {
+                    if let Some(to) = user.email {
+                        rocket::tokio::spawn(mail(smtp.clone(), to, notification.clone()));
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => error!("failed to look up user {}: {}", notification.user_id, e),
+            }
+        }
+        Ok(Some(notification))
+    }
+}
+
+/// Mails `notification` to `to`, logging a failure
+async fn mail(smtp: SmtpConfig, to: String, notification: Notification) {
+    let timeout = time::Duration::from_secs(smtp.timeout_secs.max(1));
+    let result = match time::timeout(timeout, send_mail(&smtp, &to, &notification)).await {
+        Ok(result) => result,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")),
+    };
+    if let Err(e) = result {
+        warn!(
+            "failed to mail notification {} to {} via {}:{}: {}",
+            notification.id, to, smtp.host, smtp.port, e
+        );
+    }
+}
+
+/// Holds one SMTP conversation delivering `notification` to `to`
+async fn send_mail(smtp: &SmtpConfig, to: &str, notification: &Notification) -> io::Result<()> {
+    let stream = TcpStream::connect((smtp.host.as_str(), smtp.port)).await?;
+    let mut stream = BufReader::new(stream);
+
+    reply(&mut stream, 220).await?;
+    command(&mut stream, "EHLO localhost", 250).await?;
+    command(&mut stream, &format!("MAIL FROM:<{}>", smtp.from), 250).await?;
+    command(&mut stream, &format!("RCPT TO:<{}>", to), 250).await?;
+    command(&mut stream, "DATA", 354).await?;
+
+    let mut message = format!(
+        "From: <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n",
+        smtp.from,
+        to,
+        notification.message.replace(['\r', '\n'], " "),
+        notification.at.to_rfc2822(),
+    );
+    for line in notification.message.lines() {
+        // A leading dot is doubled so the line cannot end the message.
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push_str(".\r\n");
+    stream.get_mut().write_all(message.as_bytes()).await?;
+    reply(&mut stream, 250).await?;
+
+    command(&mut stream, "QUIT", 221).await
+}
+
+/// Sends `line` and waits for a reply with code `expected`
+async fn command(stream: &mut BufReader<TcpStream>, line: &str, expected: u16) -> io::Result<()> {
+    stream
+        .get_mut()
+        .write_all(format!("{}\r\n", line).as_bytes())
+        .await?;
+    reply(stream, expected).await
+}
+
+/// Reads one reply, all of its lines, and checks its code is `expected`
+async fn reply(stream: &mut BufReader<TcpStream>, expected: u16) -> io::Result<()> {
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed the connection",
+            ));
+        }
+        // Every line but the last of a reply has `-` after the code.
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        return match line.get(..3).and_then(|code| code.parse::<u16>().ok()) {
+            Some(code) if code == expected => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "expected {}, got '{}'",
+                expected,
+                line.trim_end()
+            ))),
+        };
+    }
+}
+
+/// Lists the notifications sent to the signed-in user, oldest first
+#[utoipa::path(
+    tag = "notifications",
+    responses(
+        (status = 200, description = "The user's notifications", body = [Notification])
+    )
+)]
+#[get("/notifications")]
+pub fn list_notifications(
+    store: &State<Arc<dyn NotificationStore>>,
+    user: AuthUser,
+) -> Result<Json<Vec<Notification>>, ApiError> {
+    Ok(Json(store.notifications(user.id)?))
+}
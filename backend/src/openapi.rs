@@ -0,0 +1,159 @@
+//! OpenAPI 3 description of the API, `GET /openapi.json` and `GET /docs`
+//!
+//! Each route carries a `#[utoipa::path]` annotation above its Rocket
+//! attribute; utoipa reads the method, path and parameters from the Rocket
+//! attribute, and the schemas from `ToSchema` derives on the request and
+//! response types. `ApiDoc` lists the annotated routes. The
+//! `openapi_matches_the_mounted_routes` API test fails when a mounted route
+//! is missing from the document or the document names one that is not
+//! mounted.
+
+use crate::error::ErrorBody;
+use rocket::response::content::RawHtml;
+use rocket::serde::json::Json;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, RefOr, ResponseBuilder};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+/// The generated document; see `spec`
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Task API",
+        description = "Tasks with subtasks, blockers, projects, tags, comments, history, a trash, webhooks and notifications"
+    ),
+    paths(
+        crate::auth::register,
+        crate::auth::login,
+        crate::auth::me,
+        crate::fetch_tasks,
+        crate::events::task_events,
+        crate::get_task,
+        crate::create_task,
+        crate::bulk::bulk_tasks,
+        crate::transfer::export_tasks,
+        crate::transfer::import_tasks,
+        crate::update_task,
+        crate::patch_task,
+        crate::delete_task,
+        crate::links::add_subtask,
+        crate::links::remove_subtask,
+        crate::links::add_blocker,
+        crate::links::remove_blocker,
+        crate::links::task_tree,
+        crate::history::task_history,
+        crate::history::revert_task,
+        crate::comments::list_comments,
+        crate::comments::create_comment,
+        crate::comments::edit_comment,
+        crate::comments::delete_comment,
+        crate::trash::trashed_tasks,
+        crate::trash::restore_task,
+        crate::webhooks::create_webhook,
+        crate::webhooks::list_webhooks,
+        crate::webhooks::get_webhook,
+        crate::webhooks::delete_webhook,
+        crate::webhooks::webhook_deliveries,
+        crate::notifications::list_notifications,
+        crate::projects::create_project,
+        crate::projects::list_projects,
+        crate::projects::get_project,
+        crate::projects::delete_project,
+        crate::tags::list_tags,
+        crate::tags::rename_tag,
+        crate::tags::merge_tag,
+        openapi_json,
+        docs,
+        crate::health::healthz,
+        crate::health::readyz,
+        crate::metrics::metrics,
+    ),
+    components(schemas(crate::events::TaskEvent, crate::webhooks::WebhookPayload)),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Accounts and bearer tokens"),
+        (name = "tasks", description = "Tasks and their links, history and trash"),
+        (name = "comments", description = "Discussion on tasks, with @mentions"),
+        (name = "projects", description = "Projects to file tasks under, with their task counts"),
+        (name = "tags", description = "The tags on tasks, renamed or merged across them"),
+        (name = "webhooks", description = "Task events pushed to registered URLs"),
+        (name = "notifications", description = "Reminders and mentions sent to the signed-in user"),
+        (name = "docs", description = "This document"),
+        (name = "operations", description = "Health checks and metrics for load balancers and monitoring"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Requires a bearer token on every operation that does not set its own
+/// `security`, and documents the 401 it answers without one
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+
+        let unauthorized = ResponseBuilder::new()
+            .description("Missing, invalid or expired bearer token")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(RefOr::Ref(utoipa::openapi::Ref::from_schema_name(
+                        ErrorBody::name(),
+                    ))))
+                    .build(),
+            )
+            .build();
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                if operation.security.is_none() {
+                    operation.security = Some(vec![SecurityRequirement::new(
+                        "bearer",
+                        Vec::<String>::new(),
+                    )]);
+                    operation
+                        .responses
+                        .responses
+                        .insert("401".to_string(), unauthorized.clone().into());
+                }
+            }
+        }
+    }
+}
+
+/// The OpenAPI document for every route `rocket()` mounts
+pub fn spec() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+/// This API described as OpenAPI 3
+#[utoipa::path(
+    tag = "docs",
+    security(()),
+    responses((status = 200, description = "The OpenAPI document", content_type = "application/json"))
+)]
+#[get("/openapi.json")]
+pub fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(spec())
+}
+
+/// A browsable rendering of `/openapi.json`
+#[utoipa::path(
+    tag = "docs",
+    security(()),
+    responses((status = 200, description = "An HTML page", content_type = "text/html"))
+)]
+#[get("/docs")]
+pub fn docs() -> RawHtml<&'static str> {
+    RawHtml(include_str!("docs.html"))
+}
@@ -0,0 +1,260 @@
+//! Projects: `/projects` and filing tasks under them
+//!
+//! Each user keeps their own projects, created with `POST /projects`. A task
+//! joins one by setting `project_id` to one of its editor's projects, and
+//! `GET /tasks?project=<id>` lists the tasks filed under it. The listing and
+//! `GET /projects/<id>` count a project's tasks by status, leaving out the
+//! trash. Deleting a project keeps its tasks, taking them out of it.
+
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::history::Changes;
+use crate::store::{ProjectStore, TaskStore, TaskWrite};
+use crate::task::{Task, TaskStatus};
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Longest accepted project name, in characters
+pub const MAX_NAME_LEN: usize = 100;
+
+/// A project as stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub id: u32,
+    pub owner_id: u32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `POST /projects`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewProject {
+    pub name: String,
+}
+
+impl NewProject {
+    /// Trims the name and checks its length
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        self.name = self.name.trim().to_string();
+        if self.name.is_empty() {
+            Err(vec![FieldError::new("name", "must not be empty")])
+        } else if self.name.chars().count() > MAX_NAME_LEN {
+            Err(vec![FieldError::new(
+                "name",
+                format!("must be at most {} characters", MAX_NAME_LEN),
+            )])
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// How many of a project's tasks are in each status
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TaskCounts {
+    pub total: usize,
+    pub todo: usize,
+    pub in_progress: usize,
+    pub blocked: usize,
+    pub done: usize,
+}
+
+impl TaskCounts {
+    fn add(&mut self, task: &Task) {
+        self.total += 1;
+        match task.status {
+            TaskStatus::Todo => self.todo += 1,
+            TaskStatus::InProgress => self.in_progress += 1,
+            TaskStatus::Blocked => self.blocked += 1,
+            TaskStatus::Done => self.done += 1,
+        }
+    }
+}
+
+/// A project as listed, with its task counts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProjectSummary {
+    pub id: u32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Tasks filed under the project, leaving out the trash
+    pub tasks: TaskCounts,
+}
+
+/// Summarizes `projects`, counting their tasks among `tasks`
+fn summarize(projects: Vec<Project>, tasks: &[Task]) -> Vec<ProjectSummary> {
+    projects
+        .into_iter()
+        .map(|project| {
+            let mut counts = TaskCounts::default();
+            for task in tasks {
+                if task.project_id == Some(project.id) && !task.is_trashed() {
+                    counts.add(task);
+                }
+            }
+            ProjectSummary {
+                id: project.id,
+                name: project.name,
+                created_at: project.created_at,
+                tasks: counts,
+            }
+        })
+        .collect()
+}
+
+/// Loads project `id` if it belongs to `user`
+///
+/// Other users' projects answer 404, as if they did not exist.
+fn owned_project(store: &dyn ProjectStore, id: u32, user: &AuthUser) -> Result<Project, ApiError> {
+    store
+        .projects()?
+        .into_iter()
+        .find(|project| project.id == id && project.owner_id == user.id)
+        .ok_or_else(|| ApiError::NotFound(format!("project {} does not exist", id)))
+}
+
+/// Checks that `user` may file a task under `project_id`, which must be
+/// none or one of their projects
+pub fn check_project(
+    store: &dyn ProjectStore,
+    user: &AuthUser,
+    project_id: Option<u32>,
+) -> Result<(), ApiError> {
+    let Some(id) = project_id else {
+        return Ok(());
+    };
+    match owned_project(store, id, user) {
+        Err(ApiError::NotFound(message)) => Err(ApiError::Validation(vec![FieldError::new(
+            "project_id",
+            message,
+        )])),
+        result => result.map(|_| ()),
+    }
+}
+
+/// Creates a project for the signed-in user
+#[utoipa::path(
+    tag = "projects",
+    responses(
+        (status = 201, description = "The new project", body = ProjectSummary),
+        (status = 409, description = "You already have a project by that name", body = ErrorBody),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[post("/projects", data = "<project>")]
+pub fn create_project(
+    store: &State<Arc<dyn ProjectStore>>,
+    user: AuthUser,
+    project: Json<NewProject>,
+) -> Result<Created<Json<ProjectSummary>>, ApiError> {
+    let mut project = project.into_inner();
+    project.validate().map_err(ApiError::Validation)?;
+    let taken = store.projects()?.into_iter().any(|existing| {
+        existing.owner_id == user.id && existing.name.to_lowercase() == project.name.to_lowercase()
+    });
+    if taken {
+        return Err(ApiError::Conflict(format!(
+            "you already have a project named '{}'",
+            project.name
+        )));
+    }
+
+    let project = store.insert_project(Project {
+        id: 0,
+        owner_id: user.id,
+        name: project.name,
+        created_at: Utc::now(),
+    })?;
+    let summary = summarize(vec![project], &[]).remove(0);
+    Ok(Created::new(uri!(get_project(summary.id)).to_string()).body(Json(summary)))
+}
+
+/// Lists the signed-in user's projects with their task counts
+#[utoipa::path(
+    tag = "projects",
+    responses(
+        (status = 200, description = "Your projects", body = [ProjectSummary])
+    )
+)]
+#[get("/projects")]
+pub fn list_projects(
+    store: &State<Arc<dyn ProjectStore>>,
+    tasks: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+) -> Result<Json<Vec<ProjectSummary>>, ApiError> {
+    let projects = store
+        .projects()?
+        .into_iter()
+        .filter(|project| project.owner_id == user.id)
+        .collect();
+    Ok(Json(summarize(projects, &tasks.list()?)))
+}
+
+#[utoipa::path(
+    tag = "projects",
+    responses(
+        (status = 200, description = "The project", body = ProjectSummary),
+        (status = 404, description = "No such project of yours", body = ErrorBody)
+    )
+)]
+#[get("/projects/<id>")]
+pub fn get_project(
+    store: &State<Arc<dyn ProjectStore>>,
+    tasks: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Json<ProjectSummary>, ApiError> {
+    let project = owned_project(store.as_ref(), id, &user)?;
+    Ok(Json(summarize(vec![project], &tasks.list()?).remove(0)))
+}
+
+/// Deletes a project, taking its tasks, trashed ones too, out of it
+#[utoipa::path(
+    tag = "projects",
+    responses(
+        (status = 204, description = "The project is deleted"),
+        (status = 404, description = "No such project of yours", body = ErrorBody),
+        (status = 412, description = "One of its tasks changed meanwhile", body = ErrorBody)
+    )
+)]
+#[delete("/projects/<id>")]
+pub fn delete_project(
+    store: &State<Arc<dyn ProjectStore>>,
+    tasks: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Status, ApiError> {
+    owned_project(store.as_ref(), id, &user)?;
+
+    let now = Utc::now();
+    let mut updated = Vec::new();
+    for before in tasks.list()? {
+        if before.project_id == Some(id) {
+            let mut after = before.clone();
+            after.project_id = None;
+            after.touch(now);
+            updated.push((before, after));
+        }
+    }
+    let writes = updated
+        .iter()
+        .map(|(_, after)| TaskWrite::Update(after.clone()))
+        .collect();
+    tasks.commit(writes)?;
+    for (before, after) in &updated {
+        changes.updated(&user, before, after);
+    }
+
+    if store.delete_project(id)? {
+        Ok(Status::NoContent)
+    } else {
+        Err(ApiError::NotFound(format!("project {} does not exist", id)))
+    }
+}
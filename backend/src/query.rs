@@ -0,0 +1,530 @@
+//! Filtering, sorting and pagination for `GET /tasks`
+//!
+//! `TaskQuery` is read from the query string, checked with `validate`, and
+//! applied to a list of tasks with `ListQuery::apply`. Stores get this for
+//! free through `TaskStore::query`.
+
+use crate::error::FieldError;
+use crate::tag;
+use crate::task::{Priority, Task, TaskStatus};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use utoipa::IntoParams;
+
+pub use task_model::page::{TaskPage, DEFAULT_LIMIT, MAX_LIMIT};
+
+/// Raw query string of `GET /tasks`, e.g.
+/// `?completed=false&q=spider&sort=-id&limit=20&cursor=<next_cursor>`
+///
+/// `completed=true` is shorthand for `status=done`, `completed=false` for any
+/// other status. `project` is a project id and `tag` matches tags the way
+/// they are stored, so `?tag=Errands` finds `errands`.
+#[derive(Debug, Clone, Default, FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaskQuery {
+    pub completed: Option<bool>,
+    pub status: Option<String>,
+    pub priority: Option<String>,
+    pub overdue: Option<bool>,
+    pub q: Option<String>,
+    pub project: Option<u32>,
+    pub tag: Option<String>,
+    pub sort: Option<String>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+/// Task fields that can be sorted on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Id,
+    Title,
+    Status,
+    Priority,
+    DueDate,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// A sort field and direction, written `field` or `-field` for descending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Sort {
+            field: SortField::Id,
+            descending: false,
+        }
+    }
+}
+
+impl std::str::FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (descending, name) = match s.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, s),
+        };
+        let field = match name {
+            "id" => SortField::Id,
+            "title" => SortField::Title,
+            "status" => SortField::Status,
+            "priority" => SortField::Priority,
+            "due_date" => SortField::DueDate,
+            "created_at" => SortField::CreatedAt,
+            "updated_at" => SortField::UpdatedAt,
+            other => return Err(format!("cannot sort by '{}'", other)),
+        };
+        Ok(Sort { field, descending })
+    }
+}
+
+/// The value a task is sorted by
+///
+/// Titles compare case-insensitively, and tasks without a due date sort
+/// after those with one.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    Id,
+    Title(String),
+    Status(TaskStatus),
+    Priority(Priority),
+    /// Whether the date is missing, then the date
+    DueDate(bool, Option<NaiveDate>),
+    CreatedAt(DateTime<Utc>),
+    UpdatedAt(DateTime<Utc>),
+}
+
+impl SortKey {
+    fn field(&self) -> SortField {
+        match self {
+            SortKey::Id => SortField::Id,
+            SortKey::Title(_) => SortField::Title,
+            SortKey::Status(_) => SortField::Status,
+            SortKey::Priority(_) => SortField::Priority,
+            SortKey::DueDate(..) => SortField::DueDate,
+            SortKey::CreatedAt(_) => SortField::CreatedAt,
+            SortKey::UpdatedAt(_) => SortField::UpdatedAt,
+        }
+    }
+}
+
+/// Where a task falls in a listing: its sort key, with ties broken by id
+///
+/// A cursor is the position of the last task on the previous page, so a
+/// page starts in the right place even if tasks were added or removed
+/// since.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Position {
+    pub key: SortKey,
+    pub id: u32,
+}
+
+impl Position {
+    /// The opaque `cursor` form of this position
+    pub fn cursor(&self) -> String {
+        let json = serde_json::to_vec(self).expect("positions always serialize");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    fn from_cursor(cursor: &str) -> Option<Position> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+impl Sort {
+    pub fn position(&self, task: &Task) -> Position {
+        let key = match self.field {
+            SortField::Id => SortKey::Id,
+            SortField::Title => SortKey::Title(task.title.to_lowercase()),
+            SortField::Status => SortKey::Status(task.status),
+            SortField::Priority => SortKey::Priority(task.priority),
+            SortField::DueDate => SortKey::DueDate(task.due_date.is_none(), task.due_date),
+            SortField::CreatedAt => SortKey::CreatedAt(task.created_at),
+            SortField::UpdatedAt => SortKey::UpdatedAt(task.updated_at),
+        };
+        Position { key, id: task.id }
+    }
+
+    /// Orders two positions in this sort's direction
+    fn order(&self, a: &Position, b: &Position) -> Ordering {
+        if self.descending {
+            b.cmp(a)
+        } else {
+            a.cmp(b)
+        }
+    }
+
+    /// Orders two tasks; ties are broken by id so pages are stable
+    fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        self.order(&self.position(a), &self.position(b))
+    }
+}
+
+/// A validated `TaskQuery`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListQuery {
+    pub completed: Option<bool>,
+    pub status: Option<TaskStatus>,
+    pub priority: Option<Priority>,
+    /// Only tasks that are (or are not) overdue as of `today`
+    pub overdue: Option<bool>,
+    pub today: NaiveDate,
+    /// Lowercased search text
+    pub text: Option<String>,
+    pub project: Option<u32>,
+    /// Normalized tag
+    pub tag: Option<String>,
+    pub sort: Sort,
+    pub limit: usize,
+    /// Only tasks sorted after this position, from `cursor`
+    pub after: Option<Position>,
+    /// Only tasks this user may see; `None` matches every task
+    pub viewer: Option<u32>,
+    /// Match tasks in the trash instead of live ones
+    pub trashed: bool,
+}
+
+impl TaskQuery {
+    /// Parses the enum fields and `cursor` and checks `limit`
+    ///
+    /// `today` is the date `overdue` is judged against.
+    pub fn validate(self, today: NaiveDate) -> Result<ListQuery, Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        let status = self.status.as_deref().and_then(|status| {
+            status
+                .parse()
+                .map_err(|e: String| errors.push(FieldError::new("status", e)))
+                .ok()
+        });
+
+        let priority = self.priority.as_deref().and_then(|priority| {
+            priority
+                .parse()
+                .map_err(|e: String| errors.push(FieldError::new("priority", e)))
+                .ok()
+        });
+
+        let tag = self.tag.as_deref().and_then(|tag| {
+            tag::normalize(tag)
+                .map_err(|e| errors.push(FieldError::new("tag", e)))
+                .ok()
+        });
+
+        let sort = match self.sort.as_deref() {
+            None | Some("") => Sort::default(),
+            Some(sort) => sort.parse().unwrap_or_else(|e: String| {
+                errors.push(FieldError::new("sort", e));
+                Sort::default()
+            }),
+        };
+
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            errors.push(FieldError::new(
+                "limit",
+                format!("must be between 1 and {}", MAX_LIMIT),
+            ));
+        }
+
+        // The cursor is opaque to clients; it holds the position of the last
+        // task on the previous page, and only makes sense under the same sort.
+        let after = match self.cursor.as_deref() {
+            None | Some("") => None,
+            Some(cursor) => match Position::from_cursor(cursor) {
+                Some(after) if after.key.field() == sort.field => Some(after),
+                _ => {
+                    errors.push(FieldError::new("cursor", "is not a valid cursor"));
+                    None
+                }
+            },
+        };
+
+        let text = self
+            .q
+            .map(|q| q.trim().to_lowercase())
+            .filter(|q| !q.is_empty());
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(ListQuery {
+            completed: self.completed,
+            status,
+            priority,
+            overdue: self.overdue,
+            today,
+            text,
+            project: self.project,
+            tag,
+            sort,
+            limit,
+            after,
+            viewer: None,
+            trashed: false,
+        })
+    }
+}
+
+impl ListQuery {
+    pub fn matches(&self, task: &Task) -> bool {
+        if task.is_trashed() != self.trashed {
+            return false;
+        }
+        if self.viewer.is_some_and(|user| !task.visible_to(user)) {
+            return false;
+        }
+        if self
+            .completed
+            .is_some_and(|completed| task.is_done() != completed)
+        {
+            return false;
+        }
+        if self.status.is_some_and(|status| task.status != status) {
+            return false;
+        }
+        if self
+            .priority
+            .is_some_and(|priority| task.priority != priority)
+        {
+            return false;
+        }
+        if self
+            .overdue
+            .is_some_and(|overdue| task.is_overdue(self.today) != overdue)
+        {
+            return false;
+        }
+        if self
+            .project
+            .is_some_and(|project| task.project_id != Some(project))
+        {
+            return false;
+        }
+        if self.tag.as_ref().is_some_and(|tag| !task.has_tag(tag)) {
+            return false;
+        }
+        if let Some(text) = &self.text {
+            if !task.title.to_lowercase().contains(text)
+                && !task.description.to_lowercase().contains(text)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Filters, sorts and slices `tasks` into a page
+    pub fn apply(&self, tasks: Vec<Task>) -> TaskPage {
+        let mut tasks: Vec<Task> = tasks.into_iter().filter(|t| self.matches(t)).collect();
+        let total = tasks.len();
+        if let Some(after) = &self.after {
+            tasks.retain(|t| self.sort.order(&self.sort.position(t), after) == Ordering::Greater);
+        }
+        tasks.sort_by(|a, b| self.sort.compare(a, b));
+
+        let more = tasks.len() > self.limit;
+        tasks.truncate(self.limit);
+        let next_cursor = more
+            .then(|| tasks.last())
+            .flatten()
+            .map(|last| self.sort.position(last).cursor());
+
+        TaskPage {
+            items: tasks,
+            total,
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 10).unwrap()
+    }
+
+    fn tasks() -> Vec<Task> {
+        let now = Utc.with_ymd_and_hms(2024, 5, 10, 9, 0, 0).unwrap();
+        let mut tasks = vec![
+            Task::new(1, "Play Spiderman".into(), "on the console".into(), now),
+            Task::new(2, "blockchain".into(), "create project".into(), now),
+            Task::new(
+                3,
+                "smart contract".into(),
+                "learn spider patterns".into(),
+                now,
+            ),
+            Task::new(4, "Read".into(), "the book".into(), now),
+        ];
+        tasks[1].priority = Priority::Urgent;
+        tasks[1].due_date = today().pred_opt();
+        tasks[2].set_status(TaskStatus::Done, now).unwrap();
+        tasks[2].due_date = today().pred_opt();
+        tasks[3].due_date = today().succ_opt();
+        tasks[0].project_id = Some(1);
+        tasks[3].project_id = Some(1);
+        tasks[0].tags = vec!["games".into()];
+        tasks[1].tags = vec!["work".into(), "games".into()];
+        tasks
+    }
+
+    fn query(raw: TaskQuery) -> ListQuery {
+        raw.validate(today()).unwrap()
+    }
+
+    fn ids(page: &TaskPage) -> Vec<u32> {
+        page.items.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn filters_by_text_and_completion() {
+        let page = query(TaskQuery {
+            q: Some("SPIDER".into()),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [1, 3]);
+
+        let page = query(TaskQuery {
+            q: Some("spider".into()),
+            completed: Some(false),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].id, 1);
+    }
+
+    #[test]
+    fn filters_by_status_priority_and_overdue() {
+        let page = query(TaskQuery {
+            status: Some("done".into()),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [3]);
+
+        let page = query(TaskQuery {
+            priority: Some("urgent".into()),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [2]);
+
+        let page = query(TaskQuery {
+            overdue: Some(true),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [2]);
+    }
+
+    #[test]
+    fn filters_by_project_and_tag() {
+        let page = query(TaskQuery {
+            project: Some(1),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [1, 4]);
+
+        let page = query(TaskQuery {
+            tag: Some(" Games".into()),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [1, 2]);
+
+        let page = query(TaskQuery {
+            project: Some(1),
+            tag: Some("games".into()),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [1]);
+    }
+
+    #[test]
+    fn sorts_and_pages() {
+        let raw = TaskQuery {
+            sort: Some("-title".into()),
+            limit: Some(3),
+            ..Default::default()
+        };
+        let page = query(raw.clone()).apply(tasks());
+        assert_eq!(ids(&page), [3, 4, 1]);
+        assert_eq!(page.total, 4);
+
+        // Tasks added before the cursor do not shift the next page.
+        let mut more = tasks();
+        more.push(Task::new(
+            5,
+            "zebra".into(),
+            String::new(),
+            more[0].created_at,
+        ));
+        let page = query(TaskQuery {
+            cursor: page.next_cursor,
+            ..raw
+        })
+        .apply(more);
+        assert_eq!(ids(&page), [2]);
+        assert_eq!(page.total, 5);
+        assert_eq!(page.next_cursor, None);
+
+        let page = query(TaskQuery {
+            sort: Some("due_date".into()),
+            ..Default::default()
+        })
+        .apply(tasks());
+        assert_eq!(ids(&page), [2, 3, 4, 1]);
+    }
+
+    #[test]
+    fn rejects_bad_parameters() {
+        let errors = TaskQuery {
+            status: Some("someday".into()),
+            tag: Some(" ".into()),
+            sort: Some("colour".into()),
+            limit: Some(0),
+            cursor: Some("abc".into()),
+            ..Default::default()
+        }
+        .validate(today())
+        .unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["status", "tag", "sort", "limit", "cursor"]);
+
+        // A cursor only fits the sort it was issued for.
+        let page = query(TaskQuery {
+            sort: Some("title".into()),
+            limit: Some(1),
+            ..Default::default()
+        })
+        .apply(tasks());
+        let errors = TaskQuery {
+            sort: Some("priority".into()),
+            cursor: page.next_cursor,
+            ..Default::default()
+        }
+        .validate(today())
+        .unwrap_err();
+        assert_eq!(errors[0].field, "cursor");
+    }
+}
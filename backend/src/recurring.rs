@@ -0,0 +1,248 @@
+//! Recurring tasks: spawning the next occurrence in the background
+//!
+//! A task with a `recurrence` rule is the latest occurrence of its series.
+//! Its next occurrence is due on the first rule date after its due date (or
+//! creation date, without one). Once that task is done, or once that date
+//! has come, the scheduler creates the next occurrence as a fresh `todo`
+//! copy, with the same reminders, project and tags, and moves the rule over to it, so each
+//! series has one task carrying the rule at a time. Occurrences missed while
+//! the server was down are skipped: the new task is due on the latest rule
+//! date that has passed.
+//!
+//! The scheduler runs at liftoff, whenever a repeating task is completed and
+//! every `recurring.check_interval_secs`. It reads the time from the managed
+//! `Clock`, so tests can move it forward.
+
+use crate::clock::{self, Clock};
+use crate::events::EventLog;
+use crate::history::{self, Action, Revision};
+use crate::metrics::Metrics;
+use crate::store::{HistoryStore, StoreError, TaskStore, TaskWrite};
+use crate::task::Task;
+use crate::webhooks::Dispatcher;
+use chrono::{DateTime, NaiveDate, Utc};
+use rocket::fairing::AdHoc;
+use rocket::tokio::{select, task, time};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// The `recurring` section of `Rocket.toml`
+///
+/// ```toml
+/// [default.recurring]
+/// check_interval_secs = 60
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecurringConfig {
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+fn default_check_interval_secs() -> u64 {
+    60
+}
+
+impl Default for RecurringConfig {
+    fn default() -> Self {
+        RecurringConfig {
+            check_interval_secs: default_check_interval_secs(),
+        }
+    }
+}
+
+/// The due date of the occurrence to create after `task` on `today`, if one
+/// is due
+pub fn next_due(task: &Task, today: NaiveDate) -> Option<NaiveDate> {
+    let rule = task.recurrence.as_ref()?;
+    if task.is_trashed() {
+        return None;
+    }
+    let anchor = task.due_date.unwrap_or(task.created_at.date_naive());
+    let rule = rule.anchored(anchor);
+    let mut due = rule.next_after(anchor)?;
+    if due > today && !task.is_done() {
+        return None;
+    }
+    while let Some(next) = rule.next_after(due).filter(|next| *next <= today) {
+        due = next;
+    }
+    Some(due)
+}
+
+/// Creates the next occurrence of every repeating task that has one due at
+/// `now`, returning the revisions to record
+///
+/// Each new task and the change to the one it follows are saved together,
+/// the store numbering the new task. A task changed by someone else in the
+/// meantime is left for the next run.
+pub fn spawn_due(store: &dyn TaskStore, now: DateTime<Utc>) -> Result<Vec<Revision>, StoreError> {
+    let tasks = store.list()?;
+    let mut revisions = Vec::new();
+
+    for before in tasks {
+        let Some(due) = next_due(&before, now.date_naive()) else {
+            continue;
+        };
+        let anchor = before.due_date.unwrap_or(before.created_at.date_naive());
+        let mut next = Task::new(0, before.title.clone(), before.description.clone(), now);
+        next.priority = before.priority;
+        next.due_date = Some(due);
+        next.owner_id = before.owner_id;
+        next.shared = before.shared;
+        next.reminders = before.reminders.clone();
+        next.project_id = before.project_id;
+        next.tags = before.tags.clone();
+        next.recurrence = before.recurrence.as_ref().map(|rule| rule.anchored(anchor));
+
+        let mut after = before.clone();
+        after.recurrence = None;
+        after.touch(now);
+
+        match store.commit(vec![
+            TaskWrite::Create(next.clone()),
+            TaskWrite::Update(after.clone()),
+        ]) {
+            Ok(created) => next.id = created[0],
+            Err(StoreError::StaleVersion(_)) => continue,
+            Err(e) => return Err(e),
+        }
+        revisions.push(Revision::new(None, Action::Created, None, Some(&next), now));
+        revisions.push(Revision::new(
+            None,
+            Action::Updated,
+            Some(&before),
+            Some(&after),
+            now,
+        ));
+    }
+    Ok(revisions)
+}
+
+/// Runs `spawn_due` at liftoff, after every completed repeating task and on
+/// the configured interval until shutdown
+pub fn schedule() -> AdHoc {
+    AdHoc::on_liftoff("Recurring Tasks", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(store), Some(history), Some(events), Some(webhooks)) = (
+                rocket.state::<RecurringConfig>(),
+                rocket.state::<Arc<dyn TaskStore>>(),
+                rocket.state::<Arc<dyn HistoryStore>>(),
+                rocket.state::<Arc<EventLog>>(),
+                rocket.state::<Dispatcher>(),
+            ) else {
+                return;
+            };
+
+            let interval = time::Duration::from_secs(config.check_interval_secs.max(1));
+            let (store, history, events) =
+                (Arc::clone(store), Arc::clone(history), Arc::clone(events));
+            let webhooks = webhooks.clone();
+            let clock: Arc<dyn Clock> = clock::of(rocket);
+            let metrics = rocket.state::<Arc<Metrics>>().cloned();
+            let mut completions = events.subscribe();
+            let mut shutdown = rocket.shutdown();
+
+            rocket::tokio::spawn(async move {
+                loop {
+                    let store = Arc::clone(&store);
+                    let now = clock.now();
+                    let result = task::spawn_blocking(move || spawn_due(store.as_ref(), now)).await;
+                    match result {
+                        Ok(Ok(revisions)) => {
+                            for revision in revisions {
+                                history::record(&events, history.as_ref(), &webhooks, revision);
+                            }
+                        }
+                        Ok(Err(e)) => {
+                            error!("failed to spawn recurring tasks: {}", e);
+                            if let Some(metrics) = &metrics {
+                                metrics.record_storage_error(&e);
+                            }
+                        }
+                        Err(e) => error!("recurring task scheduler panicked: {}", e),
+                    }
+
+                    let sleep = time::sleep(interval);
+                    rocket::tokio::pin!(sleep);
+                    loop {
+                        select! {
+                            _ = &mut sleep => break,
+                            event = completions.recv() => match event {
+                                Ok(event) if event.task.is_done()
+                                    && event.task.recurrence.is_some() => break,
+                                Ok(_) => {}
+                                // Completions may have been missed. The log
+                                // is held above, so it never closes.
+                                Err(_) => break,
+                            },
+                            _ = &mut shutdown => return,
+                        }
+                    }
+                }
+            });
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recurrence::Recurrence;
+    use crate::store::CsvStore;
+    use crate::task::{NewTask, TaskStatus};
+    use chrono::Weekday;
+
+    fn at(date: &str) -> DateTime<Utc> {
+        format!("{}T09:00:00Z", date).parse().unwrap()
+    }
+
+    #[test]
+    fn spawns_once_done_or_due() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CsvStore::new(dir.path().join("tasks.csv"));
+        // 2026-10-12 is a Monday.
+        let mut weekly = NewTask::new("rotate keys");
+        weekly.due_date = "2026-10-12".parse().ok();
+        weekly.shared = true;
+        weekly.recurrence = Some(Recurrence::Week {
+            interval: 1,
+            weekdays: Vec::new(),
+        });
+        store.insert(weekly, at("2026-10-01")).unwrap();
+
+        assert!(spawn_due(&store, at("2026-10-12")).unwrap().is_empty());
+
+        let mut done = store.get(1).unwrap().unwrap();
+        done.set_status(TaskStatus::Done, at("2026-10-12")).unwrap();
+        done.touch(at("2026-10-12"));
+        store.update(done).unwrap();
+        let revisions = spawn_due(&store, at("2026-10-13")).unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].action, Action::Created);
+        let first = store.get(1).unwrap().unwrap();
+        assert_eq!(first.recurrence, None);
+        assert_eq!(first.version, 3);
+
+        let second = store.get(2).unwrap().unwrap();
+        assert_eq!(second.title, "rotate keys");
+        assert_eq!(second.status, TaskStatus::Todo);
+        assert!(second.shared);
+        assert_eq!(second.due_date, "2026-10-19".parse().ok());
+        assert_eq!(
+            second.recurrence,
+            Some(Recurrence::Week {
+                interval: 1,
+                weekdays: vec![Weekday::Mon],
+            })
+        );
+
+        // Left open past three more Mondays: one task, due on the last.
+        assert!(spawn_due(&store, at("2026-10-18")).unwrap().is_empty());
+        spawn_due(&store, at("2026-11-04")).unwrap();
+        let third = store.get(3).unwrap().unwrap();
+        assert_eq!(third.due_date, "2026-11-02".parse().ok());
+        assert_eq!(store.get(2).unwrap().unwrap().status, TaskStatus::Todo);
+        assert!(spawn_due(&store, at("2026-11-04")).unwrap().is_empty());
+        assert_eq!(store.list().unwrap().len(), 3);
+    }
+}
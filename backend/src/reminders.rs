@@ -0,0 +1,158 @@
+//! Reminders: notifying owners ahead of their tasks' due dates
+//!
+//! Each of a task's `reminders` fires at its time on the given number of
+//! days before the due date, and the owner gets a `reminder` notification
+//! through the `Notifier`. Only open tasks with an owner and a due date
+//! remind. A reminder's outbox key names the task, by id and creation time,
+//! and the moment it fires, so it is sent once, and again only if the due
+//! date moves. Reminders that
+//! came due while the server was down are sent when it starts.
+//!
+//! Reminders are checked at liftoff and then every
+//! `notifications.check_interval_secs`, against the managed `Clock`.
+
+use crate::clock::{self, Clock};
+use crate::metrics::Metrics;
+use crate::notifications::{Notification, NotificationConfig, NotificationKind, Notifier};
+use crate::store::{StoreError, TaskStore};
+use crate::task::Task;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rocket::fairing::AdHoc;
+use rocket::tokio::{select, task, time};
+use std::sync::Arc;
+
+/// The notifications for the reminders of `task` that fire at or before
+/// `now`
+pub fn fired(task: &Task, now: DateTime<Utc>) -> Vec<Notification> {
+    let (Some(owner_id), Some(due)) = (task.owner_id, task.due_date) else {
+        return Vec::new();
+    };
+    if task.is_done() || task.is_trashed() {
+        return Vec::new();
+    }
+    task.reminders
+        .iter()
+        .filter_map(|reminder| reminder.fires_at(due))
+        .filter(|at| *at <= now)
+        .map(|at| Notification {
+            id: 0,
+            user_id: owner_id,
+            task_id: task.id,
+            kind: NotificationKind::Reminder,
+            message: format!("'{}' is due on {}", task.title, due),
+            at,
+            key: format!(
+                "reminder:{}:{}:{}",
+                task.id,
+                task.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+                at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+        })
+        .collect()
+}
+
+/// Sends every reminder fired by `now` that has not been sent yet, returning
+/// how many were sent
+pub fn send_fired(
+    store: &dyn TaskStore,
+    notifier: &Notifier,
+    now: DateTime<Utc>,
+) -> Result<usize, StoreError> {
+    let mut sent = 0;
+    for task in store.list()? {
+        for notification in fired(&task, now) {
+            if notifier.notify(notification, &task)?.is_some() {
+                sent += 1;
+            }
+        }
+    }
+    Ok(sent)
+}
+
+/// Runs `send_fired` at liftoff and on the configured interval until
+/// shutdown
+pub fn schedule() -> AdHoc {
+    AdHoc::on_liftoff("Reminders", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(store), Some(notifier)) = (
+                rocket.state::<NotificationConfig>(),
+                rocket.state::<Arc<dyn TaskStore>>(),
+                rocket.state::<Notifier>(),
+            ) else {
+                return;
+            };
+
+            let interval = time::Duration::from_secs(config.check_interval_secs.max(1));
+            let store = Arc::clone(store);
+            let notifier = notifier.clone();
+            let clock: Arc<dyn Clock> = clock::of(rocket);
+            let metrics = rocket.state::<Arc<Metrics>>().cloned();
+            let mut shutdown = rocket.shutdown();
+
+            rocket::tokio::spawn(async move {
+                loop {
+                    let (store, notifier) = (Arc::clone(&store), notifier.clone());
+                    let now = clock.now();
+                    let result =
+                        task::spawn_blocking(move || send_fired(store.as_ref(), &notifier, now))
+                            .await;
+                    match result {
+                        Ok(Ok(0)) => {}
+                        Ok(Ok(sent)) => info!("sent {} reminder(s)", sent),
+                        Ok(Err(e)) => {
+                            error!("failed to send reminders: {}", e);
+                            if let Some(metrics) = &metrics {
+                                metrics.record_storage_error(&e);
+                            }
+                        }
+                        Err(e) => error!("reminder check panicked: {}", e),
+                    }
+
+                    select! {
+                        _ = time::sleep(interval) => {}
+                        _ = &mut shutdown => return,
+                    }
+                }
+            });
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::Reminder;
+    use crate::task::TaskStatus;
+
+    #[test]
+    fn fires_on_the_owners_open_tasks() {
+        let now: DateTime<Utc> = "2026-10-14T12:00:00Z".parse().unwrap();
+        let mut task = Task::new(4, "file taxes".into(), String::new(), now);
+        task.owner_id = Some(2);
+        task.due_date = "2026-10-15".parse().ok();
+        task.reminders = vec![
+            Reminder::days_before(1),
+            Reminder::days_before(2),
+            Reminder::days_before(0),
+        ];
+
+        let due = fired(&task, now);
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[0].user_id, 2);
+        assert_eq!(due[0].message, "'file taxes' is due on 2026-10-15");
+        assert_eq!(
+            due[0].key,
+            "reminder:4:2026-10-14T12:00:00.000000Z:2026-10-14T09:00:00Z"
+        );
+        assert_eq!(
+            due[1].key,
+            "reminder:4:2026-10-14T12:00:00.000000Z:2026-10-13T09:00:00Z"
+        );
+
+        let mut done = task.clone();
+        done.set_status(TaskStatus::Done, now).unwrap();
+        assert!(fired(&done, now).is_empty());
+        task.due_date = None;
+        assert!(fired(&task, now).is_empty());
+    }
+}
@@ -0,0 +1,708 @@
+//! Storage backends for tasks
+//!
+//! Handlers talk to a `TaskStore` managed as Rocket state instead of reading
+//! and writing `tasks.csv` directly. The concrete backend is chosen from the
+//! `storage` section of the Rocket configuration. User accounts live in a
+//! `UserStore`, the audit log in a `HistoryStore`, webhooks in a
+//! `WebhookStore`, sent notifications in a `NotificationStore`, projects in a
+//! `ProjectStore` and comments in a `CommentStore` of the same kind.
+
+mod csv_migrate;
+mod csv_store;
+mod csv_users;
+mod jsonl_comments;
+mod jsonl_history;
+mod jsonl_notifications;
+mod jsonl_projects;
+mod jsonl_webhooks;
+mod sqlite_store;
+
+pub use csv_migrate::{CsvSchema, MigrationReport, RejectedRow};
+pub(crate) use csv_store::write_csv;
+pub use csv_store::CsvStore;
+pub use csv_users::CsvUserStore;
+pub use jsonl_comments::JsonlCommentStore;
+pub use jsonl_history::JsonlHistoryStore;
+pub use jsonl_notifications::JsonlNotificationStore;
+pub use jsonl_projects::JsonlProjectStore;
+pub use jsonl_webhooks::JsonlWebhookStore;
+pub use sqlite_store::SqliteStore;
+
+use crate::auth::User;
+use crate::comments::Comment;
+use crate::history::Revision;
+use crate::notifications::Notification;
+use crate::projects::Project;
+use crate::query::{ListQuery, TaskPage};
+use crate::task::{NewTask, Task};
+use crate::webhooks::{Delivery, Webhook};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Errors raised by a storage backend
+#[derive(Debug)]
+pub enum StoreError {
+    /// A task with this id already exists
+    DuplicateId(u32),
+    /// A user with this name already exists
+    DuplicateUsername(String),
+    /// The task was changed since the copy being saved was read
+    StaleVersion(u32),
+    /// The CSV header matches no known layout
+    UnknownSchema(String),
+    /// Every task id has been handed out
+    IdsExhausted,
+    Io(std::io::Error),
+    Csv(csv::Error),
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::DuplicateId(id) => write!(f, "task {} already exists", id),
+            StoreError::DuplicateUsername(name) => write!(f, "user '{}' already exists", name),
+            StoreError::StaleVersion(id) => write!(f, "task {} was changed concurrently", id),
+            StoreError::UnknownSchema(header) => {
+                write!(f, "unrecognized CSV header '{}'", header)
+            }
+            StoreError::IdsExhausted => write!(f, "no task ids are left"),
+            StoreError::Io(e) => write!(f, "I/O error: {}", e),
+            StoreError::Csv(e) => write!(f, "CSV error: {}", e),
+            StoreError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            StoreError::Json(e) => write!(f, "JSON error: {}", e),
+        }
+    }
+}
+
+impl StoreError {
+    /// Short name of the variant, for metrics labels
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StoreError::DuplicateId(_) => "duplicate_id",
+            StoreError::DuplicateUsername(_) => "duplicate_username",
+            StoreError::StaleVersion(_) => "stale_version",
+            StoreError::UnknownSchema(_) => "unknown_schema",
+            StoreError::IdsExhausted => "ids_exhausted",
+            StoreError::Io(_) => "io",
+            StoreError::Csv(_) => "csv",
+            StoreError::Sqlite(_) => "sqlite",
+            StoreError::Json(_) => "json",
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<csv::Error> for StoreError {
+    fn from(e: csv::Error) -> Self {
+        StoreError::Csv(e)
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Json(e)
+    }
+}
+
+/// The id after `id`, failing rather than wrapping once ids run out
+pub(crate) fn id_after(id: u32) -> Result<u32, StoreError> {
+    id.checked_add(1).ok_or(StoreError::IdsExhausted)
+}
+
+/// Persistent collection of tasks keyed by `Task::id`
+///
+/// `insert` assigns `next_id` unless the new task names one, and fails with
+/// `StoreError::DuplicateId` if that id is taken. `update` and `delete`
+/// return `false` when no task with the given id exists. Ids are never
+/// reused, so links, history and outbox keys naming a deleted task cannot
+/// come to point at a new one.
+///
+/// `update` expects a task that has been `touch`ed once since it was read: it
+/// fails with `StoreError::StaleVersion` unless the stored version is exactly
+/// one below `task.version`, so of two writers starting from the same version
+/// only the first succeeds.
+pub trait TaskStore: Send + Sync {
+    fn list(&self) -> Result<Vec<Task>, StoreError>;
+    fn get(&self, id: u32) -> Result<Option<Task>, StoreError>;
+    fn insert(&self, task: NewTask, now: DateTime<Utc>) -> Result<Task, StoreError>;
+    fn update(&self, task: Task) -> Result<bool, StoreError>;
+    fn delete(&self, id: u32) -> Result<bool, StoreError>;
+
+    /// The id the next inserted task gets: one above every id ever stored,
+    /// deleted tasks included
+    fn next_id(&self) -> Result<u32, StoreError>;
+
+    /// Applies `writes` in order as a single storage write, or none of them,
+    /// returning the ids given to its `Create` writes
+    ///
+    /// Fails like `insert` with an explicit id on a taken id, and with
+    /// `StoreError::StaleVersion` on an update `update` would refuse or whose
+    /// task no longer exists.
+    fn commit(&self, writes: Vec<TaskWrite>) -> Result<Vec<u32>, StoreError>;
+
+    /// Returns one page of the tasks matching `query`
+    ///
+    /// The default filters and sorts the result of `list` in memory.
+    fn query(&self, query: &ListQuery) -> Result<TaskPage, StoreError> {
+        Ok(query.apply(self.list()?))
+    }
+
+    /// Reads the store and writes to it without changing any task, failing
+    /// if either is impossible
+    ///
+    /// The default lists the tasks and commits an empty batch.
+    fn check(&self) -> Result<(), StoreError> {
+        self.list()?;
+        self.commit(Vec::new())?;
+        Ok(())
+    }
+}
+
+/// One change in a `TaskStore::commit`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskWrite {
+    /// A new task, with its id already chosen
+    Insert(Task),
+    /// A new task stored under the next free id, whatever its `id` says
+    Create(Task),
+    Update(Task),
+    Delete(u32),
+}
+
+/// Persistent collection of user accounts
+///
+/// Usernames are unique; `insert_user` fails with
+/// `StoreError::DuplicateUsername` if the name is taken.
+pub trait UserStore: Send + Sync {
+    fn get_user(&self, id: u32) -> Result<Option<User>, StoreError>;
+    fn find_user(&self, username: &str) -> Result<Option<User>, StoreError>;
+    fn insert_user(
+        &self,
+        username: &str,
+        email: Option<&str>,
+        password_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<User, StoreError>;
+}
+
+/// Append-only log of task revisions
+///
+/// `append` numbers the revision after the last one stored for its task,
+/// ignoring `revision.revision`, and returns it as stored.
+pub trait HistoryStore: Send + Sync {
+    fn append(&self, revision: Revision) -> Result<Revision, StoreError>;
+    /// Revisions of task `task_id`, oldest first
+    fn history(&self, task_id: u32) -> Result<Vec<Revision>, StoreError>;
+}
+
+/// Registered webhooks and the log of attempts to deliver to them
+///
+/// `insert_webhook` assigns the next free id, ignoring `webhook.id`, and
+/// returns the hook as stored. Deliveries outlive the hook they were made to.
+pub trait WebhookStore: Send + Sync {
+    fn webhooks(&self) -> Result<Vec<Webhook>, StoreError>;
+    fn insert_webhook(&self, webhook: Webhook) -> Result<Webhook, StoreError>;
+    fn delete_webhook(&self, id: u32) -> Result<bool, StoreError>;
+    fn record_delivery(&self, delivery: Delivery) -> Result<(), StoreError>;
+    /// Deliveries made to webhook `webhook_id`, oldest first
+    fn deliveries(&self, webhook_id: u32) -> Result<Vec<Delivery>, StoreError>;
+}
+
+/// The outbox of notifications sent to users
+///
+/// `append` assigns the next id, ignoring `notification.id`, and returns the
+/// notification as stored, or `None` if one with the same `key` was appended
+/// before, so that each is sent only once.
+pub trait NotificationStore: Send + Sync {
+    fn append(&self, notification: Notification) -> Result<Option<Notification>, StoreError>;
+    /// Notifications sent to user `user_id`, oldest first
+    fn notifications(&self, user_id: u32) -> Result<Vec<Notification>, StoreError>;
+}
+
+/// Projects tasks can be filed under
+///
+/// `insert_project` assigns the next id, ignoring `project.id`, and returns
+/// the project as stored.
+pub trait ProjectStore: Send + Sync {
+    fn projects(&self) -> Result<Vec<Project>, StoreError>;
+    fn insert_project(&self, project: Project) -> Result<Project, StoreError>;
+    fn delete_project(&self, id: u32) -> Result<bool, StoreError>;
+}
+
+/// Comments on tasks, each task's oldest first
+///
+/// `insert_comment` assigns the next id, ignoring `comment.id`, and returns
+/// the comment as stored. `delete_comments` removes every comment on a task
+/// and returns how many there were.
+pub trait CommentStore: Send + Sync {
+    fn comments(&self, task_id: u32) -> Result<Vec<Comment>, StoreError>;
+    fn insert_comment(&self, comment: Comment) -> Result<Comment, StoreError>;
+    fn update_comment(&self, comment: Comment) -> Result<bool, StoreError>;
+    fn delete_comment(&self, id: u32) -> Result<bool, StoreError>;
+    fn delete_comments(&self, task_id: u32) -> Result<usize, StoreError>;
+}
+
+/// The `storage` section of `Rocket.toml`
+///
+/// ```toml
+/// [default.storage]
+/// kind = "sqlite"
+/// path = "tasks.db"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum StorageConfig {
+    Csv { path: PathBuf },
+    Sqlite { path: PathBuf },
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig::Csv {
+            path: PathBuf::from("tasks.csv"),
+        }
+    }
+}
+
+impl StorageConfig {
+    /// The file the tasks are kept in
+    pub fn path(&self) -> &Path {
+        match self {
+            StorageConfig::Csv { path } | StorageConfig::Sqlite { path } => path,
+        }
+    }
+
+    /// The same backend with a relative path taken as relative to `dir`;
+    /// absolute paths are kept
+    pub fn in_dir(self, dir: &Path) -> Self {
+        match self {
+            StorageConfig::Csv { path } => StorageConfig::Csv {
+                path: dir.join(path),
+            },
+            StorageConfig::Sqlite { path } => StorageConfig::Sqlite {
+                path: dir.join(path),
+            },
+        }
+    }
+
+    /// Opens the configured backend
+    pub fn open(&self) -> Result<Arc<dyn TaskStore>, StoreError> {
+        match self {
+            StorageConfig::Csv { path } => {
+                let (store, report) = CsvStore::open(path)?;
+                if report.migrated > 0 {
+                    info!(
+                        "migrated {} tasks in {} from schema v{} to v{}",
+                        report.migrated,
+                        path.display(),
+                        report.schema.version(),
+                        CsvSchema::CURRENT.version()
+                    );
+                }
+                for row in &report.rejected {
+                    warn!("skipped row in {}: {}", path.display(), row);
+                }
+                Ok(Arc::new(store))
+            }
+            StorageConfig::Sqlite { path } => Ok(Arc::new(SqliteStore::open(path)?)),
+        }
+    }
+
+    /// Opens the user accounts kept alongside the tasks
+    ///
+    /// For CSV this is `users.csv` in the same directory as the tasks file;
+    /// SQLite keeps them in a table of the same database.
+    pub fn open_users(&self) -> Result<Arc<dyn UserStore>, StoreError> {
+        match self {
+            StorageConfig::Csv { path } => Ok(Arc::new(CsvUserStore::new(
+                path.with_file_name("users.csv"),
+            ))),
+            StorageConfig::Sqlite { path } => Ok(Arc::new(SqliteStore::open(path)?)),
+        }
+    }
+
+    /// Opens the audit log kept alongside the tasks
+    ///
+    /// For CSV this is `history.jsonl` in the same directory as the tasks
+    /// file, one revision per line; SQLite keeps it in a table.
+    pub fn open_history(&self) -> Result<Arc<dyn HistoryStore>, StoreError> {
+        match self {
+            StorageConfig::Csv { path } => Ok(Arc::new(JsonlHistoryStore::new(
+                path.with_file_name("history.jsonl"),
+            ))),
+            StorageConfig::Sqlite { path } => Ok(Arc::new(SqliteStore::open(path)?)),
+        }
+    }
+
+    /// Opens the webhooks and delivery log kept alongside the tasks
+    ///
+    /// For CSV these are `webhooks.jsonl` and `deliveries.jsonl` in the same
+    /// directory as the tasks file; SQLite keeps them in tables.
+    pub fn open_webhooks(&self) -> Result<Arc<dyn WebhookStore>, StoreError> {
+        match self {
+            StorageConfig::Csv { path } => Ok(Arc::new(JsonlWebhookStore::new(
+                path.with_file_name("webhooks.jsonl"),
+                path.with_file_name("deliveries.jsonl"),
+            ))),
+            StorageConfig::Sqlite { path } => Ok(Arc::new(SqliteStore::open(path)?)),
+        }
+    }
+
+    /// Opens the notification outbox kept alongside the tasks
+    ///
+    /// For CSV this is `notifications.jsonl` in the same directory as the
+    /// tasks file; SQLite keeps it in a table.
+    pub fn open_notifications(&self) -> Result<Arc<dyn NotificationStore>, StoreError> {
+        match self {
+            StorageConfig::Csv { path } => Ok(Arc::new(JsonlNotificationStore::new(
+                path.with_file_name("notifications.jsonl"),
+            ))),
+            StorageConfig::Sqlite { path } => Ok(Arc::new(SqliteStore::open(path)?)),
+        }
+    }
+
+    /// Opens the projects kept alongside the tasks
+    ///
+    /// For CSV this is `projects.jsonl` in the same directory as the tasks
+    /// file; SQLite keeps them in a table.
+    pub fn open_projects(&self) -> Result<Arc<dyn ProjectStore>, StoreError> {
+        match self {
+            StorageConfig::Csv { path } => Ok(Arc::new(JsonlProjectStore::new(
+                path.with_file_name("projects.jsonl"),
+            ))),
+            StorageConfig::Sqlite { path } => Ok(Arc::new(SqliteStore::open(path)?)),
+        }
+    }
+
+    /// Opens the comments kept alongside the tasks
+    ///
+    /// For CSV this is `comments.jsonl` in the same directory as the tasks
+    /// file; SQLite keeps them in a table.
+    pub fn open_comments(&self) -> Result<Arc<dyn CommentStore>, StoreError> {
+        match self {
+            StorageConfig::Csv { path } => Ok(Arc::new(JsonlCommentStore::new(
+                path.with_file_name("comments.jsonl"),
+            ))),
+            StorageConfig::Sqlite { path } => Ok(Arc::new(SqliteStore::open(path)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Action;
+    use crate::notifications::NotificationKind;
+    use crate::task::{Priority, TaskStatus};
+    use crate::webhooks::WebhookEvent;
+
+    fn new_task(id: Option<u32>, title: &str) -> NewTask {
+        NewTask {
+            id,
+            owner_id: Some(3),
+            title: title.into(),
+            description: String::new(),
+            status: TaskStatus::Todo,
+            priority: Priority::Normal,
+            due_date: None,
+            shared: false,
+            recurrence: None,
+            reminders: Vec::new(),
+            project_id: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn exercise(store: &dyn TaskStore) {
+        let now = Utc::now();
+        assert!(store.list().unwrap().is_empty());
+
+        assert_eq!(store.insert(new_task(None, "write"), now).unwrap().id, 1);
+        assert_eq!(store.insert(new_task(None, "read"), now).unwrap().id, 2);
+        assert!(matches!(
+            store.insert(new_task(Some(2), "again"), now),
+            Err(StoreError::DuplicateId(2))
+        ));
+        assert_eq!(store.insert(new_task(Some(7), "pick"), now).unwrap().id, 7);
+        assert_eq!(store.insert(new_task(None, "next"), now).unwrap().id, 8);
+        assert!(store.delete(7).unwrap());
+        assert!(store.delete(8).unwrap());
+        assert_eq!(store.next_id().unwrap(), 9);
+        assert_eq!(store.list().unwrap().len(), 2);
+        let before = store.list().unwrap();
+        store.check().unwrap();
+        assert_eq!(store.list().unwrap(), before);
+
+        let mut task = store.get(1).unwrap().unwrap();
+        task.set_status(TaskStatus::Done, now).unwrap();
+        task.due_date = now.date_naive().succ_opt();
+        task.parent_id = Some(2);
+        task.blocked_by = vec![2, 8];
+        task.shared = true;
+        task.deleted_at = Some(now);
+        task.recurrence = Some(crate::recurrence::Recurrence::Cron {
+            expr: "1,15 * *".into(),
+        });
+        task.reminders = vec![crate::reminder::Reminder::days_before(2)];
+        task.project_id = Some(4);
+        task.tags = vec!["home".into(), "errands, big".into()];
+        assert!(matches!(
+            store.update(task.clone()),
+            Err(StoreError::StaleVersion(1))
+        ));
+        task.touch(now);
+        assert!(store.update(task.clone()).unwrap());
+        assert_eq!(store.get(1).unwrap(), Some(task.clone()));
+        assert!(matches!(
+            store.update(task),
+            Err(StoreError::StaleVersion(1))
+        ));
+
+        assert!(store.delete(2).unwrap());
+        assert!(!store.delete(2).unwrap());
+        assert!(!store
+            .update(Task::new(9, "missing".into(), String::new(), now))
+            .unwrap());
+        assert_eq!(store.get(2).unwrap(), None);
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(store.list().unwrap()[0].owner_id, Some(3));
+
+        let mut renamed = store.get(1).unwrap().unwrap();
+        renamed.title = "renamed".into();
+        renamed.touch(now);
+        let added = new_task(None, "added").into_task(5, now);
+        let created = new_task(None, "created").into_task(0, now);
+        let batch = vec![
+            TaskWrite::Update(renamed.clone()),
+            TaskWrite::Insert(added.clone()),
+            TaskWrite::Create(created.clone()),
+        ];
+
+        // The stale update at the end rejects the whole batch.
+        let mut stale = batch.clone();
+        stale.push(TaskWrite::Update(renamed.clone()));
+        assert!(matches!(
+            store.commit(stale),
+            Err(StoreError::StaleVersion(1))
+        ));
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(store.get(1).unwrap().unwrap().title, "write");
+
+        assert_eq!(store.commit(batch).unwrap(), [9]);
+        assert_eq!(store.get(1).unwrap(), Some(renamed));
+        assert_eq!(store.get(5).unwrap(), Some(added));
+        assert_eq!(store.get(9).unwrap().unwrap().title, "created");
+    }
+
+    fn exercise_users(store: &dyn UserStore) {
+        let now = Utc::now();
+        assert_eq!(store.find_user("ada").unwrap(), None);
+
+        let ada = store.insert_user("ada", None, "hash-a", now).unwrap();
+        let bob = store
+            .insert_user("bob", Some("bob@example.com"), "hash-b", now)
+            .unwrap();
+        assert_ne!(ada.id, bob.id);
+        assert!(matches!(
+            store.insert_user("ada", None, "other", now),
+            Err(StoreError::DuplicateUsername(_))
+        ));
+
+        assert_eq!(store.find_user("bob").unwrap(), Some(bob));
+        assert_eq!(store.get_user(ada.id).unwrap(), Some(ada));
+    }
+
+    fn exercise_history(store: &dyn HistoryStore) {
+        let now = Utc::now();
+        let task = Task::new(5, "audit".into(), String::new(), now);
+        let mut renamed = task.clone();
+        renamed.title = "audited".into();
+
+        let first = Revision::new(Some(1), Action::Created, None, Some(&task), now);
+        let second = Revision::new(Some(1), Action::Updated, Some(&task), Some(&renamed), now);
+        let other = Revision::new(None, Action::Deleted, Some(&renamed), None, now);
+        assert_eq!(store.append(first).unwrap().revision, 1);
+        assert_eq!(store.append(second.clone()).unwrap().revision, 2);
+        store
+            .append(Revision {
+                task_id: 6,
+                ..other
+            })
+            .unwrap();
+
+        let history = store.history(5).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history[1],
+            Revision {
+                revision: 2,
+                ..second
+            }
+        );
+        assert_eq!(store.history(6).unwrap()[0].revision, 1);
+        assert!(store.history(7).unwrap().is_empty());
+    }
+
+    fn exercise_webhooks(store: &dyn WebhookStore) {
+        let now = Utc::now();
+        let hook = |owner_id: u32| Webhook {
+            id: 0,
+            owner_id,
+            url: format!("http://127.0.0.1/hooks/{}", owner_id),
+            secret: "0123456789abcdef".into(),
+            events: vec![WebhookEvent::Created, WebhookEvent::Completed],
+            created_at: now,
+        };
+        let first = store.insert_webhook(hook(1)).unwrap();
+        let second = store.insert_webhook(hook(2)).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(store.webhooks().unwrap(), [first.clone(), second.clone()]);
+
+        let delivery = |webhook_id: u32, attempt: u32| Delivery {
+            webhook_id,
+            event_id: 4,
+            event: WebhookEvent::Created,
+            task_id: 9,
+            attempt,
+            at: now,
+            status: Some(500),
+            delivered: false,
+            error: Some("answered 500".into()),
+        };
+        store.record_delivery(delivery(1, 1)).unwrap();
+        store.record_delivery(delivery(2, 1)).unwrap();
+        store.record_delivery(delivery(1, 2)).unwrap();
+        assert_eq!(
+            store.deliveries(1).unwrap(),
+            [delivery(1, 1), delivery(1, 2)]
+        );
+
+        // The id of a deleted hook is not handed out again.
+        assert!(store.delete_webhook(2).unwrap());
+        assert!(!store.delete_webhook(2).unwrap());
+        assert_eq!(store.webhooks().unwrap(), [first]);
+        assert_eq!(store.deliveries(2).unwrap().len(), 1);
+        assert_eq!(store.insert_webhook(hook(3)).unwrap().id, 3);
+    }
+
+    fn exercise_notifications(store: &dyn NotificationStore) {
+        let now = Utc::now();
+        let notification = |user_id: u32, key: &str| Notification {
+            id: 0,
+            user_id,
+            task_id: 4,
+            kind: NotificationKind::Reminder,
+            message: "'file taxes' is due on 2026-10-15".into(),
+            at: now,
+            key: key.into(),
+        };
+        let first = store.append(notification(1, "reminder:4:a")).unwrap();
+        assert_eq!(first.as_ref().map(|n| n.id), Some(1));
+        assert_eq!(store.append(notification(1, "reminder:4:a")).unwrap(), None);
+        assert_eq!(
+            store
+                .append(notification(2, "reminder:4:b"))
+                .unwrap()
+                .unwrap()
+                .id,
+            2
+        );
+
+        assert_eq!(store.notifications(1).unwrap(), [first.unwrap()]);
+        assert_eq!(store.notifications(2).unwrap().len(), 1);
+        assert!(store.notifications(3).unwrap().is_empty());
+    }
+
+    fn exercise_projects(store: &dyn ProjectStore) {
+        let now = Utc::now();
+        let project = |owner_id: u32, name: &str| Project {
+            id: 0,
+            owner_id,
+            name: name.into(),
+            created_at: now,
+        };
+        assert_eq!(store.insert_project(project(1, "home")).unwrap().id, 1);
+        let work = store.insert_project(project(2, "work")).unwrap();
+        assert_eq!(work.id, 2);
+        assert_eq!(store.projects().unwrap().len(), 2);
+
+        assert!(store.delete_project(1).unwrap());
+        assert!(!store.delete_project(1).unwrap());
+        assert_eq!(store.projects().unwrap(), [work]);
+    }
+
+    fn exercise_comments(store: &dyn CommentStore) {
+        let now = Utc::now();
+        let comment = |task_id: u32, body: &str| Comment {
+            id: 0,
+            task_id,
+            author_id: 1,
+            body: body.into(),
+            created_at: now,
+            edited_at: None,
+        };
+        assert_eq!(store.insert_comment(comment(4, "first")).unwrap().id, 1);
+        let mut second = store.insert_comment(comment(4, "second")).unwrap();
+        store.insert_comment(comment(5, "elsewhere")).unwrap();
+        assert_eq!(second.id, 2);
+
+        second.body = "second, edited".into();
+        second.edited_at = Some(now);
+        assert!(store.update_comment(second.clone()).unwrap());
+        assert!(store.delete_comment(1).unwrap());
+        assert!(!store.delete_comment(1).unwrap());
+        assert_eq!(store.comments(4).unwrap(), [second]);
+
+        assert_eq!(store.delete_comments(5).unwrap(), 1);
+        assert!(store.comments(5).unwrap().is_empty());
+        assert!(!store.update_comment(comment(5, "gone")).unwrap());
+    }
+
+    #[test]
+    fn csv_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise(&CsvStore::new(dir.path().join("tasks.csv")));
+        exercise_users(&CsvUserStore::new(dir.path().join("users.csv")));
+        exercise_history(&JsonlHistoryStore::new(dir.path().join("history.jsonl")));
+        exercise_webhooks(&JsonlWebhookStore::new(
+            dir.path().join("webhooks.jsonl"),
+            dir.path().join("deliveries.jsonl"),
+        ));
+        exercise_notifications(&JsonlNotificationStore::new(
+            dir.path().join("notifications.jsonl"),
+        ));
+        exercise_projects(&JsonlProjectStore::new(dir.path().join("projects.jsonl")));
+        exercise_comments(&JsonlCommentStore::new(dir.path().join("comments.jsonl")));
+    }
+
+    #[test]
+    fn sqlite_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(dir.path().join("tasks.db")).unwrap();
+        exercise(&store);
+        exercise_users(&store);
+        exercise_history(&store);
+        exercise_webhooks(&store);
+        exercise_notifications(&store);
+        exercise_projects(&store);
+        exercise_comments(&store);
+    }
+}
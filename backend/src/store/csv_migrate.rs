@@ -0,0 +1,390 @@
+//! Schema detection and migration for `tasks.csv`
+//!
+//! The file's header row identifies its layout. Older layouts are converted
+//! to the current one when the store is opened. Whenever the file is
+//! rewritten or has unreadable rows, the original is kept next to it as
+//! `<name>.v<N>.bak`.
+
+use super::csv_store::{write_tasks, CsvRow};
+use super::StoreError;
+use crate::task::{Task, TaskStatus};
+use chrono::{DateTime, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use serde::Deserialize;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+/// Layouts `tasks.csv` has used, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvSchema {
+    /// `task_name,task_description,task_complete` with `yes`/`no` values and no ids
+    V1,
+    /// `id,title,description,completed`
+    V2,
+    /// `id,title,description,status,priority,due_date,created_at,updated_at,completed_at`
+    V3,
+    /// v3 plus `parent_id,blocked_by`, with `blocked_by` written as `2;5`
+    V4,
+    /// v4 plus `owner_id,shared`
+    V5,
+    /// v5 plus `deleted_at`
+    V6,
+    /// v6 plus `version`
+    V7,
+    /// v7 plus `recurrence`, the rule as JSON or empty
+    V8,
+    /// v8 plus `reminders`, a JSON array or empty
+    V9,
+    /// v9 plus `project_id,tags`, the tags as a JSON array or empty
+    V10,
+}
+
+impl CsvSchema {
+    pub const CURRENT: CsvSchema = CsvSchema::V10;
+
+    /// Identifies a layout from its header row
+    pub fn detect(headers: &StringRecord) -> Option<CsvSchema> {
+        let headers: Vec<&str> = headers.iter().map(str::trim).collect();
+        match headers.as_slice() {
+            ["task_name", "task_description", "task_complete"] => Some(CsvSchema::V1),
+            ["id", "title", "description", "completed"] => Some(CsvSchema::V2),
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at"] => {
+                Some(CsvSchema::V3)
+            }
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at", "parent_id", "blocked_by"] => {
+                Some(CsvSchema::V4)
+            }
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at", "parent_id", "blocked_by", "owner_id", "shared"] => {
+                Some(CsvSchema::V5)
+            }
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at", "parent_id", "blocked_by", "owner_id", "shared", "deleted_at"] => {
+                Some(CsvSchema::V6)
+            }
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at", "parent_id", "blocked_by", "owner_id", "shared", "deleted_at", "version"] => {
+                Some(CsvSchema::V7)
+            }
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at", "parent_id", "blocked_by", "owner_id", "shared", "deleted_at", "version", "recurrence"] => {
+                Some(CsvSchema::V8)
+            }
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at", "parent_id", "blocked_by", "owner_id", "shared", "deleted_at", "version", "recurrence", "reminders"] => {
+                Some(CsvSchema::V9)
+            }
+            ["id", "title", "description", "status", "priority", "due_date", "created_at", "updated_at", "completed_at", "parent_id", "blocked_by", "owner_id", "shared", "deleted_at", "version", "recurrence", "reminders", "project_id", "tags"] => {
+                Some(CsvSchema::V10)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn version(self) -> u32 {
+        match self {
+            CsvSchema::V1 => 1,
+            CsvSchema::V2 => 2,
+            CsvSchema::V3 => 3,
+            CsvSchema::V4 => 4,
+            CsvSchema::V5 => 5,
+            CsvSchema::V6 => 6,
+            CsvSchema::V7 => 7,
+            CsvSchema::V8 => 8,
+            CsvSchema::V9 => 9,
+            CsvSchema::V10 => 10,
+        }
+    }
+}
+
+/// A row that could not be read or migrated
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    /// 1-based line number in the file
+    pub line: u64,
+    /// The raw row, comma-joined
+    pub record: String,
+    pub reason: String,
+}
+
+impl fmt::Display for RejectedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} ({})", self.line, self.reason, self.record)
+    }
+}
+
+/// What opening a CSV file found and changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// Layout found on disk, before any migration
+    pub schema: CsvSchema,
+    /// Rows rewritten into the current layout (0 if none was needed)
+    pub migrated: usize,
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// Brings the file at `path` up to `CsvSchema::CURRENT`
+///
+/// A missing or empty file counts as current. Rows that cannot be read are
+/// reported rather than dropped silently; for a legacy file they are left
+/// out of the rewritten file but remain in the backup. Migrated tasks get
+/// `now` as their creation time, since older layouts did not record one.
+pub fn migrate(path: &Path, now: DateTime<Utc>) -> Result<MigrationReport, StoreError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(MigrationReport {
+                schema: CsvSchema::CURRENT,
+                migrated: 0,
+                rejected: Vec::new(),
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Flexible so that rows with a wrong field count are reported, not fatal.
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(BufReader::new(file));
+    let headers = reader.headers()?.clone();
+    if headers.is_empty() {
+        return Ok(MigrationReport {
+            schema: CsvSchema::CURRENT,
+            migrated: 0,
+            rejected: Vec::new(),
+        });
+    }
+
+    let schema = CsvSchema::detect(&headers)
+        .ok_or_else(|| StoreError::UnknownSchema(headers.iter().collect::<Vec<_>>().join(",")))?;
+
+    let mut tasks = Vec::new();
+    let mut rejected = Vec::new();
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                rejected.push(RejectedRow {
+                    line: e.position().map(|p| p.line()).unwrap_or_default(),
+                    record: String::new(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let parsed = match schema {
+            CsvSchema::V1 => parse_v1(&record, tasks.len() as u32 + 1, now),
+            CsvSchema::V2 => record
+                .deserialize::<TaskV2>(Some(&headers))
+                .map(|task| task.upgrade(now))
+                .map_err(|e| e.to_string()),
+            CsvSchema::V3
+            | CsvSchema::V4
+            | CsvSchema::V5
+            | CsvSchema::V6
+            | CsvSchema::V7
+            | CsvSchema::V8
+            | CsvSchema::V9
+            | CsvSchema::V10 => record
+                .deserialize::<CsvRow>(Some(&headers))
+                .map_err(|e| e.to_string())
+                .and_then(Task::try_from),
+        };
+
+        match parsed {
+            Ok(task) => tasks.push(task),
+            Err(reason) => rejected.push(RejectedRow {
+                line,
+                record: record.iter().collect::<Vec<_>>().join(","),
+                reason,
+            }),
+        }
+    }
+
+    // Unreadable rows would be dropped by the next write, so keep a copy.
+    if schema != CsvSchema::CURRENT || !rejected.is_empty() {
+        let mut backup = path.file_name().unwrap_or_default().to_owned();
+        backup.push(format!(".v{}.bak", schema.version()));
+        fs::copy(path, path.with_file_name(backup))?;
+    }
+
+    if schema == CsvSchema::CURRENT {
+        return Ok(MigrationReport {
+            schema,
+            migrated: 0,
+            rejected,
+        });
+    }
+
+    write_tasks(path, &tasks)?;
+
+    Ok(MigrationReport {
+        schema,
+        migrated: tasks.len(),
+        rejected,
+    })
+}
+
+/// A row of the v2 layout
+#[derive(Deserialize)]
+struct TaskV2 {
+    id: u32,
+    title: String,
+    description: String,
+    completed: bool,
+}
+
+impl TaskV2 {
+    fn upgrade(self, now: DateTime<Utc>) -> Task {
+        completed_task(
+            Task::new(self.id, self.title, self.description, now),
+            self.completed,
+            now,
+        )
+    }
+}
+
+/// Marks a migrated task done if the old layout said it was complete
+fn completed_task(mut task: Task, completed: bool, now: DateTime<Utc>) -> Task {
+    if completed {
+        task.status = TaskStatus::Done;
+        task.completed_at = Some(now);
+    }
+    task
+}
+
+/// Converts one `task_name,task_description,task_complete` row
+fn parse_v1(record: &StringRecord, id: u32, now: DateTime<Utc>) -> Result<Task, String> {
+    if record.len() != 3 {
+        return Err(format!("expected 3 fields, found {}", record.len()));
+    }
+
+    let title = record[0].trim();
+    if title.is_empty() {
+        return Err("task_name is empty".to_string());
+    }
+
+    let completed = match record[2].trim().to_lowercase().as_str() {
+        "yes" | "y" | "true" => true,
+        "no" | "n" | "false" => false,
+        other => {
+            return Err(format!(
+                "task_complete must be yes or no, found '{}'",
+                other
+            ))
+        }
+    };
+
+    let task = Task::new(id, title.to_string(), record[1].trim().to_string(), now);
+    Ok(completed_task(task, completed, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{CsvStore, TaskStore};
+
+    #[test]
+    fn migrates_legacy_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.csv");
+        fs::write(
+            &path,
+            "task_name,task_description,task_complete\n\
+             blockchain,create project,no\n\
+             smart contract,learn it,yes\n\
+             ,no name,no\n\
+             play batman,I must play it,maybe\n\
+             play superman,I must play it,No\n\
+             play spiderman\n",
+        )
+        .unwrap();
+
+        let now = Utc::now();
+        let report = migrate(&path, now).unwrap();
+        assert_eq!(report.schema, CsvSchema::V1);
+        assert_eq!(report.migrated, 3);
+        assert_eq!(
+            report.rejected.iter().map(|r| r.line).collect::<Vec<_>>(),
+            vec![4, 5, 7]
+        );
+
+        let report = migrate(&path, now).unwrap();
+        assert_eq!(report.schema, CsvSchema::CURRENT);
+        assert_eq!(report.migrated, 0);
+        assert!(report.rejected.is_empty());
+
+        let tasks = CsvStore::new(&path).list().unwrap();
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[1].id, 2);
+        assert_eq!(tasks[1].status, TaskStatus::Done);
+        assert_eq!(tasks[1].completed_at, Some(now));
+        assert_eq!(tasks[2].title, "play superman");
+        assert_eq!(tasks[2].status, TaskStatus::Todo);
+        assert!(dir.path().join("tasks.csv.v1.bak").exists());
+    }
+
+    #[test]
+    fn rejects_unknown_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.csv");
+        fs::write(&path, "name,done\nx,1\n").unwrap();
+        assert!(matches!(
+            migrate(&path, Utc::now()),
+            Err(StoreError::UnknownSchema(_))
+        ));
+    }
+
+    #[test]
+    fn migrates_completed_flag_to_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.csv");
+        fs::write(
+            &path,
+            "id,title,description,completed\n\
+             4,blockchain,create project,false\n\
+             9,smart contract,learn it,true\n",
+        )
+        .unwrap();
+
+        let report = migrate(&path, Utc::now()).unwrap();
+        assert_eq!(report.schema, CsvSchema::V2);
+        assert_eq!(report.migrated, 2);
+
+        let tasks = CsvStore::new(&path).list().unwrap();
+        assert_eq!(tasks[0].id, 4);
+        assert_eq!(tasks[0].status, TaskStatus::Todo);
+        assert_eq!(tasks[1].id, 9);
+        assert_eq!(tasks[1].status, TaskStatus::Done);
+    }
+
+    #[test]
+    fn adds_projects_and_tags_to_flat_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.csv");
+        fs::write(
+            &path,
+            "id,title,description,status,priority,due_date,created_at,updated_at,completed_at,\
+             parent_id,blocked_by,owner_id,shared,deleted_at,version,recurrence,reminders\n\
+             3,file taxes,,todo,high,2026-10-15,2026-10-01T09:00:00Z,2026-10-01T09:00:00Z,,,,1,\
+             false,,2,,\"[{\"\"days_before\"\":1,\"\"at\"\":\"\"09:00:00\"\"}]\"\n",
+        )
+        .unwrap();
+
+        let report = migrate(&path, Utc::now()).unwrap();
+        assert_eq!(report.schema, CsvSchema::V9);
+        assert_eq!(report.migrated, 1);
+        assert!(report.rejected.is_empty());
+
+        let tasks = CsvStore::new(&path).list().unwrap();
+        assert_eq!(tasks[0].title, "file taxes");
+        assert_eq!(tasks[0].reminders.len(), 1);
+        assert_eq!(tasks[0].project_id, None);
+        assert!(tasks[0].tags.is_empty());
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with(",reminders,project_id,tags"));
+    }
+}
@@ -0,0 +1,378 @@
+use super::csv_migrate::{self, MigrationReport};
+use super::{id_after, StoreError, TaskStore, TaskWrite};
+use crate::task::{NewTask, Priority, Task, TaskStatus};
+use chrono::{DateTime, NaiveDate, Utc};
+use csv::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// One row of `tasks.csv`
+///
+/// Mirrors `Task`, except that `blocked_by` is written as `2;5` and
+/// `recurrence`, `reminders` and `tags` as JSON since a CSV cell cannot hold
+/// a list or an object. Columns added after v3 default when missing, so
+/// rows of any layout from v3 on read as-is.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct CsvRow {
+    id: u32,
+    title: String,
+    description: String,
+    status: TaskStatus,
+    priority: Priority,
+    due_date: Option<NaiveDate>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    parent_id: Option<u32>,
+    #[serde(default)]
+    blocked_by: String,
+    #[serde(default)]
+    owner_id: Option<u32>,
+    #[serde(default)]
+    shared: bool,
+    #[serde(default)]
+    deleted_at: Option<DateTime<Utc>>,
+    #[serde(default = "first_version")]
+    version: u32,
+    #[serde(default)]
+    recurrence: String,
+    #[serde(default)]
+    reminders: String,
+    #[serde(default)]
+    project_id: Option<u32>,
+    #[serde(default)]
+    tags: String,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl From<&Task> for CsvRow {
+    fn from(task: &Task) -> Self {
+        CsvRow {
+            id: task.id,
+            title: task.title.clone(),
+            description: task.description.clone(),
+            status: task.status,
+            priority: task.priority,
+            due_date: task.due_date,
+            created_at: task.created_at,
+            updated_at: task.updated_at,
+            completed_at: task.completed_at,
+            parent_id: task.parent_id,
+            blocked_by: task
+                .blocked_by
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(";"),
+            owner_id: task.owner_id,
+            shared: task.shared,
+            deleted_at: task.deleted_at,
+            version: task.version,
+            recurrence: task
+                .recurrence
+                .as_ref()
+                .map(|rule| serde_json::to_string(rule).expect("rules serialize to JSON"))
+                .unwrap_or_default(),
+            reminders: match task.reminders.as_slice() {
+                [] => String::new(),
+                reminders => serde_json::to_string(reminders).expect("reminders serialize to JSON"),
+            },
+            project_id: task.project_id,
+            tags: match task.tags.as_slice() {
+                [] => String::new(),
+                tags => serde_json::to_string(tags).expect("tags serialize to JSON"),
+            },
+        }
+    }
+}
+
+impl TryFrom<CsvRow> for Task {
+    type Error = String;
+
+    fn try_from(row: CsvRow) -> Result<Self, Self::Error> {
+        let blocked_by = row
+            .blocked_by
+            .split(';')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse()
+                    .map_err(|_| format!("invalid blocked_by id '{}'", id))
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+        let recurrence = match row.recurrence.trim() {
+            "" => None,
+            json => {
+                Some(serde_json::from_str(json).map_err(|e| format!("invalid recurrence: {}", e))?)
+            }
+        };
+        let reminders = match row.reminders.trim() {
+            "" => Vec::new(),
+            json => serde_json::from_str(json).map_err(|e| format!("invalid reminders: {}", e))?,
+        };
+        let tags = match row.tags.trim() {
+            "" => Vec::new(),
+            json => serde_json::from_str(json).map_err(|e| format!("invalid tags: {}", e))?,
+        };
+
+        Ok(Task {
+            id: row.id,
+            title: row.title,
+            description: row.description,
+            status: row.status,
+            priority: row.priority,
+            due_date: row.due_date,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            completed_at: row.completed_at,
+            parent_id: row.parent_id,
+            blocked_by,
+            owner_id: row.owner_id,
+            shared: row.shared,
+            deleted_at: row.deleted_at,
+            version: row.version,
+            recurrence,
+            reminders,
+            project_id: row.project_id,
+            tags,
+        })
+    }
+}
+
+/// Stores every task as a row of a single CSV file
+///
+/// Each mutation reads the whole file and writes it back out while holding
+/// `lock`, so concurrent requests cannot interleave their read-modify-write
+/// cycles and lose each other's changes.
+///
+/// Deleting the task with the highest id records that id in a file next to
+/// the CSV (`tasks.csv.last_id`), so it is never handed out again.
+pub struct CsvStore {
+    path: PathBuf,
+    last_id_path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl CsvStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let mut last_id_name = path.file_name().unwrap_or_default().to_owned();
+        last_id_name.push(".last_id");
+        CsvStore {
+            last_id_path: path.with_file_name(last_id_name),
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Opens the file at `path`, first migrating it to the current layout
+    pub fn open(path: impl AsRef<Path>) -> Result<(Self, MigrationReport), StoreError> {
+        let report = csv_migrate::migrate(path.as_ref(), Utc::now())?;
+        Ok((CsvStore::new(path), report))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        // The guarded data is `()`, so a poisoned lock carries no broken state.
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn load(&self) -> Result<Vec<Task>, StoreError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(_) => return Ok(Vec::new()), // Return empty vec if file doesn't exist
+        };
+
+        let mut reader = Reader::from_reader(BufReader::new(file));
+        let mut tasks = Vec::new();
+
+        for result in reader.deserialize::<CsvRow>() {
+            match result.map_err(|e| e.to_string()).and_then(Task::try_from) {
+                Ok(task) => tasks.push(task),
+                Err(_) => continue, // Skip invalid rows, reported by `open`
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    fn save(&self, tasks: &[Task]) -> Result<(), StoreError> {
+        write_tasks(&self.path, tasks)
+    }
+
+    /// The highest id ever stored, counting deleted tasks
+    fn last_id(&self, tasks: &[Task]) -> Result<u32, StoreError> {
+        let recorded = match fs::read_to_string(&self.last_id_path) {
+            Ok(text) => text.trim().parse().unwrap_or(0),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        let stored = tasks.iter().map(|task| task.id).max().unwrap_or(0);
+        Ok(recorded.max(stored))
+    }
+
+    /// Records `last_id` before the tasks holding it are deleted
+    fn save_last_id(&self, last_id: u32) -> Result<(), StoreError> {
+        fs::write(&self.last_id_path, format!("{}\n", last_id))?;
+        Ok(())
+    }
+}
+
+/// Writes `tasks` to `path` in the current layout
+pub(super) fn write_tasks(path: &Path, tasks: &[Task]) -> Result<(), StoreError> {
+    write_rows(path, tasks.iter().map(CsvRow::from))
+}
+
+/// Writes `tasks` as CSV in the `tasks.csv` layout, header first
+pub(crate) fn write_csv<W: Write>(writer: W, tasks: &[Task]) -> Result<(), StoreError> {
+    let mut writer = Writer::from_writer(writer);
+    for task in tasks {
+        writer.serialize(CsvRow::from(task))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `rows` to a temp file next to `path` and renames it into place,
+/// so a crash mid-write never leaves a truncated file
+pub(super) fn write_rows<R: Serialize>(
+    path: &Path,
+    rows: impl IntoIterator<Item = R>,
+) -> Result<(), StoreError> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let file = File::create(&tmp_path)?;
+    let mut writer = Writer::from_writer(BufWriter::new(&file));
+
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()?;
+    drop(writer);
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+impl TaskStore for CsvStore {
+    fn list(&self) -> Result<Vec<Task>, StoreError> {
+        let _guard = self.lock();
+        self.load()
+    }
+
+    fn get(&self, id: u32) -> Result<Option<Task>, StoreError> {
+        let _guard = self.lock();
+        Ok(self.load()?.into_iter().find(|task| task.id == id))
+    }
+
+    fn insert(&self, task: NewTask, now: DateTime<Utc>) -> Result<Task, StoreError> {
+        let _guard = self.lock();
+        let mut tasks = self.load()?;
+
+        let id = match task.id {
+            Some(id) if tasks.iter().any(|item| item.id == id) => {
+                return Err(StoreError::DuplicateId(id))
+            }
+            Some(id) => id,
+            None => id_after(self.last_id(&tasks)?)?,
+        };
+
+        let task = task.into_task(id, now);
+        tasks.push(task.clone());
+        self.save(&tasks)?;
+        Ok(task)
+    }
+
+    fn update(&self, task: Task) -> Result<bool, StoreError> {
+        let _guard = self.lock();
+        let mut tasks = self.load()?;
+
+        if let Some(index) = tasks.iter().position(|item| item.id == task.id) {
+            if tasks[index].version + 1 != task.version {
+                return Err(StoreError::StaleVersion(task.id));
+            }
+            tasks[index] = task;
+            self.save(&tasks)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn delete(&self, id: u32) -> Result<bool, StoreError> {
+        let _guard = self.lock();
+        let mut tasks = self.load()?;
+
+        if let Some(index) = tasks.iter().position(|item| item.id == id) {
+            self.save_last_id(self.last_id(&tasks)?)?;
+            tasks.remove(index);
+            self.save(&tasks)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn next_id(&self) -> Result<u32, StoreError> {
+        let _guard = self.lock();
+        id_after(self.last_id(&self.load()?)?)
+    }
+
+    fn commit(&self, writes: Vec<TaskWrite>) -> Result<Vec<u32>, StoreError> {
+        let _guard = self.lock();
+        let mut tasks = self.load()?;
+        let last_id = self.last_id(&tasks)?;
+        let mut next_id = id_after(last_id)?;
+        let mut created = Vec::new();
+        let deleted = writes
+            .iter()
+            .any(|write| matches!(write, TaskWrite::Delete(_)));
+
+        // Applied to the loaded copy first, so a failing write leaves the
+        // file untouched.
+        for write in writes {
+            let position = |id: u32| tasks.iter().position(|item| item.id == id);
+            match write {
+                TaskWrite::Insert(task) => {
+                    if position(task.id).is_some() {
+                        return Err(StoreError::DuplicateId(task.id));
+                    }
+                    next_id = next_id.max(id_after(task.id)?);
+                    tasks.push(task);
+                }
+                TaskWrite::Create(mut task) => {
+                    task.id = next_id;
+                    next_id = id_after(next_id)?;
+                    created.push(task.id);
+                    tasks.push(task);
+                }
+                TaskWrite::Update(task) => match position(task.id) {
+                    Some(index) if tasks[index].version + 1 == task.version => {
+                        tasks[index] = task;
+                    }
+                    _ => return Err(StoreError::StaleVersion(task.id)),
+                },
+                TaskWrite::Delete(id) => {
+                    if let Some(index) = position(id) {
+                        tasks.remove(index);
+                    }
+                }
+            }
+        }
+        if deleted {
+            self.save_last_id(last_id)?;
+        }
+        self.save(&tasks)?;
+        Ok(created)
+    }
+}
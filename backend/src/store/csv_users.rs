@@ -0,0 +1,83 @@
+use super::csv_store::write_rows;
+use super::{StoreError, UserStore};
+use crate::auth::User;
+use chrono::{DateTime, Utc};
+use csv::Reader;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Stores user accounts as rows of a CSV file, `users.csv` next to the tasks
+///
+/// Locked the same way as `CsvStore`, so two sign-ups cannot claim the same
+/// id or username.
+pub struct CsvUserStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl CsvUserStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        CsvUserStore {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn load(&self) -> Result<Vec<User>, StoreError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut reader = Reader::from_reader(BufReader::new(file));
+        let users = reader.deserialize().collect::<Result<Vec<User>, _>>()?;
+        Ok(users)
+    }
+}
+
+impl UserStore for CsvUserStore {
+    fn get_user(&self, id: u32) -> Result<Option<User>, StoreError> {
+        let _guard = self.lock();
+        Ok(self.load()?.into_iter().find(|user| user.id == id))
+    }
+
+    fn find_user(&self, username: &str) -> Result<Option<User>, StoreError> {
+        let _guard = self.lock();
+        Ok(self
+            .load()?
+            .into_iter()
+            .find(|user| user.username == username))
+    }
+
+    fn insert_user(
+        &self,
+        username: &str,
+        email: Option<&str>,
+        password_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<User, StoreError> {
+        let _guard = self.lock();
+        let mut users = self.load()?;
+        if users.iter().any(|user| user.username == username) {
+            return Err(StoreError::DuplicateUsername(username.to_string()));
+        }
+
+        let user = User {
+            id: users.iter().map(|user| user.id).max().unwrap_or(0) + 1,
+            username: username.to_string(),
+            password_hash: password_hash.to_string(),
+            created_at: now,
+            email: email.map(str::to_string),
+        };
+        users.push(user.clone());
+        write_rows(&self.path, &users)?;
+        Ok(user)
+    }
+}
@@ -0,0 +1,79 @@
+use super::jsonl_webhooks::{load, save};
+use super::{CommentStore, StoreError};
+use crate::comments::Comment;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Keeps comments as a JSON Lines file, oldest first, rewritten through a
+/// temporary file on every edit or deletion
+pub struct JsonlCommentStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonlCommentStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        JsonlCommentStore {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CommentStore for JsonlCommentStore {
+    fn comments(&self, task_id: u32) -> Result<Vec<Comment>, StoreError> {
+        let _guard = self.lock();
+        Ok(load::<Comment>(&self.path)?
+            .into_iter()
+            .filter(|comment| comment.task_id == task_id)
+            .collect())
+    }
+
+    fn insert_comment(&self, mut comment: Comment) -> Result<Comment, StoreError> {
+        let _guard = self.lock();
+        let mut comments: Vec<Comment> = load(&self.path)?;
+        comment.id = comments.iter().map(|comment| comment.id).max().unwrap_or(0) + 1;
+        comments.push(comment.clone());
+        save(&self.path, &comments)?;
+        Ok(comment)
+    }
+
+    fn update_comment(&self, comment: Comment) -> Result<bool, StoreError> {
+        let _guard = self.lock();
+        let mut comments: Vec<Comment> = load(&self.path)?;
+        let Some(stored) = comments.iter_mut().find(|stored| stored.id == comment.id) else {
+            return Ok(false);
+        };
+        *stored = comment;
+        save(&self.path, &comments)?;
+        Ok(true)
+    }
+
+    fn delete_comment(&self, id: u32) -> Result<bool, StoreError> {
+        let _guard = self.lock();
+        let mut comments: Vec<Comment> = load(&self.path)?;
+        let before = comments.len();
+        comments.retain(|comment| comment.id != id);
+        if comments.len() == before {
+            return Ok(false);
+        }
+        save(&self.path, &comments)?;
+        Ok(true)
+    }
+
+    fn delete_comments(&self, task_id: u32) -> Result<usize, StoreError> {
+        let _guard = self.lock();
+        let mut comments: Vec<Comment> = load(&self.path)?;
+        let before = comments.len();
+        comments.retain(|comment| comment.task_id != task_id);
+        let deleted = before - comments.len();
+        if deleted > 0 {
+            save(&self.path, &comments)?;
+        }
+        Ok(deleted)
+    }
+}
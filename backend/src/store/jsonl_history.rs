@@ -0,0 +1,71 @@
+use super::{HistoryStore, StoreError};
+use crate::history::Revision;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Keeps the audit log as a JSON Lines file, one revision per line
+///
+/// The file is only ever appended to. Lines that cannot be parsed, such as
+/// one cut short by a crash, are skipped when reading.
+pub struct JsonlHistoryStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonlHistoryStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        JsonlHistoryStore {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn load(&self, task_id: u32) -> Result<Vec<Revision>, StoreError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut revisions = Vec::new();
+        for line in BufReader::new(file).lines() {
+            match serde_json::from_str::<Revision>(&line?) {
+                Ok(revision) if revision.task_id == task_id => revisions.push(revision),
+                Ok(_) => {}
+                Err(e) => warn!("skipped unreadable line in {}: {}", self.path.display(), e),
+            }
+        }
+        Ok(revisions)
+    }
+}
+
+impl HistoryStore for JsonlHistoryStore {
+    fn append(&self, mut revision: Revision) -> Result<Revision, StoreError> {
+        let _guard = self.lock();
+        revision.revision = self
+            .load(revision.task_id)?
+            .last()
+            .map_or(1, |last| last.revision + 1);
+
+        let mut line = serde_json::to_string(&revision)?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(revision)
+    }
+
+    fn history(&self, task_id: u32) -> Result<Vec<Revision>, StoreError> {
+        let _guard = self.lock();
+        self.load(task_id)
+    }
+}
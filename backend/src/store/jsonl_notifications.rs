@@ -0,0 +1,49 @@
+use super::jsonl_webhooks::{append, load};
+use super::{NotificationStore, StoreError};
+use crate::notifications::Notification;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Keeps the notification outbox as a JSON Lines file, one notification per
+/// line
+///
+/// Like the audit log the file is only ever appended to, and unreadable
+/// lines are skipped when reading.
+pub struct JsonlNotificationStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonlNotificationStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        JsonlNotificationStore {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl NotificationStore for JsonlNotificationStore {
+    fn append(&self, mut notification: Notification) -> Result<Option<Notification>, StoreError> {
+        let _guard = self.lock();
+        let sent: Vec<Notification> = load(&self.path)?;
+        if sent.iter().any(|earlier| earlier.key == notification.key) {
+            return Ok(None);
+        }
+        notification.id = sent.iter().map(|earlier| earlier.id).max().unwrap_or(0) + 1;
+        append(&self.path, &notification)?;
+        Ok(Some(notification))
+    }
+
+    fn notifications(&self, user_id: u32) -> Result<Vec<Notification>, StoreError> {
+        let _guard = self.lock();
+        Ok(load::<Notification>(&self.path)?
+            .into_iter()
+            .filter(|notification| notification.user_id == user_id)
+            .collect())
+    }
+}
@@ -0,0 +1,53 @@
+use super::jsonl_webhooks::{load, save};
+use super::{ProjectStore, StoreError};
+use crate::projects::Project;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Keeps projects as a JSON Lines file, rewritten through a temporary file
+/// on every change like the webhooks file
+pub struct JsonlProjectStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonlProjectStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        JsonlProjectStore {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ProjectStore for JsonlProjectStore {
+    fn projects(&self) -> Result<Vec<Project>, StoreError> {
+        let _guard = self.lock();
+        load(&self.path)
+    }
+
+    fn insert_project(&self, mut project: Project) -> Result<Project, StoreError> {
+        let _guard = self.lock();
+        let mut projects: Vec<Project> = load(&self.path)?;
+        project.id = projects.iter().map(|project| project.id).max().unwrap_or(0) + 1;
+        projects.push(project.clone());
+        save(&self.path, &projects)?;
+        Ok(project)
+    }
+
+    fn delete_project(&self, id: u32) -> Result<bool, StoreError> {
+        let _guard = self.lock();
+        let mut projects: Vec<Project> = load(&self.path)?;
+        let before = projects.len();
+        projects.retain(|project| project.id != id);
+        if projects.len() == before {
+            return Ok(false);
+        }
+        save(&self.path, &projects)?;
+        Ok(true)
+    }
+}
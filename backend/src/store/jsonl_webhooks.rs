@@ -0,0 +1,135 @@
+use super::{StoreError, WebhookStore};
+use crate::webhooks::{Delivery, Webhook};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Keeps webhooks and their delivery log as JSON Lines files
+///
+/// The hooks file is rewritten through a temporary file on every change,
+/// like `tasks.csv`; the delivery log is only ever appended to. Unreadable
+/// lines are skipped when reading, as in `JsonlHistoryStore`.
+pub struct JsonlWebhookStore {
+    webhooks: PathBuf,
+    deliveries: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonlWebhookStore {
+    pub fn new(webhooks: impl AsRef<Path>, deliveries: impl AsRef<Path>) -> Self {
+        JsonlWebhookStore {
+            webhooks: webhooks.as_ref().to_path_buf(),
+            deliveries: deliveries.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Reads every readable line of the JSON Lines file at `path`
+pub(super) fn load<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, StoreError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("skipped unreadable line in {}: {}", path.display(), e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Replaces the JSON Lines file at `path` with `entries`, one per line,
+/// through a temporary file
+pub(super) fn save<T: Serialize>(path: &Path, entries: &[T]) -> Result<(), StoreError> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let file = File::create(&tmp_path)?;
+    let mut writer = BufWriter::new(&file);
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    drop(writer);
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Appends `entry` to the JSON Lines file at `path` as one line
+pub(super) fn append<T: Serialize>(path: &Path, entry: &T) -> Result<(), StoreError> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    Ok(())
+}
+
+impl WebhookStore for JsonlWebhookStore {
+    fn webhooks(&self) -> Result<Vec<Webhook>, StoreError> {
+        let _guard = self.lock();
+        load(&self.webhooks)
+    }
+
+    fn insert_webhook(&self, mut webhook: Webhook) -> Result<Webhook, StoreError> {
+        let _guard = self.lock();
+        let mut webhooks: Vec<Webhook> = load(&self.webhooks)?;
+        // Skip ids still named in the delivery log, so a new hook does not
+        // inherit the deliveries of a deleted one.
+        let logged = load::<Delivery>(&self.deliveries)?
+            .into_iter()
+            .map(|delivery| delivery.webhook_id);
+        webhook.id = webhooks
+            .iter()
+            .map(|webhook| webhook.id)
+            .chain(logged)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        webhooks.push(webhook.clone());
+        save(&self.webhooks, &webhooks)?;
+        Ok(webhook)
+    }
+
+    fn delete_webhook(&self, id: u32) -> Result<bool, StoreError> {
+        let _guard = self.lock();
+        let mut webhooks: Vec<Webhook> = load(&self.webhooks)?;
+        let before = webhooks.len();
+        webhooks.retain(|webhook| webhook.id != id);
+        if webhooks.len() == before {
+            return Ok(false);
+        }
+        save(&self.webhooks, &webhooks)?;
+        Ok(true)
+    }
+
+    fn record_delivery(&self, delivery: Delivery) -> Result<(), StoreError> {
+        let _guard = self.lock();
+        append(&self.deliveries, &delivery)
+    }
+
+    fn deliveries(&self, webhook_id: u32) -> Result<Vec<Delivery>, StoreError> {
+        let _guard = self.lock();
+        Ok(load::<Delivery>(&self.deliveries)?
+            .into_iter()
+            .filter(|delivery| delivery.webhook_id == webhook_id)
+            .collect())
+    }
+}
@@ -0,0 +1,792 @@
+use super::{
+    id_after, CommentStore, HistoryStore, NotificationStore, ProjectStore, StoreError, TaskStore,
+    TaskWrite, UserStore, WebhookStore,
+};
+use crate::auth::User;
+use crate::comments::Comment;
+use crate::history::Revision;
+use crate::notifications::Notification;
+use crate::projects::Project;
+use crate::reminder::Reminder;
+use crate::task::{NewTask, Task};
+use crate::webhooks::{Delivery, Webhook};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Type;
+use rusqlite::{ffi, params, Connection, ErrorCode, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Schema changes, applied in order
+///
+/// `PRAGMA user_version` records how many have run, so each database is
+/// brought forward from wherever it was left.
+const MIGRATIONS: &[&str] = &[
+    // 1: the original flat layout
+    "CREATE TABLE IF NOT EXISTS tasks (
+        id          INTEGER PRIMARY KEY,
+        title       TEXT NOT NULL,
+        description TEXT NOT NULL,
+        completed   INTEGER NOT NULL
+    );",
+    // 2: workflow status, priority, due date and server timestamps
+    "ALTER TABLE tasks ADD COLUMN status TEXT NOT NULL DEFAULT 'todo';
+     ALTER TABLE tasks ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal';
+     ALTER TABLE tasks ADD COLUMN due_date TEXT;
+     ALTER TABLE tasks ADD COLUMN created_at TEXT NOT NULL DEFAULT '';
+     ALTER TABLE tasks ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+     ALTER TABLE tasks ADD COLUMN completed_at TEXT;
+     UPDATE tasks SET
+         status = CASE completed WHEN 0 THEN 'todo' ELSE 'done' END,
+         created_at = strftime('%Y-%m-%d %H:%M:%f', 'now'),
+         updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now');
+     UPDATE tasks SET completed_at = updated_at WHERE status = 'done';
+     ALTER TABLE tasks DROP COLUMN completed;",
+    // 3: subtasks and blockers
+    "ALTER TABLE tasks ADD COLUMN parent_id INTEGER;
+     CREATE TABLE task_blockers (
+         task_id    INTEGER NOT NULL,
+         blocker_id INTEGER NOT NULL,
+         PRIMARY KEY (task_id, blocker_id)
+     );",
+    // 4: user accounts and task ownership
+    "CREATE TABLE users (
+         id            INTEGER PRIMARY KEY,
+         username      TEXT NOT NULL UNIQUE,
+         password_hash TEXT NOT NULL,
+         created_at    TEXT NOT NULL
+     );
+     ALTER TABLE tasks ADD COLUMN owner_id INTEGER;
+     ALTER TABLE tasks ADD COLUMN shared INTEGER NOT NULL DEFAULT 0;",
+    // 5: audit log, one JSON-encoded revision per row
+    "CREATE TABLE task_history (
+         task_id  INTEGER NOT NULL,
+         revision INTEGER NOT NULL,
+         entry    TEXT NOT NULL,
+         PRIMARY KEY (task_id, revision)
+     );",
+    // 6: trash
+    "ALTER TABLE tasks ADD COLUMN deleted_at TEXT;",
+    // 7: optimistic concurrency
+    "ALTER TABLE tasks ADD COLUMN version INTEGER NOT NULL DEFAULT 1;",
+    // 8: webhooks and their delivery log, JSON-encoded like the audit log
+    "CREATE TABLE webhooks (
+         id    INTEGER PRIMARY KEY AUTOINCREMENT,
+         entry TEXT NOT NULL
+     );
+     CREATE TABLE webhook_deliveries (
+         id         INTEGER PRIMARY KEY,
+         webhook_id INTEGER NOT NULL,
+         entry      TEXT NOT NULL
+     );
+     CREATE INDEX webhook_deliveries_by_hook ON webhook_deliveries (webhook_id);",
+    // 9: recurring tasks, the rule JSON-encoded
+    "ALTER TABLE tasks ADD COLUMN recurrence TEXT;",
+    // 10: reminders, e-mail addresses and the notification outbox
+    "ALTER TABLE tasks ADD COLUMN reminders TEXT;
+     ALTER TABLE users ADD COLUMN email TEXT;
+     CREATE TABLE notifications (
+         id      INTEGER PRIMARY KEY AUTOINCREMENT,
+         key     TEXT NOT NULL UNIQUE,
+         user_id INTEGER NOT NULL,
+         entry   TEXT NOT NULL
+     );
+     CREATE INDEX notifications_by_user ON notifications (user_id);",
+    // 11: projects and tags, the tags JSON-encoded
+    "CREATE TABLE projects (
+         id    INTEGER PRIMARY KEY AUTOINCREMENT,
+         entry TEXT NOT NULL
+     );
+     ALTER TABLE tasks ADD COLUMN project_id INTEGER;
+     ALTER TABLE tasks ADD COLUMN tags TEXT;",
+    // 12: comments on tasks
+    "CREATE TABLE comments (
+         id      INTEGER PRIMARY KEY AUTOINCREMENT,
+         task_id INTEGER NOT NULL,
+         entry   TEXT NOT NULL
+     );
+     CREATE INDEX comments_by_task ON comments (task_id);",
+    // 13: never reuse task ids, seeding the counter from the audit log so
+    // ids deleted before this migration stay retired too
+    "CREATE TABLE tasks_new (
+         id           INTEGER PRIMARY KEY AUTOINCREMENT,
+         title        TEXT NOT NULL,
+         description  TEXT NOT NULL,
+         status       TEXT NOT NULL DEFAULT 'todo',
+         priority     TEXT NOT NULL DEFAULT 'normal',
+         due_date     TEXT,
+         created_at   TEXT NOT NULL DEFAULT '',
+         updated_at   TEXT NOT NULL DEFAULT '',
+         completed_at TEXT,
+         parent_id    INTEGER,
+         owner_id     INTEGER,
+         shared       INTEGER NOT NULL DEFAULT 0,
+         deleted_at   TEXT,
+         version      INTEGER NOT NULL DEFAULT 1,
+         recurrence   TEXT,
+         reminders    TEXT,
+         project_id   INTEGER,
+         tags         TEXT
+     );
+     INSERT INTO tasks_new SELECT id, title, description, status, priority, due_date,
+         created_at, updated_at, completed_at, parent_id, owner_id, shared, deleted_at,
+         version, recurrence, reminders, project_id, tags FROM tasks;
+     DROP TABLE tasks;
+     ALTER TABLE tasks_new RENAME TO tasks;
+     DELETE FROM sqlite_sequence WHERE name = 'tasks';
+     INSERT INTO sqlite_sequence (name, seq)
+         SELECT 'tasks', COALESCE(MAX(id), 0)
+         FROM (SELECT id FROM tasks UNION ALL SELECT task_id FROM task_history);",
+];
+
+const COLUMNS: &str = "id, title, description, status, priority, due_date, created_at, \
+                       updated_at, completed_at, parent_id, owner_id, shared, deleted_at, version, \
+                       recurrence, reminders, project_id, tags";
+
+/// Stores tasks in an embedded SQLite database, one row per task
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path` and runs pending migrations
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let mut conn = Connection::open(path)?;
+        migrate(&mut conn)?;
+
+        Ok(SqliteStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // A panic while holding the lock cannot leave SQLite half-written,
+        // so a poisoned mutex is still safe to use.
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), StoreError> {
+    let applied: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (version, sql) in (1u32..).zip(MIGRATIONS).skip(applied as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Reads a text column holding a name `FromStr` accepts, such as a status
+fn parsed<T: FromStr<Err = String>>(row: &Row<'_>, index: usize) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    text.parse()
+        .map_err(|e: String| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, e.into()))
+}
+
+/// Reads a nullable text column holding JSON, such as a `Recurrence`
+fn json_column<T: DeserializeOwned>(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<T>> {
+    let text: Option<String> = row.get(index)?;
+    text.map(|json| {
+        serde_json::from_str(&json)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, e.into()))
+    })
+    .transpose()
+}
+
+fn task_from_row(row: &Row<'_>) -> rusqlite::Result<Task> {
+    Ok(Task {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        status: parsed(row, 3)?,
+        priority: parsed(row, 4)?,
+        due_date: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        completed_at: row.get(8)?,
+        parent_id: row.get(9)?,
+        // Filled in from `task_blockers` by the caller
+        blocked_by: Vec::new(),
+        owner_id: row.get(10)?,
+        shared: row.get(11)?,
+        deleted_at: row.get(12)?,
+        version: row.get(13)?,
+        recurrence: json_column(row, 14)?,
+        reminders: json_column::<Vec<Reminder>>(row, 15)?.unwrap_or_default(),
+        project_id: row.get(16)?,
+        tags: json_column::<Vec<String>>(row, 17)?.unwrap_or_default(),
+    })
+}
+
+/// Ids of the tasks blocking `task_id`, in the order they were added
+fn blockers_of(conn: &Connection, task_id: u32) -> rusqlite::Result<Vec<u32>> {
+    let mut stmt =
+        conn.prepare("SELECT blocker_id FROM task_blockers WHERE task_id = ?1 ORDER BY rowid")?;
+    let ids = stmt
+        .query_map(params![task_id], |row| row.get(0))?
+        .collect();
+    ids
+}
+
+/// Replaces the stored blockers of `task` with `task.blocked_by`
+fn save_blockers(conn: &Connection, task: &Task) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM task_blockers WHERE task_id = ?1",
+        params![task.id],
+    )?;
+    for blocker_id in &task.blocked_by {
+        conn.execute(
+            "INSERT OR IGNORE INTO task_blockers (task_id, blocker_id) VALUES (?1, ?2)",
+            params![task.id, blocker_id],
+        )?;
+    }
+    Ok(())
+}
+
+impl TaskStore for SqliteStore {
+    fn list(&self) -> Result<Vec<Task>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM tasks ORDER BY id", COLUMNS))?;
+        let mut tasks = stmt
+            .query_map([], task_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut blockers: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut stmt =
+            conn.prepare("SELECT task_id, blocker_id FROM task_blockers ORDER BY rowid")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for row in rows {
+            let (task_id, blocker_id) = row?;
+            blockers.entry(task_id).or_default().push(blocker_id);
+        }
+
+        for task in &mut tasks {
+            task.blocked_by = blockers.remove(&task.id).unwrap_or_default();
+        }
+        Ok(tasks)
+    }
+
+    fn get(&self, id: u32) -> Result<Option<Task>, StoreError> {
+        let conn = self.conn();
+        let task = conn
+            .query_row(
+                &format!("SELECT {} FROM tasks WHERE id = ?1", COLUMNS),
+                params![id],
+                task_from_row,
+            )
+            .optional()?;
+
+        match task {
+            Some(mut task) => {
+                task.blocked_by = blockers_of(&conn, id)?;
+                Ok(Some(task))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn insert(&self, task: NewTask, now: DateTime<Utc>) -> Result<Task, StoreError> {
+        let conn = self.conn();
+        let requested_id = task.id;
+        let mut task = task.into_task(requested_id.unwrap_or_default(), now);
+        task.id = insert_row(&conn, requested_id, &task)?;
+        Ok(task)
+    }
+
+    fn update(&self, task: Task) -> Result<bool, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let updated = update_row(&tx, &task)?;
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    fn delete(&self, id: u32) -> Result<bool, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let deleted = delete_row(&tx, id)?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    fn next_id(&self) -> Result<u32, StoreError> {
+        next_id(&self.conn())
+    }
+
+    fn commit(&self, writes: Vec<TaskWrite>) -> Result<Vec<u32>, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let mut created = Vec::new();
+        for write in writes {
+            match write {
+                TaskWrite::Insert(task) => {
+                    insert_row(&tx, Some(task.id), &task)?;
+                    save_blockers(&tx, &task)?;
+                }
+                TaskWrite::Create(mut task) => {
+                    task.id = insert_row(&tx, None, &task)?;
+                    save_blockers(&tx, &task)?;
+                    created.push(task.id);
+                }
+                TaskWrite::Update(task) => {
+                    if !update_row(&tx, &task)? {
+                        return Err(StoreError::StaleVersion(task.id));
+                    }
+                }
+                TaskWrite::Delete(id) => {
+                    delete_row(&tx, id)?;
+                }
+            }
+        }
+        // Dropping `tx` on an early return rolls every write back.
+        tx.commit()?;
+        Ok(created)
+    }
+
+    fn check(&self) -> Result<(), StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        // An empty transaction never touches the file; rewriting the schema
+        // version does, and fails on a read-only database. Dropping `tx`
+        // rolls it back.
+        let version: u32 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        tx.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get::<_, i64>(0))?;
+        tx.pragma_update(None, "user_version", version)?;
+        Ok(())
+    }
+}
+
+fn recurrence_json(task: &Task) -> Result<Option<String>, StoreError> {
+    Ok(task
+        .recurrence
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?)
+}
+
+fn reminders_json(task: &Task) -> Result<Option<String>, StoreError> {
+    match task.reminders.as_slice() {
+        [] => Ok(None),
+        reminders => Ok(Some(serde_json::to_string(reminders)?)),
+    }
+}
+
+fn tags_json(task: &Task) -> Result<Option<String>, StoreError> {
+    match task.tags.as_slice() {
+        [] => Ok(None),
+        tags => Ok(Some(serde_json::to_string(tags)?)),
+    }
+}
+
+/// One above the highest task id ever stored, as tracked by `AUTOINCREMENT`
+fn next_id(conn: &Connection) -> Result<u32, StoreError> {
+    let last: Option<u32> = conn
+        .query_row(
+            "SELECT seq FROM sqlite_sequence WHERE name = 'tasks'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    id_after(last.unwrap_or(0))
+}
+
+/// Inserts `task` under `id`, or the next free rowid if `id` is `None`, and
+/// returns the id it was stored under
+fn insert_row(conn: &Connection, id: Option<u32>, task: &Task) -> Result<u32, StoreError> {
+    // A NULL id lets SQLite pick the next rowid.
+    let result = conn.execute(
+        &format!(
+            "INSERT INTO tasks ({}) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, \
+                     ?17, ?18)",
+            COLUMNS
+        ),
+        params![
+            id,
+            task.title,
+            task.description,
+            task.status.as_str(),
+            task.priority.as_str(),
+            task.due_date,
+            task.created_at,
+            task.updated_at,
+            task.completed_at,
+            task.parent_id,
+            task.owner_id,
+            task.shared,
+            task.deleted_at,
+            task.version,
+            recurrence_json(task)?,
+            reminders_json(task)?,
+            task.project_id,
+            tags_json(task)?
+        ],
+    );
+
+    match result {
+        Ok(_) => u32::try_from(conn.last_insert_rowid()).map_err(|_| StoreError::IdsExhausted),
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if e.extended_code == ffi::SQLITE_CONSTRAINT_PRIMARYKEY =>
+        {
+            Err(StoreError::DuplicateId(id.unwrap_or(task.id)))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Overwrites the stored row of `task` if it is one version behind
+///
+/// Returns `false` if there is no such task and `StoreError::StaleVersion`
+/// if it is at any other version.
+fn update_row(conn: &Connection, task: &Task) -> Result<bool, StoreError> {
+    let changed = conn.execute(
+        "UPDATE tasks SET title = ?2, description = ?3, status = ?4, priority = ?5,
+             due_date = ?6, created_at = ?7, updated_at = ?8, completed_at = ?9,
+             parent_id = ?10, owner_id = ?11, shared = ?12, deleted_at = ?13,
+             version = ?14, recurrence = ?15, reminders = ?16, project_id = ?17,
+             tags = ?18
+         WHERE id = ?1 AND version = ?14 - 1",
+        params![
+            task.id,
+            task.title,
+            task.description,
+            task.status.as_str(),
+            task.priority.as_str(),
+            task.due_date,
+            task.created_at,
+            task.updated_at,
+            task.completed_at,
+            task.parent_id,
+            task.owner_id,
+            task.shared,
+            task.deleted_at,
+            task.version,
+            recurrence_json(task)?,
+            reminders_json(task)?,
+            task.project_id,
+            tags_json(task)?
+        ],
+    )?;
+    if changed == 0 {
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM tasks WHERE id = ?1",
+                params![task.id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        return match exists {
+            true => Err(StoreError::StaleVersion(task.id)),
+            false => Ok(false),
+        };
+    }
+    save_blockers(conn, task)?;
+    Ok(true)
+}
+
+fn delete_row(conn: &Connection, id: u32) -> rusqlite::Result<bool> {
+    let changed = conn.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
+    conn.execute("DELETE FROM task_blockers WHERE task_id = ?1", params![id])?;
+    Ok(changed > 0)
+}
+
+fn user_from_row(row: &Row<'_>) -> rusqlite::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        created_at: row.get(3)?,
+        email: row.get(4)?,
+    })
+}
+
+impl UserStore for SqliteStore {
+    fn get_user(&self, id: u32) -> Result<Option<User>, StoreError> {
+        let user = self
+            .conn()
+            .query_row(
+                "SELECT id, username, password_hash, created_at, email FROM users WHERE id = ?1",
+                params![id],
+                user_from_row,
+            )
+            .optional()?;
+        Ok(user)
+    }
+
+    fn find_user(&self, username: &str) -> Result<Option<User>, StoreError> {
+        let user = self
+            .conn()
+            .query_row(
+                "SELECT id, username, password_hash, created_at, email FROM users \
+                 WHERE username = ?1",
+                params![username],
+                user_from_row,
+            )
+            .optional()?;
+        Ok(user)
+    }
+
+    fn insert_user(
+        &self,
+        username: &str,
+        email: Option<&str>,
+        password_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<User, StoreError> {
+        let conn = self.conn();
+        let result = conn.execute(
+            "INSERT INTO users (username, password_hash, created_at, email) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![username, password_hash, now, email],
+        );
+
+        match result {
+            Ok(_) => Ok(User {
+                id: conn.last_insert_rowid() as u32,
+                username: username.to_string(),
+                password_hash: password_hash.to_string(),
+                created_at: now,
+                email: email.map(str::to_string),
+            }),
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == ErrorCode::ConstraintViolation =>
+            {
+                Err(StoreError::DuplicateUsername(username.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl HistoryStore for SqliteStore {
+    fn append(&self, mut revision: Revision) -> Result<Revision, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        revision.revision = tx.query_row(
+            "SELECT COALESCE(MAX(revision), 0) + 1 FROM task_history WHERE task_id = ?1",
+            params![revision.task_id],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "INSERT INTO task_history (task_id, revision, entry) VALUES (?1, ?2, ?3)",
+            params![
+                revision.task_id,
+                revision.revision,
+                serde_json::to_string(&revision)?
+            ],
+        )?;
+        tx.commit()?;
+        Ok(revision)
+    }
+
+    fn history(&self, task_id: u32) -> Result<Vec<Revision>, StoreError> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT entry FROM task_history WHERE task_id = ?1 ORDER BY revision")?;
+        let entries = stmt
+            .query_map(params![task_id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        entries
+            .iter()
+            .map(|entry| Ok(serde_json::from_str(entry)?))
+            .collect()
+    }
+}
+
+impl NotificationStore for SqliteStore {
+    fn append(&self, mut notification: Notification) -> Result<Option<Notification>, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let inserted = tx.execute(
+            "INSERT INTO notifications (key, user_id, entry) VALUES (?1, ?2, '') \
+             ON CONFLICT (key) DO NOTHING",
+            params![notification.key, notification.user_id],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        let id = tx.last_insert_rowid();
+        notification.id = id as u64;
+        tx.execute(
+            "UPDATE notifications SET entry = ?1 WHERE id = ?2",
+            params![serde_json::to_string(&notification)?, id],
+        )?;
+        tx.commit()?;
+        Ok(Some(notification))
+    }
+
+    fn notifications(&self, user_id: u32) -> Result<Vec<Notification>, StoreError> {
+        entries(
+            &self.conn(),
+            "SELECT entry FROM notifications WHERE user_id = ?1 ORDER BY id",
+            params![user_id],
+        )
+    }
+}
+
+/// Decodes the `entry` column of every row `sql` selects
+fn entries<T: serde::de::DeserializeOwned>(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<T>, StoreError> {
+    let mut stmt = conn.prepare(sql)?;
+    let entries = stmt
+        .query_map(params, |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries
+        .iter()
+        .map(|entry| Ok(serde_json::from_str(entry)?))
+        .collect()
+}
+
+impl WebhookStore for SqliteStore {
+    fn webhooks(&self) -> Result<Vec<Webhook>, StoreError> {
+        entries(&self.conn(), "SELECT entry FROM webhooks ORDER BY id", [])
+    }
+
+    fn insert_webhook(&self, mut webhook: Webhook) -> Result<Webhook, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        // AUTOINCREMENT never reuses the id of a deleted hook.
+        tx.execute("INSERT INTO webhooks (entry) VALUES ('')", [])?;
+        webhook.id = tx.last_insert_rowid() as u32;
+        tx.execute(
+            "UPDATE webhooks SET entry = ?1 WHERE id = ?2",
+            params![serde_json::to_string(&webhook)?, webhook.id],
+        )?;
+        tx.commit()?;
+        Ok(webhook)
+    }
+
+    fn delete_webhook(&self, id: u32) -> Result<bool, StoreError> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM webhooks WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    fn record_delivery(&self, delivery: Delivery) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO webhook_deliveries (webhook_id, entry) VALUES (?1, ?2)",
+            params![delivery.webhook_id, serde_json::to_string(&delivery)?],
+        )?;
+        Ok(())
+    }
+
+    fn deliveries(&self, webhook_id: u32) -> Result<Vec<Delivery>, StoreError> {
+        entries(
+            &self.conn(),
+            "SELECT entry FROM webhook_deliveries WHERE webhook_id = ?1 ORDER BY id",
+            params![webhook_id],
+        )
+    }
+}
+
+impl ProjectStore for SqliteStore {
+    fn projects(&self) -> Result<Vec<Project>, StoreError> {
+        entries(&self.conn(), "SELECT entry FROM projects ORDER BY id", [])
+    }
+
+    fn insert_project(&self, mut project: Project) -> Result<Project, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("INSERT INTO projects (entry) VALUES ('')", [])?;
+        project.id = tx.last_insert_rowid() as u32;
+        tx.execute(
+            "UPDATE projects SET entry = ?1 WHERE id = ?2",
+            params![serde_json::to_string(&project)?, project.id],
+        )?;
+        tx.commit()?;
+        Ok(project)
+    }
+
+    fn delete_project(&self, id: u32) -> Result<bool, StoreError> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM projects WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+}
+
+impl CommentStore for SqliteStore {
+    fn comments(&self, task_id: u32) -> Result<Vec<Comment>, StoreError> {
+        entries(
+            &self.conn(),
+            "SELECT entry FROM comments WHERE task_id = ?1 ORDER BY id",
+            params![task_id],
+        )
+    }
+
+    fn insert_comment(&self, mut comment: Comment) -> Result<Comment, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO comments (task_id, entry) VALUES (?1, '')",
+            params![comment.task_id],
+        )?;
+        comment.id = tx.last_insert_rowid() as u32;
+        tx.execute(
+            "UPDATE comments SET entry = ?1 WHERE id = ?2",
+            params![serde_json::to_string(&comment)?, comment.id],
+        )?;
+        tx.commit()?;
+        Ok(comment)
+    }
+
+    fn update_comment(&self, comment: Comment) -> Result<bool, StoreError> {
+        let updated = self.conn().execute(
+            "UPDATE comments SET entry = ?1 WHERE id = ?2",
+            params![serde_json::to_string(&comment)?, comment.id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn delete_comment(&self, id: u32) -> Result<bool, StoreError> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM comments WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_comments(&self, task_id: u32) -> Result<usize, StoreError> {
+        Ok(self
+            .conn()
+            .execute("DELETE FROM comments WHERE task_id = ?1", params![task_id])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskStatus;
+
+    #[test]
+    fn migrates_completed_column_to_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.pragma_update(None, "user_version", 1).unwrap();
+            conn.execute_batch(
+                "INSERT INTO tasks VALUES (1, 'blockchain', 'create project', 0);
+                 INSERT INTO tasks VALUES (2, 'smart contract', 'learn it', 1);",
+            )
+            .unwrap();
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+        let tasks = store.list().unwrap();
+        assert_eq!(tasks[0].status, TaskStatus::Todo);
+        assert_eq!(tasks[0].completed_at, None);
+        assert_eq!(tasks[1].status, TaskStatus::Done);
+        assert!(tasks[1].completed_at.is_some());
+    }
+}
@@ -0,0 +1,185 @@
+//! Tags: `/tags`, with renaming and merging across tasks
+//!
+//! Tags live on the tasks themselves, so `GET /tags` counts them over the
+//! live tasks the signed-in user can see. Renaming or merging a tag rewrites
+//! it on every task the user can edit, trashed ones too, in one write: a
+//! rename refuses to reuse a tag those tasks already carry, while a merge
+//! folds one tag into the other.
+
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::history::Changes;
+use crate::store::{TaskStore, TaskWrite};
+use crate::tag;
+use crate::task::Task;
+use chrono::Utc;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// A tag and how many tasks carry it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TagCount {
+    pub tag: String,
+    /// Live tasks you can see that carry the tag
+    pub tasks: usize,
+}
+
+/// Body of `POST /tags/<tag>/rename` and `POST /tags/<tag>/merge`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct TagTarget {
+    /// The tag to end up with
+    pub to: String,
+}
+
+/// Counts the tags on the live tasks `user` can see, by tag
+fn count(tasks: &[Task], user: &AuthUser) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for task in tasks {
+        if task.visible_to(user.id) && !task.is_trashed() {
+            for tag in &task.tags {
+                *counts.entry(tag.clone()).or_default() += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Normalizes the `<tag>` of the path and the target, rejecting the same tag
+/// twice
+fn parse(from: &str, target: TagTarget) -> Result<(String, String), ApiError> {
+    let mut errors = Vec::new();
+    let from = tag::normalize(from).map_err(|e| errors.push(FieldError::new("tag", e)));
+    let to = tag::normalize(&target.to).map_err(|e| errors.push(FieldError::new("to", e)));
+    match (from, to) {
+        (Ok(from), Ok(to)) if from == to => Err(ApiError::Validation(vec![FieldError::new(
+            "to",
+            format!("is already '{}'", from),
+        )])),
+        (Ok(from), Ok(to)) => Ok((from, to)),
+        _ => Err(ApiError::Validation(errors)),
+    }
+}
+
+/// Replaces `from` with `to` on every task `user` can edit that carries it,
+/// recording the changes and returning the count for `to`
+///
+/// With `merge` unset, fails if any of those tasks already carry `to`.
+fn retag(
+    store: &dyn TaskStore,
+    changes: &Changes<'_>,
+    user: &AuthUser,
+    from: &str,
+    to: &str,
+    merge: bool,
+) -> Result<TagCount, ApiError> {
+    let tasks = store.list()?;
+    let editable: Vec<&Task> = tasks
+        .iter()
+        .filter(|task| task.editable_by(user.id))
+        .collect();
+    if !editable.iter().any(|task| task.has_tag(from)) {
+        return Err(ApiError::NotFound(format!(
+            "no task of yours is tagged '{}'",
+            from
+        )));
+    }
+    if !merge && editable.iter().any(|task| task.has_tag(to)) {
+        return Err(ApiError::Conflict(format!(
+            "'{}' is already in use; merge the tags instead",
+            to
+        )));
+    }
+
+    let now = Utc::now();
+    let mut updated = Vec::new();
+    for before in editable.into_iter().filter(|task| task.has_tag(from)) {
+        let mut after = before.clone();
+        after.tags.retain(|tag| tag != from && tag != to);
+        after.tags.push(to.to_string());
+        after.touch(now);
+        updated.push((before.clone(), after));
+    }
+    store.commit(
+        updated
+            .iter()
+            .map(|(_, after)| TaskWrite::Update(after.clone()))
+            .collect(),
+    )?;
+    for (before, after) in &updated {
+        changes.updated(user, before, after);
+    }
+
+    Ok(TagCount {
+        tag: to.to_string(),
+        tasks: count(&store.list()?, user).remove(to).unwrap_or(0),
+    })
+}
+
+/// Lists the tags on the tasks you can see, alphabetically
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "Every tag in use", body = [TagCount])
+    )
+)]
+#[get("/tags")]
+pub fn list_tags(
+    store: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+) -> Result<Json<Vec<TagCount>>, ApiError> {
+    Ok(Json(
+        count(&store.list()?, &user)
+            .into_iter()
+            .map(|(tag, tasks)| TagCount { tag, tasks })
+            .collect(),
+    ))
+}
+
+/// Renames a tag on every task you can edit
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "The renamed tag", body = TagCount),
+        (status = 404, description = "None of your tasks carry the tag", body = ErrorBody),
+        (status = 409, description = "The new name is already in use", body = ErrorBody),
+        (status = 412, description = "One of the tasks changed meanwhile", body = ErrorBody),
+        (status = 422, description = "A tag is invalid, or both are the same", body = ErrorBody)
+    )
+)]
+#[post("/tags/<tag>/rename", data = "<target>")]
+pub fn rename_tag(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    tag: &str,
+    target: Json<TagTarget>,
+) -> Result<Json<TagCount>, ApiError> {
+    let (from, to) = parse(tag, target.into_inner())?;
+    retag(store.as_ref(), &changes, &user, &from, &to, false).map(Json)
+}
+
+/// Folds a tag into another on every task you can edit
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "The tag merged into", body = TagCount),
+        (status = 404, description = "None of your tasks carry the tag", body = ErrorBody),
+        (status = 412, description = "One of the tasks changed meanwhile", body = ErrorBody),
+        (status = 422, description = "A tag is invalid, or both are the same", body = ErrorBody)
+    )
+)]
+#[post("/tags/<tag>/merge", data = "<target>")]
+pub fn merge_tag(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    tag: &str,
+    target: Json<TagTarget>,
+) -> Result<Json<TagCount>, ApiError> {
+    let (from, to) = parse(tag, target.into_inner())?;
+    retag(store.as_ref(), &changes, &user, &from, &to, true).map(Json)
+}
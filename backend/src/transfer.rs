@@ -0,0 +1,666 @@
+//! Moving tasks in and out: `GET /tasks/export` and `POST /tasks/import`
+//!
+//! Four formats are supported:
+//!
+//! - `json`: an array of tasks, as returned by `GET /tasks/<id>`
+//! - `csv`: the `tasks.csv` layout, with `blocked_by` written as `2;5`
+//! - `ndjson`: one task object per line
+//! - `todo.txt`: one task per line in the [todo.txt] format, with the id,
+//!   due date and any status other than todo/done kept as `id:`, `due:` and
+//!   `status:` tags
+//!
+//! Imported tasks belong to the importing user. Ids are kept when given and
+//! free, and assigned otherwise; subtask and blocker links, recurrence
+//! rules, reminders, projects and tags are not imported since they rarely
+//! mean the same thing in another list. An import with a taken id or an
+//! unreadable row is rejected as a whole; `dry_run=true` reports every such
+//! problem without changing anything.
+//!
+//! [todo.txt]: https://github.com/todotxt/todo.txt
+
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::history::Changes;
+use crate::store::{self, TaskStore, TaskWrite};
+use crate::task::{NewTask, Priority, Task, TaskStatus};
+use chrono::{DateTime, NaiveDate, Utc};
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::ContentType;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Json;
+use rocket::{Request, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Largest import accepted unless `limits.import` says otherwise
+pub const DEFAULT_IMPORT_LIMIT_MIB: usize = 5;
+
+/// A file format for import and export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    Ndjson,
+    TodoTxt,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Json, Format::Csv, Format::Ndjson, Format::TodoTxt];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+            Format::TodoTxt => "todo.txt",
+        }
+    }
+
+    pub fn content_type(self) -> ContentType {
+        match self {
+            Format::Json => ContentType::JSON,
+            Format::Csv => ContentType::CSV,
+            Format::Ndjson => ContentType::new("application", "x-ndjson"),
+            Format::TodoTxt => ContentType::Plain,
+        }
+    }
+
+    /// The format a request body of type `content_type` is in, if it is one
+    pub fn from_content_type(content_type: &ContentType) -> Option<Format> {
+        match (content_type.top().as_str(), content_type.sub().as_str()) {
+            ("application", "json") => Some(Format::Json),
+            ("text", "csv") => Some(Format::Csv),
+            ("application", "x-ndjson") | ("application", "ndjson") => Some(Format::Ndjson),
+            ("text", "plain") => Some(Format::TodoTxt),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "todotxt" | "txt" => Ok(Format::TodoTxt),
+            _ => Format::ALL
+                .into_iter()
+                .find(|format| format.as_str() == s)
+                .ok_or_else(|| format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/// Writes `tasks` in `format`
+pub fn export(tasks: &[Task], format: Format) -> Result<String, ApiError> {
+    let internal = |e: &dyn fmt::Display| ApiError::Internal(format!("export failed: {}", e));
+    match format {
+        Format::Json => serde_json::to_string_pretty(tasks).map_err(|e| internal(&e)),
+        Format::Ndjson => tasks
+            .iter()
+            .map(|task| serde_json::to_string(task).map(|line| line + "\n"))
+            .collect::<Result<String, _>>()
+            .map_err(|e| internal(&e)),
+        Format::Csv => {
+            let mut out = Vec::new();
+            store::write_csv(&mut out, tasks).map_err(|e| internal(&e))?;
+            String::from_utf8(out).map_err(|e| internal(&e))
+        }
+        Format::TodoTxt => Ok(tasks.iter().map(|task| todo_line(task) + "\n").collect()),
+    }
+}
+
+fn todo_priority(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::Urgent => Some('A'),
+        Priority::High => Some('B'),
+        Priority::Normal => None,
+        Priority::Low => Some('C'),
+    }
+}
+
+fn priority_from_todo(letter: char) -> Priority {
+    match letter {
+        'A' => Priority::Urgent,
+        'B' => Priority::High,
+        _ => Priority::Low,
+    }
+}
+
+/// One task as a todo.txt line
+///
+/// Done tasks start with `x` and their completion date; todo.txt drops the
+/// priority of a done task, so it is kept as a `pri:` tag instead.
+fn todo_line(task: &Task) -> String {
+    let mut parts = Vec::new();
+    let priority = todo_priority(task.priority);
+    if task.is_done() {
+        parts.push("x".to_string());
+        if let Some(completed) = task.completed_at {
+            parts.push(completed.date_naive().to_string());
+        }
+    } else if let Some(letter) = priority {
+        parts.push(format!("({})", letter));
+    }
+    parts.push(task.created_at.date_naive().to_string());
+    parts.push(task.title.split_whitespace().collect::<Vec<_>>().join(" "));
+
+    if let Some(due) = task.due_date {
+        parts.push(format!("due:{}", due));
+    }
+    if !matches!(task.status, TaskStatus::Todo | TaskStatus::Done) {
+        parts.push(format!("status:{}", task.status));
+    }
+    if let (true, Some(letter)) = (task.is_done(), priority) {
+        parts.push(format!("pri:{}", letter));
+    }
+    parts.push(format!("id:{}", task.id));
+    parts.join(" ")
+}
+
+/// A task as read from an import, before it is checked and given an id
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ImportedTask {
+    #[serde(default)]
+    pub id: Option<u32>,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub shared: bool,
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(chrono::NaiveTime::MIN).and_utc()
+}
+
+/// Reads one todo.txt line
+fn parse_todo_line(line: &str) -> Result<ImportedTask, String> {
+    let date = |word: &str| NaiveDate::parse_from_str(word, "%Y-%m-%d").ok();
+    let mut words = line.split_whitespace().peekable();
+    let mut task = ImportedTask {
+        id: None,
+        title: String::new(),
+        description: String::new(),
+        status: TaskStatus::Todo,
+        priority: Priority::Normal,
+        due_date: None,
+        created_at: None,
+        completed_at: None,
+        shared: false,
+    };
+
+    if words.peek() == Some(&"x") {
+        words.next();
+        task.status = TaskStatus::Done;
+        task.completed_at = words.peek().and_then(|word| date(word)).map(midnight);
+        if task.completed_at.is_some() {
+            words.next();
+        }
+    }
+    if let Some(letter) = words.peek().and_then(|word| {
+        let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+        let letter = letter.chars().next().filter(|c| c.is_ascii_uppercase())?;
+        (word.len() == 3).then_some(letter)
+    }) {
+        words.next();
+        task.priority = priority_from_todo(letter);
+    }
+    task.created_at = words.peek().and_then(|word| date(word)).map(midnight);
+    if task.created_at.is_some() {
+        words.next();
+    }
+
+    let mut title = Vec::new();
+    for word in words {
+        match word.split_once(':') {
+            Some(("id", id)) => {
+                task.id = Some(id.parse().map_err(|_| format!("invalid id '{}'", id))?)
+            }
+            Some(("due", due)) => {
+                task.due_date =
+                    Some(date(due).ok_or_else(|| format!("invalid due date '{}'", due))?)
+            }
+            Some(("status", status)) => task.status = status.parse()?,
+            Some(("pri", letter)) if letter.len() == 1 => {
+                task.priority = priority_from_todo(letter.chars().next().unwrap_or('C'))
+            }
+            _ => title.push(word),
+        }
+    }
+    task.title = title.join(" ");
+    Ok(task)
+}
+
+/// One row of an import, numbered from 1, and what could be read from it
+pub type ParsedRow = (usize, Result<ImportedTask, String>);
+
+/// Splits `body` into rows
+///
+/// Fails only if the document as a whole cannot be read; a bad row is
+/// returned as an error in its place.
+pub fn parse(body: &str, format: Format) -> Result<Vec<ParsedRow>, String> {
+    let lines = || {
+        body.lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+    };
+    match format {
+        Format::Json => {
+            let items: Vec<serde_json::Value> =
+                serde_json::from_str(body).map_err(|e| format!("not a JSON array: {}", e))?;
+            Ok(items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    (
+                        index + 1,
+                        serde_json::from_value(item).map_err(|e| e.to_string()),
+                    )
+                })
+                .collect())
+        }
+        Format::Ndjson => Ok(lines()
+            .map(|(row, line)| (row, serde_json::from_str(line).map_err(|e| e.to_string())))
+            .collect()),
+        Format::TodoTxt => Ok(lines()
+            .map(|(row, line)| (row, parse_todo_line(line)))
+            .collect()),
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(body.as_bytes());
+            Ok(reader
+                .deserialize()
+                .enumerate()
+                // Row 1 is the header.
+                .map(|(index, row)| (index + 2, row.map_err(|e| e.to_string())))
+                .collect())
+        }
+    }
+}
+
+/// Why a row cannot be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// The id is or was taken, or is used by an earlier row
+    DuplicateId,
+    /// The row could not be read or failed validation
+    InvalidRow,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportProblem {
+    pub row: usize,
+    pub kind: ProblemKind,
+    pub message: String,
+}
+
+impl From<&ImportProblem> for FieldError {
+    fn from(problem: &ImportProblem) -> Self {
+        FieldError::new(&format!("row {}", problem.row), problem.message.clone())
+    }
+}
+
+/// Response of `POST /tasks/import`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// The tasks imported, or that would be on a dry run
+    pub tasks: Vec<Task>,
+    pub problems: Vec<ImportProblem>,
+}
+
+/// Turns parsed rows into tasks owned by `owner`, next to the `existing` ones
+///
+/// Rows naming an id become `Insert`s, refused if the id is below `next_id`.
+/// The rest become `Create`s for the store to number, under provisional ids
+/// from `next_id` on that skip past any named in the import. Returns the
+/// writes to commit and every row that cannot be imported.
+pub fn plan(
+    rows: Vec<ParsedRow>,
+    existing: &[Task],
+    next_id: u32,
+    owner: u32,
+    now: DateTime<Utc>,
+) -> (Vec<TaskWrite>, Vec<ImportProblem>) {
+    let taken: HashSet<u32> = existing.iter().map(|task| task.id).collect();
+    let mut unnumbered = rows
+        .iter()
+        .filter_map(|(_, row)| row.as_ref().ok()?.id)
+        // The highest id fails validation below and leaves no room after it.
+        .filter_map(|id| id.checked_add(1))
+        .fold(next_id, u32::max);
+    let mut used = HashSet::new();
+    let mut writes = Vec::new();
+    let mut problems = Vec::new();
+
+    for (row, parsed) in rows {
+        let problem = |kind, message: String| ImportProblem { row, kind, message };
+        let imported = match parsed {
+            Ok(imported) => imported,
+            Err(e) => {
+                problems.push(problem(ProblemKind::InvalidRow, e));
+                continue;
+            }
+        };
+
+        let id = match imported.id {
+            Some(id) if taken.contains(&id) => {
+                problems.push(problem(
+                    ProblemKind::DuplicateId,
+                    format!("task {} already exists", id),
+                ));
+                continue;
+            }
+            Some(id) if id < next_id => {
+                problems.push(problem(
+                    ProblemKind::DuplicateId,
+                    format!("id {} was handed out before; leave it out", id),
+                ));
+                continue;
+            }
+            Some(id) if !used.insert(id) => {
+                problems.push(problem(
+                    ProblemKind::DuplicateId,
+                    format!("id {} is used by an earlier row", id),
+                ));
+                continue;
+            }
+            Some(id) => id,
+            None => {
+                unnumbered = unnumbered.saturating_add(1);
+                unnumbered - 1
+            }
+        };
+
+        let numbered = imported.id.is_some();
+        let mut new_task = NewTask {
+            id: Some(id),
+            owner_id: Some(owner),
+            title: imported.title,
+            description: imported.description,
+            status: imported.status,
+            priority: imported.priority,
+            due_date: imported.due_date,
+            shared: imported.shared,
+            recurrence: None,
+            reminders: Vec::new(),
+            project_id: None,
+            tags: Vec::new(),
+        };
+        if let Err(errors) = new_task.validate() {
+            let message = errors
+                .iter()
+                .map(|e| format!("{} {}", e.field, e.message))
+                .collect::<Vec<_>>()
+                .join("; ");
+            problems.push(problem(ProblemKind::InvalidRow, message));
+            continue;
+        }
+
+        let mut task = new_task.into_task(id, imported.created_at.unwrap_or(now));
+        task.updated_at = now;
+        if task.is_done() {
+            task.completed_at = imported.completed_at.or(Some(now));
+        }
+        writes.push(match numbered {
+            true => TaskWrite::Insert(task),
+            false => TaskWrite::Create(task),
+        });
+    }
+    (writes, problems)
+}
+
+/// The tasks `writes` insert, each `Create` taking the next id of `created`
+/// if there is one
+fn inserted(writes: Vec<TaskWrite>, created: Vec<u32>) -> Vec<Task> {
+    let mut created = created.into_iter();
+    writes
+        .into_iter()
+        .filter_map(|write| match write {
+            TaskWrite::Insert(task) => Some(task),
+            TaskWrite::Create(mut task) => {
+                task.id = created.next().unwrap_or(task.id);
+                Some(task)
+            }
+            TaskWrite::Update(_) | TaskWrite::Delete(_) => None,
+        })
+        .collect()
+}
+
+/// An exported file, sent as a download
+pub struct Export {
+    format: Format,
+    body: String,
+}
+
+impl<'r> Responder<'r, 'static> for Export {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        Response::build_from(self.body.respond_to(req)?)
+            .header(self.format.content_type())
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"tasks.{}\"", self.format),
+            )
+            .ok()
+    }
+}
+
+fn parse_format(format: &str) -> Result<Format, ApiError> {
+    format
+        .parse()
+        .map_err(|e: String| ApiError::Validation(vec![FieldError::new("format", e)]))
+}
+
+/// Every live task the signed-in user can see, in `format` (default `json`)
+#[utoipa::path(
+    tag = "tasks",
+    params(("format" = Option<String>, Query, description = "json (default), csv, ndjson or todo.txt")),
+    responses(
+        (status = 200, description = "Every task you can see, in the requested format", content(
+            (String = "application/json"),
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+            (String = "text/plain"),
+        )),
+        (status = 422, description = "Unknown format", body = ErrorBody)
+    )
+)]
+#[get("/tasks/export?<format>")]
+pub fn export_tasks(
+    store: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+    format: Option<&str>,
+) -> Result<Export, ApiError> {
+    let format = format.map_or(Ok(Format::Json), parse_format)?;
+    let tasks: Vec<Task> = store
+        .list()?
+        .into_iter()
+        .filter(|task| task.visible_to(user.id) && !task.is_trashed())
+        .collect();
+    let body = export(&tasks, format)?;
+    Ok(Export { format, body })
+}
+
+/// Imports the tasks in the request body
+///
+/// The format comes from `format`, or else from the `Content-Type`. With
+/// `dry_run=true` nothing is saved and the report says what would be.
+#[allow(clippy::too_many_arguments)]
+#[utoipa::path(
+    tag = "tasks",
+    params(
+        ("format" = Option<String>, Query, description = "json, csv, ndjson or todo.txt; taken from Content-Type when absent"),
+        ("dry_run" = Option<bool>, Query, description = "Report without saving anything"),
+    ),
+    request_body(
+        description = "Tasks in the given format",
+        content(
+            (String = "application/json"),
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+            (String = "text/plain"),
+        )
+    ),
+    responses(
+        (status = 200, description = "What was imported, or would be with dry_run", body = ImportReport),
+        (status = 409, description = "Some rows are invalid or clash with existing tasks; nothing was imported", body = ErrorBody),
+        (status = 413, description = "The body is over the import limit", body = ErrorBody),
+        (status = 422, description = "Unknown format", body = ErrorBody)
+    )
+)]
+#[post("/tasks/import?<format>&<dry_run>", data = "<data>")]
+pub async fn import_tasks(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    limits: &Limits,
+    content_type: Option<&ContentType>,
+    format: Option<&str>,
+    dry_run: Option<bool>,
+    data: Data<'_>,
+) -> Result<Json<ImportReport>, ApiError> {
+    let format = match (format, content_type.and_then(Format::from_content_type)) {
+        (Some(format), _) => parse_format(format)?,
+        (None, Some(format)) => format,
+        (None, None) => {
+            return Err(ApiError::Validation(vec![FieldError::new(
+                "format",
+                "give format=json|csv|ndjson|todo.txt or a matching Content-Type",
+            )]))
+        }
+    };
+    let dry_run = dry_run.unwrap_or(false);
+
+    let limit = limits
+        .get("import")
+        .unwrap_or(DEFAULT_IMPORT_LIMIT_MIB.mebibytes());
+    let body = data
+        .open(limit)
+        .into_string()
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("could not read the body: {}", e)))?;
+    if !body.is_complete() {
+        return Err(ApiError::TooLarge(format!(
+            "imports are limited to {}",
+            limit
+        )));
+    }
+
+    let rows = parse(&body, format).map_err(ApiError::Unprocessable)?;
+    let (writes, problems) = plan(rows, &store.list()?, store.next_id()?, user.id, Utc::now());
+    let tasks = if dry_run {
+        inserted(writes, Vec::new())
+    } else {
+        if !problems.is_empty() {
+            return Err(ApiError::ImportRejected(
+                problems.iter().map(FieldError::from).collect(),
+            ));
+        }
+        let created = store.commit(writes.clone())?;
+        let tasks = inserted(writes, created);
+        for task in &tasks {
+            changes.created(&user, task);
+        }
+        tasks
+    };
+
+    Ok(Json(ImportReport {
+        dry_run,
+        tasks,
+        problems,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn todo_txt_round_trips() {
+        let now = Utc::now();
+        let mut task = Task::new(7, "file  taxes".into(), String::new(), now);
+        task.priority = Priority::High;
+        task.due_date = NaiveDate::from_ymd_opt(2030, 4, 15);
+        task.set_status(TaskStatus::Done, now).unwrap();
+
+        let line = todo_line(&task);
+        let today = now.date_naive();
+        assert_eq!(
+            line,
+            format!("x {} {} file taxes due:2030-04-15 pri:B id:7", today, today)
+        );
+
+        let parsed = parse_todo_line(&line).unwrap();
+        assert_eq!(parsed.id, Some(7));
+        assert_eq!(parsed.title, "file taxes");
+        assert_eq!(parsed.status, TaskStatus::Done);
+        assert_eq!(parsed.priority, Priority::High);
+        assert_eq!(parsed.due_date, task.due_date);
+        assert_eq!(parsed.created_at, Some(midnight(today)));
+
+        let open = parse_todo_line("(A) call mom +family @phone status:blocked").unwrap();
+        assert_eq!(open.title, "call mom +family @phone");
+        assert_eq!(open.priority, Priority::Urgent);
+        assert_eq!(open.status, TaskStatus::Blocked);
+        assert_eq!(open.created_at, None);
+    }
+
+    #[test]
+    fn plan_reports_duplicate_ids_and_bad_rows() {
+        let now = Utc::now();
+        let existing = [Task::new(2, "kept".into(), String::new(), now)];
+        let body = r#"{"id":2,"title":"clash"}
+{"title":"first"}
+{"id":5,"title":"explicit"}
+{"id":5,"title":"again"}
+{"title":"  "}
+not json
+{"id":1,"title":"retired"}
+{"id":4294967295,"title":"last"}"#;
+
+        let rows = parse(body, Format::Ndjson).unwrap();
+        let (writes, problems) = plan(rows, &existing, 3, 1, now);
+
+        assert!(matches!(writes[0], TaskWrite::Create(_)));
+        assert!(matches!(writes[1], TaskWrite::Insert(_)));
+        let tasks = inserted(writes, Vec::new());
+        let ids: Vec<u32> = tasks.iter().map(|task| task.id).collect();
+        assert_eq!(ids, [6, 5]);
+        assert!(tasks.iter().all(|task| task.owner_id == Some(1)));
+
+        let found: Vec<(usize, ProblemKind)> = problems.iter().map(|p| (p.row, p.kind)).collect();
+        assert_eq!(
+            found,
+            [
+                (1, ProblemKind::DuplicateId),
+                (4, ProblemKind::DuplicateId),
+                (5, ProblemKind::InvalidRow),
+                (6, ProblemKind::InvalidRow),
+                (7, ProblemKind::DuplicateId),
+                (8, ProblemKind::InvalidRow),
+            ]
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Soft-deleted tasks: listing, restoring and purging the trash
+//!
+//! `DELETE /tasks/<id>` only stamps `deleted_at`; the task drops out of every
+//! other route but can be brought back with `POST /tasks/<id>/restore`. Tasks
+//! that have sat in the trash longer than `trash.retention_days` are removed
+//! for good when the server starts and then every
+//! `trash.purge_interval_minutes`.
+
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorBody};
+use crate::etag::{IfMatch, Tagged};
+use crate::events::EventLog;
+use crate::history::{self, Action, Changes, Revision};
+use crate::links;
+use crate::metrics::Metrics;
+use crate::query::{TaskPage, TaskQuery};
+use crate::store::{CommentStore, HistoryStore, StoreError, TaskStore};
+use crate::task::Task;
+use crate::webhooks::Dispatcher;
+use chrono::{DateTime, Duration, Utc};
+use rocket::fairing::AdHoc;
+use rocket::serde::json::Json;
+use rocket::tokio::{select, task, time};
+use rocket::State;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// The `trash` section of `Rocket.toml`
+///
+/// ```toml
+/// [default.trash]
+/// retention_days = 30
+/// purge_interval_minutes = 60
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrashConfig {
+    /// Days a task stays in the trash before it is purged; 0 keeps it forever
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u32,
+    #[serde(default = "default_purge_interval_minutes")]
+    pub purge_interval_minutes: u64,
+}
+
+fn default_retention_days() -> u32 {
+    30
+}
+
+fn default_purge_interval_minutes() -> u64 {
+    60
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            retention_days: default_retention_days(),
+            purge_interval_minutes: default_purge_interval_minutes(),
+        }
+    }
+}
+
+/// Permanently removes tasks trashed more than `retention` before `now`
+///
+/// Links to a purged task are removed from the tasks that remain, its
+/// comments are deleted, and both the purge and those link changes are
+/// recorded like any other change. Returns the ids of the purged tasks.
+pub fn purge(
+    store: &dyn TaskStore,
+    history: &dyn HistoryStore,
+    comments: &dyn CommentStore,
+    events: &EventLog,
+    webhooks: &Dispatcher,
+    now: DateTime<Utc>,
+    retention: Duration,
+) -> Result<Vec<u32>, StoreError> {
+    let cutoff = now - retention;
+    let expired: Vec<Task> = store
+        .list()?
+        .into_iter()
+        .filter(|task| task.deleted_at.is_some_and(|at| at <= cutoff))
+        .collect();
+
+    let mut purged = Vec::new();
+    for task in expired {
+        if !store.delete(task.id)? {
+            continue;
+        }
+        comments.delete_comments(task.id)?;
+        let mut revisions = vec![Revision::new(None, Action::Purged, Some(&task), None, now)];
+        for (before, after) in links::detach(store, task.id)? {
+            revisions.push(Revision::new(
+                None,
+                Action::Updated,
+                Some(&before),
+                Some(&after),
+                now,
+            ));
+        }
+        for revision in revisions {
+            history::record(events, history, webhooks, revision);
+        }
+        purged.push(task.id);
+    }
+    Ok(purged)
+}
+
+/// Runs `purge` at liftoff and then on the configured interval until shutdown
+pub fn purge_on_timer() -> AdHoc {
+    AdHoc::on_liftoff("Trash Purge", |rocket| {
+        Box::pin(async move {
+            let (
+                Some(config),
+                Some(store),
+                Some(history),
+                Some(comments),
+                Some(events),
+                Some(webhooks),
+            ) = (
+                rocket.state::<TrashConfig>(),
+                rocket.state::<Arc<dyn TaskStore>>(),
+                rocket.state::<Arc<dyn HistoryStore>>(),
+                rocket.state::<Arc<dyn CommentStore>>(),
+                rocket.state::<Arc<EventLog>>(),
+                rocket.state::<Dispatcher>(),
+            )
+            else {
+                return;
+            };
+            if config.retention_days == 0 {
+                return;
+            }
+
+            let retention = Duration::days(config.retention_days.into());
+            let interval = time::Duration::from_secs(config.purge_interval_minutes.max(1) * 60);
+            let (store, history, comments, events) = (
+                Arc::clone(store),
+                Arc::clone(history),
+                Arc::clone(comments),
+                Arc::clone(events),
+            );
+            let webhooks = webhooks.clone();
+            let metrics = rocket.state::<Arc<Metrics>>().cloned();
+            let mut shutdown = rocket.shutdown();
+
+            rocket::tokio::spawn(async move {
+                loop {
+                    let (store, history, comments, events, webhooks) = (
+                        Arc::clone(&store),
+                        Arc::clone(&history),
+                        Arc::clone(&comments),
+                        Arc::clone(&events),
+                        webhooks.clone(),
+                    );
+                    let result = task::spawn_blocking(move || {
+                        purge(
+                            store.as_ref(),
+                            history.as_ref(),
+                            comments.as_ref(),
+                            &events,
+                            &webhooks,
+                            Utc::now(),
+                            retention,
+                        )
+                    })
+                    .await;
+                    match result {
+                        Ok(Ok(ids)) if !ids.is_empty() => {
+                            info!("purged {} tasks from the trash", ids.len())
+                        }
+                        Ok(Ok(_)) => {}
+                        Ok(Err(e)) => {
+                            error!("failed to purge the trash: {}", e);
+                            if let Some(metrics) = &metrics {
+                                metrics.record_storage_error(&e);
+                            }
+                        }
+                        Err(e) => error!("trash purge panicked: {}", e),
+                    }
+
+                    select! {
+                        _ = time::sleep(interval) => {}
+                        _ = &mut shutdown => break,
+                    }
+                }
+            });
+        })
+    })
+}
+
+/// Lists the signed-in user's trashed tasks; takes the same parameters as
+/// `GET /tasks`
+#[utoipa::path(
+    tag = "tasks",
+    params(TaskQuery),
+    responses(
+        (status = 200, description = "One page of trashed tasks", body = TaskPage),
+        (status = 422, description = "A query parameter is invalid", body = ErrorBody)
+    )
+)]
+#[get("/tasks/trash?<query..>")]
+pub fn trashed_tasks(
+    store: &State<Arc<dyn TaskStore>>,
+    user: AuthUser,
+    query: TaskQuery,
+) -> Result<Json<TaskPage>, ApiError> {
+    let today = Utc::now().date_naive();
+    let mut query = query.validate(today).map_err(ApiError::Validation)?;
+    query.viewer = Some(user.id);
+    query.trashed = true;
+    Ok(Json(store.query(&query)?))
+}
+
+/// Takes task `id` back out of the trash
+#[utoipa::path(
+    tag = "tasks",
+    params(("If-Match" = Option<String>, Header, description = "Fail with 412 unless the task is still at this ETag")),
+    responses(
+        (status = 200, description = "The restored task", body = Task, headers(("ETag" = String, description = "The version of the task, for If-Match"))),
+        (status = 403, description = "The task belongs to another user", body = ErrorBody),
+        (status = 404, description = "No such task, or not visible to you", body = ErrorBody),
+        (status = 409, description = "The task is not in the trash", body = ErrorBody),
+        (status = 412, description = "The task has changed since the If-Match version", body = ErrorBody)
+    )
+)]
+#[post("/tasks/<id>/restore")]
+pub fn restore_task(
+    store: &State<Arc<dyn TaskStore>>,
+    changes: Changes<'_>,
+    user: AuthUser,
+    if_match: IfMatch,
+    id: u32,
+) -> Result<Tagged, ApiError> {
+    let store = store.as_ref();
+    let before = store
+        .get(id)?
+        .filter(|task| task.visible_to(user.id))
+        .ok_or_else(|| ApiError::task_not_found(id))?;
+    if !before.is_trashed() {
+        return Err(ApiError::Conflict(format!(
+            "task {} is not in the trash",
+            id
+        )));
+    }
+    if !before.editable_by(user.id) {
+        return Err(ApiError::Forbidden(format!(
+            "task {} belongs to another user",
+            id
+        )));
+    }
+    if_match.check(&before)?;
+
+    let mut task = before.clone();
+    task.deleted_at = None;
+    task.touch(Utc::now());
+    links::repair_links(store, &mut task)?;
+    if !store.update(task.clone())? {
+        return Err(ApiError::task_not_found(id));
+    }
+
+    changes.restored(&user, &before, &task);
+    Ok(Tagged(task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comments::Comment;
+    use crate::events::{Backlog, EventKind};
+    use crate::store::{CsvStore, JsonlCommentStore, JsonlHistoryStore, JsonlWebhookStore};
+    use crate::task::NewTask;
+    use crate::webhooks::WebhookConfig;
+
+    #[test]
+    fn purges_only_expired_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CsvStore::new(dir.path().join("tasks.csv"));
+        let history = JsonlHistoryStore::new(dir.path().join("history.jsonl"));
+        let comments = JsonlCommentStore::new(dir.path().join("comments.jsonl"));
+        let events = EventLog::default();
+        let webhooks = Dispatcher::new(
+            Arc::new(JsonlWebhookStore::new(
+                dir.path().join("webhooks.jsonl"),
+                dir.path().join("deliveries.jsonl"),
+            )),
+            WebhookConfig::default(),
+        )
+        .unwrap();
+        let now = Utc::now();
+
+        let new_task = |title: &str| NewTask {
+            id: None,
+            owner_id: None,
+            title: title.into(),
+            description: String::new(),
+            status: Default::default(),
+            priority: Default::default(),
+            due_date: None,
+            shared: false,
+            recurrence: None,
+            reminders: Vec::new(),
+            project_id: None,
+            tags: Vec::new(),
+        };
+        for title in ["old", "recent", "live", "blocked", "newest"] {
+            store.insert(new_task(title), now).unwrap();
+        }
+        let trashed = |id: u32, days: i64| {
+            let mut task = store.get(id).unwrap().unwrap();
+            task.deleted_at = Some(now - Duration::days(days));
+            task.touch(now);
+            store.update(task).unwrap();
+        };
+        trashed(1, 40);
+        trashed(2, 3);
+        trashed(5, 40);
+        let mut blocked = store.get(4).unwrap().unwrap();
+        blocked.blocked_by = vec![1];
+        blocked.touch(now);
+        store.update(blocked).unwrap();
+        for task_id in [1, 2] {
+            comments
+                .insert_comment(Comment {
+                    id: 0,
+                    task_id,
+                    author_id: 1,
+                    body: "on it".into(),
+                    created_at: now,
+                    edited_at: None,
+                })
+                .unwrap();
+        }
+
+        let purged = purge(
+            &store,
+            &history,
+            &comments,
+            &events,
+            &webhooks,
+            now,
+            Duration::days(30),
+        )
+        .unwrap();
+        assert_eq!(purged, [1, 5]);
+        assert_eq!(store.get(1).unwrap(), None);
+        assert!(store.get(2).unwrap().unwrap().is_trashed());
+        assert!(store.get(4).unwrap().unwrap().blocked_by.is_empty());
+        assert!(comments.comments(1).unwrap().is_empty());
+        assert_eq!(comments.comments(2).unwrap().len(), 1);
+
+        assert_eq!(history.history(1).unwrap()[0].action, Action::Purged);
+        assert_eq!(history.history(4).unwrap()[0].action, Action::Updated);
+        let Backlog::Events(published) = events.since(0) else {
+            panic!("the event log dropped the purge");
+        };
+        let published: Vec<_> = published
+            .iter()
+            .map(|event| (event.kind, event.task.id))
+            .collect();
+        assert_eq!(
+            published,
+            [
+                (EventKind::Deleted, 1),
+                (EventKind::Updated, 4),
+                (EventKind::Deleted, 5)
+            ]
+        );
+
+        // The purged ids stay retired.
+        assert_eq!(store.insert(new_task("later"), now).unwrap().id, 6);
+    }
+}
@@ -0,0 +1,786 @@
+//! Outgoing webhooks: `/webhooks` and signed deliveries of task events
+//!
+//! A user registers a URL with `POST /webhooks`, optionally narrowed to some
+//! of the events `created`, `updated`, `completed` and `deleted`. Every change
+//! `Changes` records is handed to the `Dispatcher`, which POSTs a JSON
+//! `WebhookPayload` to each matching hook whose owner can see the task. The
+//! body is signed with HMAC-SHA256 under the hook's secret and sent as
+//! `X-Webhook-Signature: sha256=<hex>`. A delivery that fails or is answered
+//! with anything but 2xx is retried with exponential backoff, and every
+//! attempt is kept in the log at `GET /webhooks/<id>/deliveries`.
+//!
+//! Hooks must use https and point at a public address, checked when a hook
+//! is registered and again, after resolving its host, on every delivery;
+//! `webhooks.allow_http` and `webhooks.allow_private` relax this for local
+//! development.
+
+use crate::auth::AuthUser;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::history::{Action, Revision};
+use crate::store::WebhookStore;
+use crate::task::Task;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::tokio::{task, time};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// The task events a webhook can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// Created, or taken back out of the trash
+    Created,
+    Updated,
+    /// Updated into `done` from another status
+    Completed,
+    /// Moved to the trash
+    Deleted,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::Created,
+        WebhookEvent::Updated,
+        WebhookEvent::Completed,
+        WebhookEvent::Deleted,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::Created => "created",
+            WebhookEvent::Updated => "updated",
+            WebhookEvent::Completed => "completed",
+            WebhookEvent::Deleted => "deleted",
+        }
+    }
+
+    /// The event a revision is delivered as
+    pub fn of(revision: &Revision) -> Self {
+        let completes = match (&revision.before, &revision.after) {
+            (Some(before), Some(after)) => after.is_done() && !before.is_done(),
+            _ => false,
+        };
+        match revision.action {
+            Action::Created | Action::Restored => WebhookEvent::Created,
+            Action::Updated | Action::Reverted if completes => WebhookEvent::Completed,
+            Action::Updated | Action::Reverted => WebhookEvent::Updated,
+            Action::Deleted | Action::Purged => WebhookEvent::Deleted,
+        }
+    }
+}
+
+/// A registered webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub id: u32,
+    pub owner_id: u32,
+    pub url: String,
+    /// Key the payloads are signed with; only returned when the hook is created
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.contains(&event)
+    }
+}
+
+/// A webhook as listed, without its secret
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookInfo {
+    pub id: u32,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Webhook> for WebhookInfo {
+    fn from(webhook: Webhook) -> Self {
+        WebhookInfo {
+            id: webhook.id,
+            url: webhook.url,
+            events: webhook.events,
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+/// Body of `POST /webhooks`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewWebhook {
+    pub url: String,
+    /// Events to deliver; every event when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// Key to sign payloads with; a random one is generated when unset
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// Shortest secret accepted from a client
+pub const MIN_SECRET_LEN: usize = 16;
+
+impl NewWebhook {
+    /// Checks the URL and secret, and fills in the default events
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        match reqwest::Url::parse(self.url.trim()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {
+                self.url = url.to_string();
+            }
+            Ok(_) => errors.push(FieldError::new("url", "must be an http or https URL")),
+            Err(e) => errors.push(FieldError::new("url", format!("is not a URL: {}", e))),
+        }
+        if self
+            .secret
+            .as_ref()
+            .is_some_and(|secret| secret.len() < MIN_SECRET_LEN)
+        {
+            errors.push(FieldError::new(
+                "secret",
+                format!("must be at least {} characters", MIN_SECRET_LEN),
+            ));
+        }
+
+        if self.events.is_empty() {
+            self.events = WebhookEvent::ALL.to_vec();
+        }
+        let mut events = Vec::new();
+        for event in WebhookEvent::ALL {
+            if self.events.contains(&event) {
+                events.push(event);
+            }
+        }
+        self.events = events;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The JSON body POSTed to a webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookPayload {
+    /// Id of the event in the live feed; the same for every attempt
+    pub event_id: u64,
+    pub event: WebhookEvent,
+    pub at: DateTime<Utc>,
+    /// The task after the change, as it sits in the trash for `deleted`
+    pub task: Task,
+}
+
+/// One attempt to deliver a payload, as kept in the delivery log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Delivery {
+    pub webhook_id: u32,
+    pub event_id: u64,
+    pub event: WebhookEvent,
+    pub task_id: u32,
+    /// Numbered from 1 for each event
+    pub attempt: u32,
+    pub at: DateTime<Utc>,
+    /// Status the hook answered with, if it answered
+    pub status: Option<u16>,
+    pub delivered: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The `webhooks` section of `Rocket.toml`
+///
+/// ```toml
+/// [default.webhooks]
+/// max_attempts = 5
+/// base_delay_ms = 1000
+/// timeout_secs = 10
+/// allow_http = false
+/// allow_private = false
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    /// Attempts per payload, including the first
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubles with each one after
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Accept plain `http://` hooks as well as https ones
+    #[serde(default)]
+    pub allow_http: bool,
+    /// Accept hooks on loopback, link-local and private addresses
+    #[serde(default)]
+    pub allow_private: bool,
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_base_delay_ms() -> u64 {
+    1000
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            max_attempts: default_max_attempts(),
+            base_delay_ms: default_base_delay_ms(),
+            timeout_secs: default_timeout_secs(),
+            allow_http: false,
+            allow_private: false,
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// Wait after failed attempt `attempt` (from 1) before the next one
+    pub fn backoff(&self, attempt: u32) -> time::Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        time::Duration::from_millis(self.base_delay_ms.saturating_mul(factor))
+    }
+
+    /// Checks that `url` may be delivered to: https unless `allow_http`, and
+    /// resolving only to public addresses unless `allow_private`
+    pub async fn check_target(&self, url: &str) -> Result<(), String> {
+        let url = reqwest::Url::parse(url).map_err(|e| format!("is not a URL: {}", e))?;
+        match url.scheme() {
+            "https" => {}
+            "http" if self.allow_http => {}
+            _ => return Err("must be an https URL".into()),
+        }
+        if self.allow_private {
+            return Ok(());
+        }
+
+        let host = url.host_str().ok_or("has no host")?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = url.port_or_known_default().unwrap_or(0);
+        let addrs = rocket::tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("cannot be resolved: {}", e))?;
+        for addr in addrs {
+            if !is_public(addr.ip()) {
+                return Err(format!(
+                    "points at {}, which is not a public address",
+                    addr.ip()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `ip` is on the public internet rather than loopback, link-local,
+/// private or another special-purpose range
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(a == 0
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space, used for carrier-grade NAT
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Resolves hook hosts for the `Dispatcher`, failing for names that point
+/// at a non-public address
+///
+/// Checking at connect time catches names that were public when the hook
+/// was registered but have been repointed since.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = rocket::tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!("{} points at non-public address {}", host, addr.ip()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The `X-Webhook-Signature` value for `body` under `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    let mut signature = String::from("sha256=");
+    for byte in mac.finalize().into_bytes() {
+        let _ = write!(signature, "{:02x}", byte);
+    }
+    signature
+}
+
+fn random_secret() -> String {
+    let mut key = [0u8; 24];
+    OsRng.fill_bytes(&mut key);
+    key.iter().fold(String::new(), |mut secret, byte| {
+        let _ = write!(secret, "{:02x}", byte);
+        secret
+    })
+}
+
+/// Sends task events to the registered webhooks; managed as Rocket state
+///
+/// Clones share the webhook store and connection pool.
+#[derive(Clone)]
+pub struct Dispatcher {
+    store: Arc<dyn WebhookStore>,
+    client: reqwest::Client,
+    config: WebhookConfig,
+}
+
+impl Dispatcher {
+    pub fn new(
+        store: Arc<dyn WebhookStore>,
+        config: WebhookConfig,
+    ) -> Result<Self, reqwest::Error> {
+        // A redirect could lead anywhere, so it counts as a failed delivery.
+        let mut client = reqwest::Client::builder()
+            .timeout(time::Duration::from_secs(config.timeout_secs.max(1)))
+            .redirect(reqwest::redirect::Policy::none());
+        if !config.allow_private {
+            client = client.dns_resolver(Arc::new(PublicResolver));
+        }
+        Ok(Dispatcher {
+            store,
+            client: client.build()?,
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &WebhookConfig {
+        &self.config
+    }
+
+    /// Starts delivering `revision`, published to the feed as `event_id`, to
+    /// every hook that wants it
+    ///
+    /// Deliveries run in the background; this only reads the registered
+    /// hooks, and logs rather than returns a failure to do so.
+    pub fn dispatch(&self, event_id: u64, revision: &Revision) {
+        let Some(task) = revision.after.as_ref().or(revision.before.as_ref()) else {
+            return;
+        };
+        let event = WebhookEvent::of(revision);
+        let webhooks = match self.store.webhooks() {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                error!("failed to load webhooks: {}", e);
+                return;
+            }
+        };
+
+        let payload = WebhookPayload {
+            event_id,
+            event,
+            at: revision.at,
+            task: task.clone(),
+        };
+        let body = match serde_json::to_string(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("failed to encode webhook payload: {}", e);
+                return;
+            }
+        };
+
+        for webhook in webhooks {
+            if webhook.wants(event) && task.visible_to(webhook.owner_id) {
+                rocket::tokio::spawn(deliver(
+                    Arc::clone(&self.store),
+                    self.client.clone(),
+                    self.config.clone(),
+                    webhook,
+                    payload.clone(),
+                    body.clone(),
+                ));
+            }
+        }
+    }
+}
+
+/// POSTs `body` to `webhook` until it is accepted or the attempts run out,
+/// logging each attempt
+async fn deliver(
+    store: Arc<dyn WebhookStore>,
+    client: reqwest::Client,
+    config: WebhookConfig,
+    webhook: Webhook,
+    payload: WebhookPayload,
+    body: String,
+) {
+    let signature = sign(&webhook.secret, body.as_bytes());
+    let attempts = config.max_attempts.max(1);
+
+    // The hook may predate a stricter configuration.
+    if let Err(e) = config.check_target(&webhook.url).await {
+        warn!("not delivering to webhook {}: url {}", webhook.id, e);
+        log_delivery(
+            &store,
+            Delivery {
+                webhook_id: webhook.id,
+                event_id: payload.event_id,
+                event: payload.event,
+                task_id: payload.task.id,
+                attempt: 1,
+                at: Utc::now(),
+                status: None,
+                delivered: false,
+                error: Some(format!("url {}", e)),
+            },
+        )
+        .await;
+        return;
+    }
+
+    for attempt in 1..=attempts {
+        let result = client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", payload.event.as_str())
+            .header("X-Webhook-Delivery", payload.event_id.to_string())
+            .header("X-Webhook-Signature", &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let (status, error) = match result {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                Some(format!("answered {}", response.status())),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let delivery = Delivery {
+            webhook_id: webhook.id,
+            event_id: payload.event_id,
+            event: payload.event,
+            task_id: payload.task.id,
+            attempt,
+            at: Utc::now(),
+            status,
+            delivered: error.is_none(),
+            error,
+        };
+        let delivered = delivery.delivered;
+        log_delivery(&store, delivery).await;
+
+        if delivered {
+            return;
+        }
+        if attempt < attempts {
+            time::sleep(config.backoff(attempt)).await;
+        }
+    }
+    warn!(
+        "gave up delivering event {} to webhook {} after {} attempts",
+        payload.event_id, webhook.id, attempts
+    );
+}
+
+/// Adds `delivery` to the delivery log, logging rather than returning a
+/// failure to
+async fn log_delivery(store: &Arc<dyn WebhookStore>, delivery: Delivery) {
+    let store = Arc::clone(store);
+    match task::spawn_blocking(move || store.record_delivery(delivery)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("failed to record webhook delivery: {}", e),
+        Err(e) => error!("recording a webhook delivery panicked: {}", e),
+    }
+}
+
+/// Loads webhook `id` if it belongs to `user`
+///
+/// Other users' hooks answer 404, as if they did not exist.
+fn owned_webhook(store: &dyn WebhookStore, id: u32, user: &AuthUser) -> Result<Webhook, ApiError> {
+    store
+        .webhooks()?
+        .into_iter()
+        .find(|webhook| webhook.id == id && webhook.owner_id == user.id)
+        .ok_or_else(|| ApiError::NotFound(format!("webhook {} does not exist", id)))
+}
+
+/// Registers a webhook for the signed-in user
+///
+/// The response is the only one that includes the secret. The URL must be
+/// https and resolve to public addresses unless the configuration allows
+/// otherwise.
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 201, description = "The new webhook, with its secret", body = Webhook),
+        (status = 422, description = "The body failed validation", body = ErrorBody)
+    )
+)]
+#[post("/webhooks", data = "<webhook>")]
+pub async fn create_webhook(
+    store: &State<Arc<dyn WebhookStore>>,
+    dispatcher: &State<Dispatcher>,
+    user: AuthUser,
+    webhook: Json<NewWebhook>,
+) -> Result<Created<Json<Webhook>>, ApiError> {
+    let mut webhook = webhook.into_inner();
+    webhook.validate().map_err(ApiError::Validation)?;
+    if let Err(e) = dispatcher.config().check_target(&webhook.url).await {
+        return Err(ApiError::Validation(vec![FieldError::new("url", e)]));
+    }
+
+    let webhook = store.insert_webhook(Webhook {
+        id: 0,
+        owner_id: user.id,
+        url: webhook.url,
+        secret: webhook.secret.unwrap_or_else(random_secret),
+        events: webhook.events,
+        created_at: Utc::now(),
+    })?;
+    Ok(Created::new(uri!(get_webhook(webhook.id)).to_string()).body(Json(webhook)))
+}
+
+/// Lists the signed-in user's webhooks
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Your webhooks", body = [WebhookInfo])
+    )
+)]
+#[get("/webhooks")]
+pub fn list_webhooks(
+    store: &State<Arc<dyn WebhookStore>>,
+    user: AuthUser,
+) -> Result<Json<Vec<WebhookInfo>>, ApiError> {
+    Ok(Json(
+        store
+            .webhooks()?
+            .into_iter()
+            .filter(|webhook| webhook.owner_id == user.id)
+            .map(WebhookInfo::from)
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "The webhook", body = WebhookInfo),
+        (status = 404, description = "No such webhook of yours", body = ErrorBody)
+    )
+)]
+#[get("/webhooks/<id>")]
+pub fn get_webhook(
+    store: &State<Arc<dyn WebhookStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Json<WebhookInfo>, ApiError> {
+    owned_webhook(store.as_ref(), id, &user).map(|webhook| Json(webhook.into()))
+}
+
+/// Unregisters a webhook; deliveries already under way still finish
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 204, description = "The webhook is unregistered"),
+        (status = 404, description = "No such webhook of yours", body = ErrorBody)
+    )
+)]
+#[delete("/webhooks/<id>")]
+pub fn delete_webhook(
+    store: &State<Arc<dyn WebhookStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Status, ApiError> {
+    owned_webhook(store.as_ref(), id, &user)?;
+    if store.delete_webhook(id)? {
+        Ok(Status::NoContent)
+    } else {
+        Err(ApiError::NotFound(format!("webhook {} does not exist", id)))
+    }
+}
+
+/// Every delivery attempt made to a webhook, oldest first
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Every delivery attempt, oldest first", body = [Delivery]),
+        (status = 404, description = "No such webhook of yours", body = ErrorBody)
+    )
+)]
+#[get("/webhooks/<id>/deliveries")]
+pub fn webhook_deliveries(
+    store: &State<Arc<dyn WebhookStore>>,
+    user: AuthUser,
+    id: u32,
+) -> Result<Json<Vec<Delivery>>, ApiError> {
+    owned_webhook(store.as_ref(), id, &user)?;
+    Ok(Json(store.deliveries(id)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completing_a_task_is_its_own_event() {
+        let now = Utc::now();
+        let task = Task::new(1, "ship".into(), String::new(), now);
+        let mut done = task.clone();
+        done.set_status(crate::task::TaskStatus::Done, now).unwrap();
+        let mut renamed = done.clone();
+        renamed.title = "shipped".into();
+
+        let event = |action, before: Option<&Task>, after: Option<&Task>| {
+            WebhookEvent::of(&Revision::new(None, action, before, after, now))
+        };
+        assert_eq!(
+            event(Action::Created, None, Some(&task)),
+            WebhookEvent::Created
+        );
+        assert_eq!(
+            event(Action::Updated, Some(&task), Some(&done)),
+            WebhookEvent::Completed
+        );
+        assert_eq!(
+            event(Action::Updated, Some(&done), Some(&renamed)),
+            WebhookEvent::Updated
+        );
+        assert_eq!(
+            event(Action::Deleted, Some(&done), Some(&done)),
+            WebhookEvent::Deleted
+        );
+    }
+
+    #[test]
+    fn signs_and_backs_off() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let config = WebhookConfig {
+            base_delay_ms: 100,
+            ..WebhookConfig::default()
+        };
+        let delays: Vec<u128> = (1..=4).map(|n| config.backoff(n).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800]);
+    }
+
+    #[rocket::async_test]
+    async fn only_public_https_targets_pass() {
+        let config = WebhookConfig::default();
+        for url in [
+            "http://93.184.215.14/hook",
+            "https://127.0.0.1/hook",
+            "https://localhost/hook",
+            "https://[::1]/hook",
+            "https://[::ffff:10.0.0.1]/hook",
+            "https://[fe80::1]/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://192.168.1.20/hook",
+            "https://100.64.0.1/hook",
+            "https://0.0.0.0/hook",
+        ] {
+            assert!(config.check_target(url).await.is_err(), "{}", url);
+        }
+        assert_eq!(
+            config.check_target("https://93.184.215.14/hook").await,
+            Ok(())
+        );
+
+        let config = WebhookConfig {
+            allow_http: true,
+            allow_private: true,
+            ..WebhookConfig::default()
+        };
+        assert_eq!(config.check_target("http://127.0.0.1:9/hook").await, Ok(()));
+    }
+
+    #[rocket::async_test]
+    async fn deliveries_to_private_targets_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn WebhookStore> = Arc::new(crate::store::JsonlWebhookStore::new(
+            dir.path().join("webhooks.jsonl"),
+            dir.path().join("deliveries.jsonl"),
+        ));
+        let now = Utc::now();
+        let webhook = Webhook {
+            id: 1,
+            owner_id: 1,
+            url: "https://127.0.0.1:9/hook".into(),
+            secret: "a shared secret!".into(),
+            events: WebhookEvent::ALL.to_vec(),
+            created_at: now,
+        };
+        let payload = WebhookPayload {
+            event_id: 1,
+            event: WebhookEvent::Created,
+            at: now,
+            task: Task::new(1, "ship".into(), String::new(), now),
+        };
+
+        let config = WebhookConfig::default();
+        let dispatcher = Dispatcher::new(Arc::clone(&store), config.clone()).unwrap();
+        deliver(
+            Arc::clone(&store),
+            dispatcher.client,
+            config,
+            webhook,
+            payload,
+            "{}".into(),
+        )
+        .await;
+
+        let deliveries = store.deliveries(1).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert!(!deliveries[0].delivered);
+        assert!(deliveries[0].error.as_ref().unwrap().contains("127.0.0.1"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Blocking calls to the task API
+
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::fmt;
+use task_model::auth::LoginResponse;
+use task_model::error::ErrorBody;
+use task_model::page::{TaskPage, MAX_LIMIT};
+use task_model::task::Task;
+
+/// Why a command failed
+#[derive(Debug)]
+pub enum CliError {
+    /// Bad command line; the usage is printed with it
+    Usage(String),
+    /// The config file could not be read or written
+    Config(String),
+    /// The server could not be reached or sent something unreadable
+    Http(reqwest::Error),
+    /// The server answered with an error
+    Api { status: StatusCode, body: ErrorBody },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Config(message) => f.write_str(message),
+            CliError::Http(e) => write!(f, "cannot talk to the server: {}", e),
+            CliError::Api { status, body } => {
+                write!(f, "{} ({})", body.message, status)?;
+                for detail in &body.details {
+                    write!(f, "\n  {}: {}", detail.field, detail.message)?;
+                }
+                if *status == StatusCode::UNAUTHORIZED {
+                    write!(f, "\nsign in with `tasks login <username>`")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<reqwest::Error> for CliError {
+    fn from(e: reqwest::Error) -> Self {
+        CliError::Http(e)
+    }
+}
+
+/// A connection to one server, signed in with `token` if there is one
+pub struct Api {
+    http: Client,
+    base: String,
+    token: Option<String>,
+}
+
+impl Api {
+    pub fn new(base: String, token: Option<String>) -> Self {
+        Api {
+            http: Client::new(),
+            base,
+            token,
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Every task visible to the signed-in user, following `next_cursor`
+    /// across pages; only those not done if `open`
+    pub fn list(&self, open: bool) -> Result<Vec<Task>, CliError> {
+        let mut tasks = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut query = vec![("sort", "id".to_string()), ("limit", MAX_LIMIT.to_string())];
+            if open {
+                query.push(("completed", "false".to_string()));
+            }
+            if let Some(cursor) = cursor.take() {
+                query.push(("cursor", cursor));
+            }
+            let page: TaskPage = parse(self.request(Method::GET, "/tasks").query(&query).send()?)?;
+            tasks.extend(page.items);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(tasks),
+            }
+        }
+    }
+
+    pub fn create(&self, body: &Value) -> Result<Task, CliError> {
+        parse(self.request(Method::POST, "/tasks").json(body).send()?)
+    }
+
+    pub fn update(&self, id: u32, patch: &Value) -> Result<Task, CliError> {
+        parse(
+            self.request(Method::PATCH, &format!("/tasks/{}", id))
+                .json(patch)
+                .send()?,
+        )
+    }
+
+    pub fn delete(&self, id: u32) -> Result<(), CliError> {
+        check(
+            self.request(Method::DELETE, &format!("/tasks/{}", id))
+                .send()?,
+        )?;
+        Ok(())
+    }
+
+    pub fn register(&self, username: &str, password: &str) -> Result<(), CliError> {
+        let body = json!({ "username": username, "password": password });
+        check(self.request(Method::POST, "/users").json(&body).send()?)?;
+        Ok(())
+    }
+
+    pub fn login(&self, username: &str, password: &str) -> Result<LoginResponse, CliError> {
+        let body = json!({ "username": username, "password": password });
+        parse(self.request(Method::POST, "/login").json(&body).send()?)
+    }
+}
+
+/// Passes a successful response through and turns any other into
+/// `CliError::Api`
+fn check(response: Response) -> Result<Response, CliError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    // Errors are JSON `ErrorBody`s, except from whatever sits in front of
+    // the server.
+    let body = response.json().unwrap_or_else(|_| {
+        ErrorBody::new(
+            "http_error",
+            status.canonical_reason().unwrap_or("request failed"),
+        )
+    });
+    Err(CliError::Api { status, body })
+}
+
+fn parse<T: DeserializeOwned>(response: Response) -> Result<T, CliError> {
+    Ok(check(response)?.json()?)
+}
@@ -0,0 +1,328 @@
+//! Command-line parsing
+//!
+//! `--url <url>` and `--json` may appear anywhere; everything else is a
+//! command followed by its arguments and options.
+
+use chrono::NaiveDate;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use task_model::task::{Priority, TaskStatus};
+
+pub const USAGE: &str = "\
+Usage: tasks [--url <url>] [--json] <command>
+
+Commands:
+  list [--open]                 List your tasks, or only those not done
+  add <title> [-d <description>] [--due <YYYY-MM-DD>] [--priority <p>]
+                                Create a task
+  done <id>                     Mark a task as done
+  rm <id>                       Move a task to the trash
+  edit <id> [--title <t>] [-d <description>] [--due <YYYY-MM-DD|none>]
+            [--priority <p>] [--status <s>]
+                                Change a task
+  register <username>           Create an account
+  login <username>              Sign in and save the token
+  help                          Show this message
+
+Priorities: low, normal, high, urgent
+Statuses:   todo, in_progress, blocked, done
+
+The server is --url, else $TASKS_URL, else `url` in the config file, else
+http://127.0.0.1:8000. The config file is $TASKS_CONFIG, else
+$XDG_CONFIG_HOME/tasks/config.toml or ~/.config/tasks/config.toml.";
+
+/// Everything given on the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    /// Server URL from `--url`, overriding the environment and config file
+    pub url: Option<String>,
+    /// Print JSON instead of tables
+    pub json: bool,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    List { open: bool },
+    Add(TaskFields),
+    Done { id: u32 },
+    Remove { id: u32 },
+    Edit { id: u32, fields: TaskFields },
+    Register { username: String },
+    Login { username: String },
+    Help,
+}
+
+/// Task fields set by `add` or changed by `edit`; `None` leaves a field as
+/// it is, or at the server's default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskFields {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// `Some(None)` clears the due date
+    pub due_date: Option<Option<NaiveDate>>,
+    pub priority: Option<Priority>,
+    pub status: Option<TaskStatus>,
+}
+
+impl TaskFields {
+    pub fn is_empty(&self) -> bool {
+        *self == TaskFields::default()
+    }
+
+    /// The fields as a request body for `POST /tasks` or `PATCH /tasks/<id>`
+    pub fn to_json(&self) -> Value {
+        let mut body = Map::new();
+        if let Some(title) = &self.title {
+            body.insert("title".into(), json!(title));
+        }
+        if let Some(description) = &self.description {
+            body.insert("description".into(), json!(description));
+        }
+        if let Some(due_date) = &self.due_date {
+            body.insert("due_date".into(), json!(due_date));
+        }
+        if let Some(priority) = &self.priority {
+            body.insert("priority".into(), json!(priority));
+        }
+        if let Some(status) = &self.status {
+            body.insert("status".into(), json!(status));
+        }
+        Value::Object(body)
+    }
+}
+
+/// An option a command accepts: its long name, short name and whether it
+/// takes a value
+type Flag = (&'static str, Option<&'static str>, bool);
+
+const DESCRIPTION: Flag = ("--description", Some("-d"), true);
+const DUE: Flag = ("--due", None, true);
+const PRIORITY: Flag = ("--priority", None, true);
+
+/// Parses the arguments after the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut url = None;
+    let mut json = false;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--url" => url = Some(args.next().ok_or("--url needs a value")?),
+            _ => rest.push(arg),
+        }
+    }
+
+    let mut rest = rest.into_iter();
+    let name = rest.next();
+    let rest: Vec<String> = rest.collect();
+    let command = match name.as_deref() {
+        None | Some("help" | "--help" | "-h") => Command::Help,
+        Some("list") => {
+            let (_, options) = split(&rest, &[("--open", None, false)], 0)?;
+            Command::List {
+                open: options.contains_key("--open"),
+            }
+        }
+        Some("add") => {
+            let (positional, options) = split(&rest, &[DESCRIPTION, DUE, PRIORITY], 1)?;
+            let mut fields = fields(&options)?;
+            fields.title = positional.into_iter().next();
+            if fields.due_date == Some(None) {
+                return Err("--due must be a date for a new task".into());
+            }
+            Command::Add(fields)
+        }
+        Some("done") => Command::Done { id: id(&rest)? },
+        Some("rm") => Command::Remove { id: id(&rest)? },
+        Some("edit") => {
+            let flags = [
+                ("--title", None, true),
+                DESCRIPTION,
+                DUE,
+                PRIORITY,
+                ("--status", None, true),
+            ];
+            let (positional, options) = split(&rest, &flags, 1)?;
+            let fields = fields(&options)?;
+            if fields.is_empty() {
+                return Err(
+                    "edit needs at least one of --title, -d, --due, --priority or --status".into(),
+                );
+            }
+            Command::Edit {
+                id: parse_id(&positional[0])?,
+                fields,
+            }
+        }
+        Some("register") => Command::Register {
+            username: username(&rest)?,
+        },
+        Some("login") => Command::Login {
+            username: username(&rest)?,
+        },
+        Some(other) => return Err(format!("unknown command '{}'", other)),
+    };
+    Ok(Args { url, json, command })
+}
+
+/// Splits `args` into exactly `positional` positional arguments and the
+/// `flags` given, keyed by long name; switches map to an empty string
+fn split(
+    args: &[String],
+    flags: &[Flag],
+    positional: usize,
+) -> Result<(Vec<String>, BTreeMap<&'static str, String>), String> {
+    let mut values = Vec::new();
+    let mut options = BTreeMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            values.push(arg.clone());
+            continue;
+        }
+        let &(long, _, takes_value) = flags
+            .iter()
+            .find(|(long, short, _)| arg == long || Some(arg.as_str()) == *short)
+            .ok_or_else(|| format!("unknown option '{}'", arg))?;
+        let value = if takes_value {
+            args.next()
+                .ok_or_else(|| format!("{} needs a value", long))?
+                .clone()
+        } else {
+            String::new()
+        };
+        options.insert(long, value);
+    }
+
+    if values.len() != positional {
+        return Err(format!(
+            "expected {} argument(s), got {}",
+            positional,
+            values.len()
+        ));
+    }
+    Ok((values, options))
+}
+
+fn fields(options: &BTreeMap<&str, String>) -> Result<TaskFields, String> {
+    let due_date = match options.get("--due").map(String::as_str) {
+        None => None,
+        Some("none") => Some(None),
+        Some(date) => Some(Some(
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| format!("--due: '{}' is not a YYYY-MM-DD date", date))?,
+        )),
+    };
+    Ok(TaskFields {
+        title: options.get("--title").cloned(),
+        description: options.get("--description").cloned(),
+        due_date,
+        priority: options
+            .get("--priority")
+            .map(|p| p.parse().map_err(|e| format!("--priority: {}", e)))
+            .transpose()?,
+        status: options
+            .get("--status")
+            .map(|s| s.parse().map_err(|e| format!("--status: {}", e)))
+            .transpose()?,
+    })
+}
+
+fn id(args: &[String]) -> Result<u32, String> {
+    let (positional, _) = split(args, &[], 1)?;
+    parse_id(&positional[0])
+}
+
+fn parse_id(arg: &str) -> Result<u32, String> {
+    arg.parse()
+        .map_err(|_| format!("'{}' is not a task id", arg))
+}
+
+fn username(args: &[String]) -> Result<String, String> {
+    let (mut positional, _) = split(args, &[], 1)?;
+    Ok(positional.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Args, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_commands_and_global_flags() {
+        let args = parse_str("list --open --json --url http://tasks:9000").unwrap();
+        assert_eq!(args.url.as_deref(), Some("http://tasks:9000"));
+        assert!(args.json);
+        assert_eq!(args.command, Command::List { open: true });
+
+        let args = parse(
+            [
+                "add",
+                "water plants",
+                "-d",
+                "the ferns",
+                "--due",
+                "2026-05-01",
+            ]
+            .map(String::from),
+        )
+        .unwrap();
+        let Command::Add(fields) = args.command else {
+            panic!("not an add: {:?}", args.command);
+        };
+        assert_eq!(
+            fields.to_json(),
+            json!({ "title": "water plants", "description": "the ferns", "due_date": "2026-05-01" })
+        );
+
+        let args = parse_str("edit 4 --due none --priority urgent --status in_progress").unwrap();
+        let Command::Edit { id: 4, fields } = args.command else {
+            panic!("not an edit of 4: {:?}", args.command);
+        };
+        assert_eq!(
+            fields.to_json(),
+            json!({ "due_date": null, "priority": "urgent", "status": "in_progress" })
+        );
+
+        assert_eq!(
+            parse_str("done 3").unwrap().command,
+            Command::Done { id: 3 }
+        );
+        assert_eq!(
+            parse_str("rm 3").unwrap().command,
+            Command::Remove { id: 3 }
+        );
+        assert_eq!(parse_str("").unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        for (line, error) in [
+            ("frobnicate", "unknown command 'frobnicate'"),
+            ("list --closed", "unknown option '--closed'"),
+            ("add", "expected 1 argument(s), got 0"),
+            ("add title --due", "--due needs a value"),
+            (
+                "add title --due tomorrow",
+                "--due: 'tomorrow' is not a YYYY-MM-DD date",
+            ),
+            ("done three", "'three' is not a task id"),
+            (
+                "edit 3",
+                "edit needs at least one of --title, -d, --due, --priority or --status",
+            ),
+            (
+                "edit 3 --status finished",
+                "--status: unknown status 'finished'",
+            ),
+            ("list --url", "--url needs a value"),
+        ] {
+            assert_eq!(parse_str(line).unwrap_err(), error, "for {:?}", line);
+        }
+    }
+}
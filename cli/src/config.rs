@@ -0,0 +1,120 @@
+//! Where the server is and how to sign in to it
+//!
+//! Read from a TOML file, overridden by `TASKS_URL` / `TASKS_TOKEN` and then
+//! by `--url`. `tasks login` writes the token it receives back to the file.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Server used when nothing else names one; the backend's default address
+pub const DEFAULT_URL: &str = "http://127.0.0.1:8000";
+
+/// The config file
+///
+/// ```toml
+/// url = "https://tasks.example.com"
+/// token = "..."
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Config {
+    /// Reads `path`, or returns an empty config if it does not exist
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Writes the config to `path`, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        }
+        fs::write(path, text).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+
+    /// The server URL and token to use, given `--url` and a way to read
+    /// environment variables
+    pub fn resolve(
+        &self,
+        url_flag: Option<String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> (String, Option<String>) {
+        let url = url_flag
+            .or_else(|| env("TASKS_URL"))
+            .or_else(|| self.url.clone())
+            .unwrap_or_else(|| DEFAULT_URL.to_string());
+        let token = env("TASKS_TOKEN").or_else(|| self.token.clone());
+        (url.trim_end_matches('/').to_string(), token)
+    }
+}
+
+/// `$TASKS_CONFIG`, else `tasks/config.toml` under `$XDG_CONFIG_HOME` or
+/// `~/.config`
+pub fn path(env: impl Fn(&str) -> Option<String>) -> Result<PathBuf, String> {
+    if let Some(path) = env("TASKS_CONFIG") {
+        return Ok(PathBuf::from(path));
+    }
+    let dir = env("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok_or("cannot find the config file: set TASKS_CONFIG or HOME")?;
+    Ok(dir.join("tasks").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn flags_beat_environment_beats_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let vars = HashMap::from([("HOME", dir.path().to_str().unwrap().to_string())]);
+        let env = |name: &str| vars.get(name).cloned();
+        let path = path(env).unwrap();
+        assert_eq!(path, dir.path().join(".config/tasks/config.toml"));
+
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+        assert_eq!(
+            Config::default().resolve(None, env),
+            (DEFAULT_URL.to_string(), None)
+        );
+
+        let config = Config {
+            url: Some("http://file:1/".into()),
+            token: Some("from-file".into()),
+        };
+        config.save(&path).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(
+            config.resolve(None, env),
+            ("http://file:1".to_string(), Some("from-file".to_string()))
+        );
+
+        let vars = HashMap::from([
+            ("TASKS_URL", "http://env:2".to_string()),
+            ("TASKS_TOKEN", "from-env".to_string()),
+        ]);
+        let env = |name: &str| vars.get(name).cloned();
+        assert_eq!(
+            config.resolve(None, env),
+            ("http://env:2".to_string(), Some("from-env".to_string()))
+        );
+        assert_eq!(
+            config.resolve(Some("http://flag:3".into()), env).0,
+            "http://flag:3"
+        );
+    }
+}
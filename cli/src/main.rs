@@ -0,0 +1,127 @@
+//! `tasks`: a command-line client for the task backend
+//!
+//! Talks to the server over its REST API and decodes the answers into the
+//! backend's own `Task` type. Run `tasks help` for the commands.
+
+mod api;
+mod args;
+mod config;
+mod output;
+
+use api::{Api, CliError};
+use args::{Args, Command};
+use config::Config;
+use serde::Serialize;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+use task_model::task::{Task, TaskStatus};
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e @ CliError::Usage(_)) => {
+            eprintln!("error: {}\n\n{}", e, args::USAGE);
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let Args { url, json, command } = args::parse(args).map_err(CliError::Usage)?;
+    if command == Command::Help {
+        println!("{}", args::USAGE);
+        return Ok(());
+    }
+
+    let env = |name: &str| std::env::var(name).ok();
+    let config_path = config::path(env).map_err(CliError::Config)?;
+    let mut config = Config::load(&config_path).map_err(CliError::Config)?;
+    let (url, token) = config.resolve(url, env);
+    let api = Api::new(url, token);
+
+    match command {
+        Command::List { open } => {
+            let tasks = api.list(open)?;
+            if json {
+                print_json(&tasks);
+            } else {
+                print!("{}", output::table(&tasks));
+            }
+        }
+        Command::Add(fields) => {
+            let task = api.create(&fields.to_json())?;
+            print_task(&task, json, "Added");
+        }
+        Command::Done { id } => {
+            let patch = serde_json::json!({ "status": TaskStatus::Done });
+            let task = api.update(id, &patch)?;
+            print_task(&task, json, "Completed");
+        }
+        Command::Remove { id } => {
+            api.delete(id)?;
+            if !json {
+                println!("Moved task {} to the trash", id);
+            }
+        }
+        Command::Edit { id, fields } => {
+            let task = api.update(id, &fields.to_json())?;
+            print_task(&task, json, "Updated");
+        }
+        Command::Register { username } => {
+            api.register(&username, &password()?)?;
+            println!(
+                "Registered {}; sign in with `tasks login {}`",
+                username, username
+            );
+        }
+        Command::Login { username } => {
+            let login = api.login(&username, &password()?)?;
+            config.token = Some(login.token);
+            config.save(&config_path).map_err(CliError::Config)?;
+            println!(
+                "Signed in as {} until {}; token saved to {}",
+                username,
+                login.expires_at,
+                config_path.display()
+            );
+        }
+        Command::Help => unreachable!("handled above"),
+    }
+    Ok(())
+}
+
+/// Prints `task` as JSON, or as `<verb> task <id>` and its table row
+fn print_task(task: &Task, json: bool, verb: &str) {
+    if json {
+        print_json(task);
+    } else {
+        println!("{} task {}", verb, task.id);
+        print!("{}", output::table(std::slice::from_ref(task)));
+    }
+}
+
+fn print_json(value: &impl Serialize) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("tasks serialize to JSON")
+    );
+}
+
+/// `$TASKS_PASSWORD`, or a line read from standard input after a prompt
+fn password() -> Result<String, CliError> {
+    if let Ok(password) = std::env::var("TASKS_PASSWORD") {
+        return Ok(password);
+    }
+    eprint!("Password: ");
+    io::stderr().flush().ok();
+    let mut line = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| CliError::Usage(format!("cannot read the password: {}", e)))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
@@ -0,0 +1,71 @@
+//! Tasks as text tables
+
+use task_model::task::Task;
+
+const HEADERS: [&str; 5] = ["ID", "STATUS", "PRIORITY", "DUE", "TITLE"];
+
+/// `tasks` as a table with one row per task and columns padded to fit
+pub fn table(tasks: &[Task]) -> String {
+    if tasks.is_empty() {
+        return "No tasks.\n".to_string();
+    }
+
+    let rows: Vec<[String; 5]> = tasks
+        .iter()
+        .map(|task| {
+            [
+                task.id.to_string(),
+                task.status.to_string(),
+                task.priority.to_string(),
+                task.due_date.map(|d| d.to_string()).unwrap_or_default(),
+                task.title.clone(),
+            ]
+        })
+        .collect();
+    let mut widths = HEADERS.map(|header| header.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    let headers = HEADERS.map(String::from);
+    for row in std::iter::once(&headers).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Utc};
+    use task_model::task::{Priority, TaskStatus};
+
+    #[test]
+    fn pads_columns_to_the_widest_cell() {
+        let now = Utc::now();
+        let mut first = Task::new(7, "water plants".into(), String::new(), now);
+        first.due_date = NaiveDate::from_ymd_opt(2026, 5, 1);
+        let mut second = Task::new(12, "file taxes".into(), String::new(), now);
+        second.status = TaskStatus::InProgress;
+        second.priority = Priority::Urgent;
+
+        assert_eq!(
+            table(&[first, second]),
+            "\
+ID  STATUS       PRIORITY  DUE         TITLE
+7   todo         normal    2026-05-01  water plants
+12  in_progress  urgent                file taxes
+"
+        );
+        assert_eq!(table(&[]), "No tasks.\n");
+    }
+}
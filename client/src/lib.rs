@@ -0,0 +1,313 @@
+//! Async Rust client for the task API
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), task_client::ClientError> {
+//! use task_client::TaskClient;
+//! use task_model::task::{NewTask, TaskPatch, TaskStatus};
+//!
+//! let client = TaskClient::new("http://127.0.0.1:8000", "<bearer token>")?;
+//! let task = client.create(&NewTask::new("water plants")).await?;
+//! let patch = TaskPatch {
+//!     status: Some(TaskStatus::Done),
+//!     ..TaskPatch::default()
+//! };
+//! client.update_if_unchanged(&task, &patch).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests and responses use the server's own types from `task_model`, and
+//! error responses come back as a `ClientError` variant per status.
+
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use task_model::error::ErrorBody;
+use task_model::page::TaskPage;
+use task_model::task::{NewTask, Task, TaskPatch};
+
+/// Why a call failed
+///
+/// Each status the API documents has its own variant carrying the server's
+/// `ErrorBody`; anything else is `Unexpected`.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The base URL given to `TaskClient::new` is not an absolute URL
+    InvalidUrl(String),
+    /// The server could not be reached, or sent a body that is not what the
+    /// route returns
+    Http(reqwest::Error),
+    /// 400: the request could not be read
+    BadRequest(ErrorBody),
+    /// 401: the token is missing, invalid or expired
+    Unauthorized(ErrorBody),
+    /// 403: the task belongs to another user
+    Forbidden(ErrorBody),
+    /// 404: no such task, or not visible to this user
+    NotFound(ErrorBody),
+    /// 409: the id is taken, the status change is not allowed or open
+    /// blockers remain
+    Conflict(ErrorBody),
+    /// 412: the task changed since the version the update was based on
+    PreconditionFailed(ErrorBody),
+    /// 422: the body or query failed validation; see `ErrorBody::details`
+    Unprocessable(ErrorBody),
+    /// Any other error status, including 5xx
+    Unexpected { status: StatusCode, body: ErrorBody },
+}
+
+impl ClientError {
+    /// Maps an error response to its variant
+    fn from_status(status: StatusCode, body: ErrorBody) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => ClientError::BadRequest(body),
+            StatusCode::UNAUTHORIZED => ClientError::Unauthorized(body),
+            StatusCode::FORBIDDEN => ClientError::Forbidden(body),
+            StatusCode::NOT_FOUND => ClientError::NotFound(body),
+            StatusCode::CONFLICT => ClientError::Conflict(body),
+            StatusCode::PRECONDITION_FAILED => ClientError::PreconditionFailed(body),
+            StatusCode::UNPROCESSABLE_ENTITY => ClientError::Unprocessable(body),
+            status => ClientError::Unexpected { status, body },
+        }
+    }
+
+    /// The server's error body, for errors that came with one
+    pub fn body(&self) -> Option<&ErrorBody> {
+        match self {
+            ClientError::InvalidUrl(_) | ClientError::Http(_) => None,
+            ClientError::BadRequest(body)
+            | ClientError::Unauthorized(body)
+            | ClientError::Forbidden(body)
+            | ClientError::NotFound(body)
+            | ClientError::Conflict(body)
+            | ClientError::PreconditionFailed(body)
+            | ClientError::Unprocessable(body)
+            | ClientError::Unexpected { body, .. } => Some(body),
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidUrl(message) => write!(f, "invalid base URL: {}", message),
+            ClientError::Http(e) => write!(f, "request failed: {}", e),
+            ClientError::Unexpected { status, body } => {
+                write!(f, "{}: {} ({})", status, body.message, body.code)
+            }
+            other => {
+                let body = other.body().expect("status errors carry a body");
+                write!(f, "{} ({})", body.message, body.code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+/// Filters, sort and page for `TaskClient::list`, as in `GET /tasks`
+///
+/// Every field left at `None` is left out of the query string, so the
+/// server's default applies.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ListOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    /// `todo`, `in_progress`, `blocked` or `done`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// `low`, `normal`, `high` or `urgent`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overdue: Option<bool>,
+    /// Text to look for in titles and descriptions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// A project id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<u32>,
+    /// A tag, matched case-insensitively
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// A field name, `-` first for descending, e.g. `-due_date`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// `TaskPage::next_cursor` of the previous page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// A connection to one task server, signed in with a bearer token
+///
+/// Cheap to clone; clones share one connection pool.
+#[derive(Debug, Clone)]
+pub struct TaskClient {
+    http: reqwest::Client,
+    base: Url,
+    token: String,
+}
+
+impl TaskClient {
+    /// A client for the server at `base_url`, sending `token` as
+    /// `Authorization: Bearer <token>`
+    pub fn new(base_url: &str, token: impl Into<String>) -> Result<Self, ClientError> {
+        Self::with_http(reqwest::Client::new(), base_url, token)
+    }
+
+    /// Like `new`, with a `reqwest::Client` set up by the caller, e.g. with
+    /// timeouts
+    pub fn with_http(
+        http: reqwest::Client,
+        base_url: &str,
+        token: impl Into<String>,
+    ) -> Result<Self, ClientError> {
+        let mut base = Url::parse(base_url).map_err(|e| ClientError::InvalidUrl(e.to_string()))?;
+        if base.cannot_be_a_base() {
+            return Err(ClientError::InvalidUrl(format!(
+                "'{}' cannot have paths appended",
+                base_url
+            )));
+        }
+        // Without a trailing slash `join` would replace the last segment.
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        Ok(TaskClient {
+            http,
+            base,
+            token: token.into(),
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = self
+            .base
+            .join(path)
+            .expect("API paths are valid relative URLs");
+        self.http.request(method, url).bearer_auth(&self.token)
+    }
+
+    /// One page of the tasks this user can see
+    pub async fn list(&self, options: &ListOptions) -> Result<TaskPage, ClientError> {
+        parse(
+            self.request(Method::GET, "tasks")
+                .query(options)
+                .send()
+                .await?,
+        )
+        .await
+    }
+
+    /// Every matching task, following `next_cursor` from the page
+    /// `options` names to the last one
+    pub async fn list_all(&self, options: &ListOptions) -> Result<Vec<Task>, ClientError> {
+        let mut options = options.clone();
+        let mut tasks = Vec::new();
+        loop {
+            let page = self.list(&options).await?;
+            tasks.extend(page.items);
+            match page.next_cursor {
+                Some(cursor) => options.cursor = Some(cursor),
+                None => return Ok(tasks),
+            }
+        }
+    }
+
+    pub async fn get(&self, id: u32) -> Result<Task, ClientError> {
+        parse(
+            self.request(Method::GET, &format!("tasks/{}", id))
+                .send()
+                .await?,
+        )
+        .await
+    }
+
+    /// Creates `task`, owned by this user, and returns it as stored
+    pub async fn create(&self, task: &NewTask) -> Result<Task, ClientError> {
+        parse(
+            self.request(Method::POST, "tasks")
+                .json(task)
+                .send()
+                .await?,
+        )
+        .await
+    }
+
+    /// Applies `patch` to task `id`, whatever its current version
+    pub async fn update(&self, id: u32, patch: &TaskPatch) -> Result<Task, ClientError> {
+        parse(
+            self.request(Method::PATCH, &format!("tasks/{}", id))
+                .json(patch)
+                .send()
+                .await?,
+        )
+        .await
+    }
+
+    /// Applies `patch` to `task` only if it is still at `task.version`, and
+    /// fails with `ClientError::PreconditionFailed` otherwise
+    pub async fn update_if_unchanged(
+        &self,
+        task: &Task,
+        patch: &TaskPatch,
+    ) -> Result<Task, ClientError> {
+        parse(
+            self.request(Method::PATCH, &format!("tasks/{}", task.id))
+                .header("If-Match", task.etag())
+                .json(patch)
+                .send()
+                .await?,
+        )
+        .await
+    }
+
+    /// Moves task `id` to the trash
+    pub async fn delete(&self, id: u32) -> Result<(), ClientError> {
+        check(
+            self.request(Method::DELETE, &format!("tasks/{}", id))
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Passes a successful response through and turns any other into its
+/// `ClientError`
+async fn check(response: Response) -> Result<Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    // The API always answers errors with an `ErrorBody`, but a proxy in
+    // front of it may not.
+    let body = response.json().await.unwrap_or_else(|_| {
+        ErrorBody::new(
+            "http_error",
+            status.canonical_reason().unwrap_or("request failed"),
+        )
+    });
+    Err(ClientError::from_status(status, body))
+}
+
+async fn parse<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+    Ok(check(response).await?.json().await?)
+}
@@ -0,0 +1,238 @@
+use rocket::fairing::AdHoc;
+use rocket::tokio::sync::oneshot;
+use serde_json::json;
+use task_client::{ClientError, ListOptions, TaskClient};
+use task_model::task::{NewTask, TaskPatch, TaskStatus};
+use tempfile::TempDir;
+
+/// Serves the backend on a free local port, keeping its files in a fresh
+/// temp directory, and returns its base URL
+async fn launch() -> (String, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let figment = rocket::Config::figment()
+        .merge(("address", "127.0.0.1"))
+        .merge(("port", 0))
+        .merge(("log_level", "off"))
+        .merge(("data_dir", dir.path()))
+        .merge(("storage.kind", "csv"))
+        .merge(("storage.path", "tasks.csv"));
+    let (sender, receiver) = oneshot::channel();
+    let rocket = backend::rocket()
+        .configure(figment)
+        .attach(AdHoc::on_liftoff("Bound Port", move |rocket| {
+            Box::pin(async move {
+                let _ = sender.send(rocket.config().port);
+            })
+        }));
+    rocket::tokio::spawn(rocket.launch());
+    let port = receiver.await.expect("the server launches");
+    (format!("http://127.0.0.1:{}", port), dir)
+}
+
+/// Registers `username` and returns their bearer token
+async fn register(base: &str, username: &str) -> String {
+    let http = reqwest::Client::new();
+    let credentials = json!({ "username": username, "password": "correct horse" });
+    let response = http
+        .post(format!("{}/users", base))
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let login: serde_json::Value = http
+        .post(format!("{}/login", base))
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    login["token"].as_str().unwrap().to_string()
+}
+
+/// Registers `username` and returns a client signed in as them
+async fn sign_in(base: &str, username: &str) -> TaskClient {
+    TaskClient::new(base, register(base, username).await).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_round_trips_against_the_server() {
+    let (base, _dir) = launch().await;
+    let client = sign_in(&base, "alice").await;
+
+    let mut new_task = NewTask::new("water plants");
+    new_task.description = "the ferns".into();
+    let created = client.create(&new_task).await.unwrap();
+    assert_eq!(created.title, "water plants");
+    assert_eq!(created.version, 1);
+    assert_eq!(client.get(created.id).await.unwrap(), created);
+    for title in ["file taxes", "call mum"] {
+        client.create(&NewTask::new(title)).await.unwrap();
+    }
+
+    let page = client
+        .list(&ListOptions {
+            limit: Some(2),
+            sort: Some("id".into()),
+            ..ListOptions::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.total, 3);
+    assert!(page.next_cursor.is_some());
+    let all = client
+        .list_all(&ListOptions {
+            limit: Some(2),
+            sort: Some("id".into()),
+            ..ListOptions::default()
+        })
+        .await
+        .unwrap();
+    let titles: Vec<&str> = all.iter().map(|task| task.title.as_str()).collect();
+    assert_eq!(titles, ["water plants", "file taxes", "call mum"]);
+
+    let done = TaskPatch {
+        status: Some(TaskStatus::Done),
+        ..TaskPatch::default()
+    };
+    let updated = client.update_if_unchanged(&created, &done).await.unwrap();
+    assert_eq!(updated.status, TaskStatus::Done);
+    assert_eq!(updated.version, 2);
+    let open = client
+        .list(&ListOptions {
+            completed: Some(false),
+            ..ListOptions::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(open.total, 2);
+
+    // `created` is now a version behind.
+    let rename = TaskPatch {
+        title: Some("water the plants".into()),
+        ..TaskPatch::default()
+    };
+    let error = client
+        .update_if_unchanged(&created, &rename)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, ClientError::PreconditionFailed(_)),
+        "{:?}",
+        error
+    );
+    let renamed = client.update(created.id, &rename).await.unwrap();
+    assert_eq!(renamed.title, "water the plants");
+
+    client.delete(created.id).await.unwrap();
+    assert!(matches!(
+        client.get(created.id).await,
+        Err(ClientError::NotFound(_))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lists_filter_by_project_and_tag() {
+    let (base, _dir) = launch().await;
+    let token = register(&base, "alice").await;
+    let client = TaskClient::new(&base, &token).unwrap();
+    let project: serde_json::Value = reqwest::Client::new()
+        .post(format!("{}/projects", base))
+        .bearer_auth(&token)
+        .json(&json!({ "name": "garden" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let project_id = project["id"].as_u64().unwrap() as u32;
+
+    let mut weeds = NewTask::new("pull weeds");
+    weeds.project_id = Some(project_id);
+    weeds.tags = vec!["outdoors".into()];
+    let weeds = client.create(&weeds).await.unwrap();
+    let mut seeds = NewTask::new("order seeds");
+    seeds.project_id = Some(project_id);
+    client.create(&seeds).await.unwrap();
+    let mut run = NewTask::new("go for a run");
+    run.tags = vec!["outdoors".into()];
+    client.create(&run).await.unwrap();
+
+    let titles = |tasks: Vec<task_model::task::Task>| -> Vec<String> {
+        tasks.into_iter().map(|task| task.title).collect()
+    };
+    let in_project = client
+        .list_all(&ListOptions {
+            project: Some(project_id),
+            ..ListOptions::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(titles(in_project), ["pull weeds", "order seeds"]);
+    let tagged = client
+        .list_all(&ListOptions {
+            tag: Some("Outdoors".into()),
+            ..ListOptions::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(titles(tagged), ["pull weeds", "go for a run"]);
+    let both = client
+        .list(&ListOptions {
+            project: Some(project_id),
+            tag: Some("outdoors".into()),
+            ..ListOptions::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(both.items, [weeds]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn error_statuses_map_to_variants() {
+    let (base, _dir) = launch().await;
+    let alice = sign_in(&base, "alice").await;
+    let bob = sign_in(&base, "bob").await;
+
+    let error = alice.create(&NewTask::new("  ")).await.unwrap_err();
+    let ClientError::Unprocessable(body) = &error else {
+        panic!("expected 422, got {:?}", error);
+    };
+    assert_eq!(body.details[0].field, "title");
+
+    let mut shared = NewTask::new("plan the trip");
+    shared.shared = true;
+    let shared = alice.create(&shared).await.unwrap();
+    let private = alice.create(&NewTask::new("diary")).await.unwrap();
+    assert!(matches!(
+        bob.delete(shared.id).await,
+        Err(ClientError::Forbidden(_))
+    ));
+    assert!(matches!(
+        bob.get(private.id).await,
+        Err(ClientError::NotFound(_))
+    ));
+
+    let mut taken = NewTask::new("again");
+    taken.id = Some(shared.id);
+    assert!(matches!(
+        alice.create(&taken).await,
+        Err(ClientError::Conflict(_))
+    ));
+
+    let stranger = TaskClient::new(&base, "not-a-token").unwrap();
+    let error = stranger.get(shared.id).await.unwrap_err();
+    assert!(matches!(error, ClientError::Unauthorized(_)), "{:?}", error);
+    assert_eq!(error.body().unwrap().code, "unauthorized");
+
+    assert!(matches!(
+        TaskClient::new("not a url", "token"),
+        Err(ClientError::InvalidUrl(_))
+    ));
+    let nowhere = TaskClient::new("http://127.0.0.1:1", "token").unwrap();
+    assert!(matches!(nowhere.get(1).await, Err(ClientError::Http(_))));
+}
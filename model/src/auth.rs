@@ -0,0 +1,13 @@
+//! Bodies of the account routes
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Body returned by `POST /login`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LoginResponse {
+    pub token: String,
+    pub token_type: String,
+    pub expires_at: DateTime<Utc>,
+}
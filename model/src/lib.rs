@@ -0,0 +1,15 @@
+//! Types shared by the task backend and its clients
+//!
+//! `Task` and its request bodies, the page `GET /tasks` returns with its
+//! limits, the `POST /login` response and the JSON error envelope are
+//! defined here once, so the server, the `tasks` CLI and
+//! `task-client` agree on the wire format. The `openapi` feature adds the
+//! `ToSchema` derives the backend's OpenAPI document is built from.
+
+pub mod auth;
+pub mod error;
+pub mod page;
+pub mod recurrence;
+pub mod reminder;
+pub mod tag;
+pub mod task;
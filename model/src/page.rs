@@ -0,0 +1,21 @@
+//! One page of a task listing
+
+use crate::task::Task;
+use serde::{Deserialize, Serialize};
+
+/// Page size when `limit` is not given
+pub const DEFAULT_LIMIT: usize = 50;
+/// Largest accepted `limit`
+pub const MAX_LIMIT: usize = 200;
+
+/// One page of `GET /tasks`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskPage {
+    pub items: Vec<Task>,
+    /// Number of tasks matching the filters, across all pages
+    pub total: usize,
+    /// Pass as `cursor` to fetch the next page; absent on the last page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
@@ -0,0 +1,379 @@
+//! Rules for tasks that repeat
+//!
+//! ```json
+//! { "every": "week", "interval": 1, "weekdays": ["mon"] }
+//! { "every": "month", "interval": 3, "day": 15 }
+//! { "every": "cron", "expr": "1,15 * *" }
+//! ```
+//!
+//! Rules work on whole days: an occurrence is a due date.
+
+use crate::error::FieldError;
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// How far ahead `Recurrence::next_after` looks for a cron match; five years
+/// always include a 29 February
+const CRON_HORIZON_DAYS: u64 = 5 * 366;
+
+/// When the next occurrence of a repeating task is due
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "every", rename_all = "snake_case")]
+pub enum Recurrence {
+    /// Every `interval` days
+    Day {
+        #[serde(default = "one")]
+        interval: u32,
+    },
+    /// On `weekdays` of every `interval`th week; with no weekdays, on the
+    /// weekday of the first occurrence
+    Week {
+        #[serde(default = "one")]
+        interval: u32,
+        #[serde(default)]
+        #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+        weekdays: Vec<Weekday>,
+    },
+    /// On `day` of every `interval`th month, or the month's last day if it is
+    /// shorter; with no day, on the day of the first occurrence
+    Month {
+        #[serde(default = "one")]
+        interval: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        day: Option<u32>,
+    },
+    /// On the days matching `expr`, the date fields of a crontab line:
+    /// `<day of month> <month> <day of week>`, each `*`, a number, a range
+    /// `a-b`, a step `*/n` or `a-b/n`, or a comma-separated list of those.
+    /// Sunday is 0 or 7. As in cron, when both days are restricted a day
+    /// matching either one counts.
+    Cron { expr: String },
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Recurrence {
+    /// The first occurrence after `date`, counting `date` as an occurrence
+    ///
+    /// `None` only for a cron expression with no match in the next five
+    /// years, which `validate` rejects.
+    pub fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Day { interval } => date.checked_add_days(Days::new(u64::from(*interval))),
+            Recurrence::Week { interval, weekdays } => {
+                let later_this_week = weekdays
+                    .iter()
+                    .map(Weekday::num_days_from_monday)
+                    .filter(|&day| day > date.weekday().num_days_from_monday())
+                    .min();
+                if let Some(day) = later_this_week {
+                    let offset = day - date.weekday().num_days_from_monday();
+                    return date.checked_add_days(Days::new(u64::from(offset)));
+                }
+                let monday = date.week(Weekday::Mon).first_day();
+                let first = weekdays
+                    .iter()
+                    .map(Weekday::num_days_from_monday)
+                    .min()
+                    .unwrap_or(date.weekday().num_days_from_monday());
+                monday.checked_add_days(Days::new(7 * u64::from(*interval) + u64::from(first)))
+            }
+            Recurrence::Month { interval, day } => {
+                let month = date
+                    .with_day(1)?
+                    .checked_add_months(Months::new(*interval))?;
+                let last = days_in_month(month);
+                month.with_day(day.unwrap_or(date.day()).min(last))
+            }
+            Recurrence::Cron { expr } => {
+                let cron = expr.parse::<Cron>().ok()?;
+                (1..=CRON_HORIZON_DAYS)
+                    .map_while(|n| date.checked_add_days(Days::new(n)))
+                    .find(|day| cron.matches(*day))
+            }
+        }
+    }
+
+    /// The rule with the parts it leaves to the first occurrence filled in
+    /// from `first`, so later occurrences do not drift, e.g. from the 31st
+    /// to the 28th after February
+    pub fn anchored(&self, first: NaiveDate) -> Recurrence {
+        match self {
+            Recurrence::Week { interval, weekdays } if weekdays.is_empty() => Recurrence::Week {
+                interval: *interval,
+                weekdays: vec![first.weekday()],
+            },
+            Recurrence::Month {
+                interval,
+                day: None,
+            } => Recurrence::Month {
+                interval: *interval,
+                day: Some(first.day()),
+            },
+            other => other.clone(),
+        }
+    }
+
+    /// Checks the interval, the day of the month and the cron expression
+    pub fn validate(&self) -> Result<(), FieldError> {
+        let problem = match self {
+            Recurrence::Day { interval }
+            | Recurrence::Week { interval, .. }
+            | Recurrence::Month { interval, .. }
+                if *interval == 0 =>
+            {
+                Some("interval must be at least 1".to_string())
+            }
+            Recurrence::Month { day: Some(day), .. } if !(1..=31).contains(day) => {
+                Some(format!("day {} is not a day of the month", day))
+            }
+            Recurrence::Cron { expr } => match expr.parse::<Cron>() {
+                Err(e) => Some(e),
+                Ok(cron) if !cron.ever_matches() => Some(format!("'{}' never matches", expr)),
+                Ok(_) => None,
+            },
+            _ => None,
+        };
+        match problem {
+            Some(message) => Err(FieldError::new("recurrence", message)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn days_in_month(first: NaiveDate) -> u32 {
+    let next = first + Months::new(1);
+    next.signed_duration_since(first).num_days() as u32
+}
+
+/// A parsed cron date expression; bit `n` of each mask is set when value `n`
+/// matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cron {
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    fn matches(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        let either = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        self.months & (1 << date.month()) != 0 && either
+    }
+
+    /// Whether any date at all matches, checked over one leap-year cycle
+    fn ever_matches(&self) -> bool {
+        let start = NaiveDate::from_ymd_opt(2000, 1, 1).expect("a valid date");
+        (0..CRON_HORIZON_DAYS)
+            .map_while(|n| start.checked_add_days(Days::new(n)))
+            .any(|day| self.matches(day))
+    }
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "'{}' must have 3 fields: day of month, month and day of week",
+                expr
+            ));
+        };
+        let mut weekday_mask = field(weekdays, 0, 7, "day of week")?;
+        // 7 is another name for Sunday.
+        if weekday_mask & (1 << 7) != 0 {
+            weekday_mask |= 1;
+        }
+        Ok(Cron {
+            days: field(days, 1, 31, "day of month")?,
+            months: field(months, 1, 12, "month")?,
+            weekdays: weekday_mask,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+/// Parses one cron field into a bit mask of the values in `min..=max`
+fn field(text: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid {} '{}'", name, text);
+    let number = |s: &str| -> Result<u32, String> {
+        s.parse()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| format!("{} '{}' is not between {} and {}", name, s, min, max))
+    };
+
+    let mut mask = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(invalid)?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn next_occurrences() {
+        let day = Recurrence::Day { interval: 3 };
+        assert_eq!(day.next_after(date("2026-02-27")), Some(date("2026-03-02")));
+
+        // 2026-10-14 is a Wednesday.
+        let week = Recurrence::Week {
+            interval: 2,
+            weekdays: vec![Weekday::Mon, Weekday::Thu],
+        };
+        assert_eq!(
+            week.next_after(date("2026-10-14")),
+            Some(date("2026-10-15"))
+        );
+        assert_eq!(
+            week.next_after(date("2026-10-15")),
+            Some(date("2026-10-26"))
+        );
+
+        let month = Recurrence::Month {
+            interval: 1,
+            day: None,
+        }
+        .anchored(date("2026-01-31"));
+        assert_eq!(
+            month.next_after(date("2026-01-31")),
+            Some(date("2026-02-28"))
+        );
+        assert_eq!(
+            month.next_after(date("2026-02-28")),
+            Some(date("2026-03-31"))
+        );
+
+        // Both days restricted: the 1st to the 7th, or any Monday.
+        let early_or_monday = Recurrence::Cron {
+            expr: "1-7 * 1".into(),
+        };
+        assert_eq!(
+            early_or_monday.next_after(date("2026-10-14")),
+            Some(date("2026-10-19"))
+        );
+        assert_eq!(
+            early_or_monday.next_after(date("2026-10-26")),
+            Some(date("2026-11-01"))
+        );
+        let leap_day = Recurrence::Cron {
+            expr: "29 2 *".into(),
+        };
+        assert_eq!(
+            leap_day.next_after(date("2026-10-14")),
+            Some(date("2028-02-29"))
+        );
+        let quarterly = Recurrence::Cron {
+            expr: "15 */3 *".into(),
+        };
+        assert_eq!(
+            quarterly.next_after(date("2026-10-14")),
+            Some(date("2026-10-15"))
+        );
+        assert_eq!(
+            quarterly.next_after(date("2026-10-15")),
+            Some(date("2027-01-15"))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for (rule, message) in [
+            (
+                Recurrence::Day { interval: 0 },
+                "interval must be at least 1",
+            ),
+            (
+                Recurrence::Month {
+                    interval: 1,
+                    day: Some(32),
+                },
+                "day 32 is not a day of the month",
+            ),
+            (
+                Recurrence::Cron {
+                    expr: "0 9 * * 1".into(),
+                },
+                "'0 9 * * 1' must have 3 fields: day of month, month and day of week",
+            ),
+            (
+                Recurrence::Cron {
+                    expr: "* 13 *".into(),
+                },
+                "month '13' is not between 1 and 12",
+            ),
+            (
+                Recurrence::Cron {
+                    expr: "30 2 *".into(),
+                },
+                "'30 2 *' never matches",
+            ),
+        ] {
+            assert_eq!(rule.validate().unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn serializes_tagged_by_period() {
+        let rule: Recurrence =
+            serde_json::from_str(r#"{"every":"week","weekdays":["mon"]}"#).unwrap();
+        assert_eq!(
+            rule,
+            Recurrence::Week {
+                interval: 1,
+                weekdays: vec![Weekday::Mon]
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&Recurrence::Month {
+                interval: 1,
+                day: None
+            })
+            .unwrap(),
+            r#"{"every":"month","interval":1}"#
+        );
+    }
+}
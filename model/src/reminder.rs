@@ -0,0 +1,68 @@
+//! Reminders before a task's due date
+//!
+//! ```json
+//! { "days_before": 1, "at": "09:00" }
+//! ```
+//!
+//! Times are UTC. A task without a due date never reminds.
+
+use crate::error::FieldError;
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Most reminders one task may have
+pub const MAX_REMINDERS: usize = 5;
+/// Furthest ahead of the due date a reminder may fire, in days
+pub const MAX_DAYS_BEFORE: u32 = 365;
+
+/// A notification to the task's owner `days_before` its due date, at `at`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Reminder {
+    #[serde(default)]
+    pub days_before: u32,
+    /// Time of day, UTC; 09:00 unless given
+    #[serde(default = "nine_am")]
+    #[cfg_attr(feature = "openapi", schema(value_type = String, example = "09:00"))]
+    pub at: NaiveTime,
+}
+
+fn nine_am() -> NaiveTime {
+    NaiveTime::from_hms_opt(9, 0, 0).expect("a valid time")
+}
+
+impl Reminder {
+    /// A reminder `days_before` the due date at 09:00 UTC
+    pub fn days_before(days_before: u32) -> Self {
+        Reminder {
+            days_before,
+            at: nine_am(),
+        }
+    }
+
+    /// When the reminder fires for a task due on `due`
+    pub fn fires_at(&self, due: NaiveDate) -> Option<DateTime<Utc>> {
+        let day = due.checked_sub_days(Days::new(u64::from(self.days_before)))?;
+        Some(day.and_time(self.at).and_utc())
+    }
+}
+
+/// Checks the number of reminders and how far ahead each one fires
+pub fn validate(reminders: &[Reminder]) -> Result<(), FieldError> {
+    if reminders.len() > MAX_REMINDERS {
+        return Err(FieldError::new(
+            "reminders",
+            format!("at most {} reminders per task", MAX_REMINDERS),
+        ));
+    }
+    if reminders
+        .iter()
+        .any(|reminder| reminder.days_before > MAX_DAYS_BEFORE)
+    {
+        return Err(FieldError::new(
+            "reminders",
+            format!("days_before must be at most {}", MAX_DAYS_BEFORE),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Free-form labels on tasks
+//!
+//! Tags are compared without regard to case or surrounding spaces, so they
+//! are kept trimmed and lowercased: `"Errands "` and `"errands"` are the
+//! same tag.
+
+use crate::error::FieldError;
+
+/// Most tags one task may have
+pub const MAX_TAGS: usize = 20;
+/// Longest accepted tag, in characters
+pub const MAX_TAG_LEN: usize = 40;
+
+/// `tag` trimmed and lowercased, or why it cannot be a tag
+pub fn normalize(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() {
+        Err("a tag must not be empty".to_string())
+    } else if tag.chars().count() > MAX_TAG_LEN {
+        Err(format!(
+            "'{}' is longer than {} characters",
+            tag, MAX_TAG_LEN
+        ))
+    } else if tag.chars().any(char::is_control) {
+        Err(format!(
+            "'{}' contains a control character",
+            tag.escape_debug()
+        ))
+    } else {
+        Ok(tag)
+    }
+}
+
+/// Normalizes `tags` in place, dropping repeats, and checks how many remain
+pub fn validate(tags: &mut Vec<String>) -> Result<(), FieldError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags.iter() {
+        let tag = normalize(tag).map_err(|message| FieldError::new("tags", message))?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(FieldError::new(
+            "tags",
+            format!("at most {} tags per task", MAX_TAGS),
+        ));
+    }
+    *tags = normalized;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_and_drops_repeats() {
+        let mut tags = vec![" Errands".to_string(), "home".into(), "ERRANDS ".into()];
+        validate(&mut tags).unwrap();
+        assert_eq!(tags, ["errands", "home"]);
+
+        let mut blank = vec!["home".to_string(), "  ".into()];
+        assert_eq!(
+            validate(&mut blank).unwrap_err().message,
+            "a tag must not be empty"
+        );
+        let mut many: Vec<String> = (0..=MAX_TAGS).map(|n| n.to_string()).collect();
+        assert!(validate(&mut many).is_err());
+    }
+}
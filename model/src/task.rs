@@ -0,0 +1,672 @@
+use crate::error::FieldError;
+use crate::recurrence::Recurrence;
+use crate::reminder::{self, Reminder};
+use crate::tag;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Longest accepted title, in characters
+pub const MAX_TITLE_LEN: usize = 200;
+/// Longest accepted description, in characters
+pub const MAX_DESCRIPTION_LEN: usize = 2000;
+
+/// Where a task is in its workflow
+///
+/// Allowed moves are listed in `TaskStatus::can_move_to`; the API rejects
+/// any other change of status.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    #[default]
+    Todo,
+    InProgress,
+    Blocked,
+    Done,
+}
+
+impl TaskStatus {
+    pub const ALL: [TaskStatus; 4] = [
+        TaskStatus::Todo,
+        TaskStatus::InProgress,
+        TaskStatus::Blocked,
+        TaskStatus::Done,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskStatus::Todo => "todo",
+            TaskStatus::InProgress => "in_progress",
+            TaskStatus::Blocked => "blocked",
+            TaskStatus::Done => "done",
+        }
+    }
+
+    /// Whether a task in this status may be moved to `next`
+    ///
+    /// Staying in the same status is always allowed. A done task can only be
+    /// reopened as `todo`.
+    pub fn can_move_to(self, next: TaskStatus) -> bool {
+        use TaskStatus::*;
+        self == next
+            || matches!(
+                (self, next),
+                (Todo, InProgress | Blocked | Done)
+                    | (InProgress, Todo | Blocked | Done)
+                    | (Blocked, Todo | InProgress)
+                    | (Done, Todo)
+            )
+    }
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TaskStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TaskStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown status '{}'", s))
+    }
+}
+
+/// How urgent a task is, lowest first
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 4] = [
+        Priority::Low,
+        Priority::Normal,
+        Priority::High,
+        Priority::Urgent,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Priority::ALL
+            .into_iter()
+            .find(|priority| priority.as_str() == s)
+            .ok_or_else(|| format!("unknown priority '{}'", s))
+    }
+}
+
+/// A status change that `TaskStatus::can_move_to` does not allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionError {
+    pub from: TaskStatus,
+    pub to: TaskStatus,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a {} task cannot move to {}", self.from, self.to)
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Task {
+    pub id: u32,
+    pub title: String,
+    pub description: String,
+    pub status: TaskStatus,
+    pub priority: Priority,
+    pub due_date: Option<NaiveDate>,
+    /// Set by the server when the task is created
+    pub created_at: DateTime<Utc>,
+    /// Set by the server on every change
+    pub updated_at: DateTime<Utc>,
+    /// Set by the server while the task is done
+    pub completed_at: Option<DateTime<Utc>>,
+    /// The task this one is a subtask of
+    #[serde(default)]
+    pub parent_id: Option<u32>,
+    /// Tasks that must be done before this one can be
+    #[serde(default)]
+    pub blocked_by: Vec<u32>,
+    /// The user who created the task; `None` for tasks from before accounts
+    #[serde(default)]
+    pub owner_id: Option<u32>,
+    /// Whether every signed-in user can see the task, not just its owner
+    #[serde(default)]
+    pub shared: bool,
+    /// Set while the task is in the trash
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Starts at 1 and goes up by one with every saved change; sent as the
+    /// task's `ETag`
+    #[serde(default = "first_version")]
+    pub version: u32,
+    /// How the task repeats; the scheduler moves it to each new occurrence,
+    /// so only the latest one carries it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    /// Notifications to the owner ahead of `due_date`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reminders: Vec<Reminder>,
+    /// The project the task is filed under, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<u32>,
+    /// Lowercased labels, each at most once
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl Task {
+    pub fn new(id: u32, title: String, description: String, now: DateTime<Utc>) -> Self {
+        Task {
+            id,
+            title,
+            description,
+            status: TaskStatus::Todo,
+            priority: Priority::Normal,
+            due_date: None,
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+            parent_id: None,
+            blocked_by: Vec::new(),
+            owner_id: None,
+            shared: false,
+            deleted_at: None,
+            version: first_version(),
+            recurrence: None,
+            reminders: Vec::new(),
+            project_id: None,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.status == TaskStatus::Done
+    }
+
+    /// Marks the task as changed at `now`, moving it to the next version
+    pub fn touch(&mut self, now: DateTime<Utc>) {
+        self.updated_at = now;
+        self.version += 1;
+    }
+
+    /// The `ETag` header value for the current version
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
+
+    pub fn is_trashed(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// An open task whose due date is before `today`
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        !self.is_done() && self.due_date.is_some_and(|due| due < today)
+    }
+
+    /// Whether the task carries `tag`, which must already be normalized
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own == tag)
+    }
+
+    /// Whether `user` may see the task
+    ///
+    /// Tasks without an owner predate accounts and stay visible to everyone.
+    pub fn visible_to(&self, user: u32) -> bool {
+        self.shared || self.owner_id.is_none_or(|owner| owner == user)
+    }
+
+    /// Whether `user` may change or delete the task
+    ///
+    /// Tasks without an owner are read-only, since no account can claim them.
+    pub fn editable_by(&self, user: u32) -> bool {
+        self.owner_id == Some(user)
+    }
+
+    /// Moves the task to `status`, keeping `completed_at` in step
+    pub fn set_status(
+        &mut self,
+        status: TaskStatus,
+        now: DateTime<Utc>,
+    ) -> Result<(), TransitionError> {
+        if !self.status.can_move_to(status) {
+            return Err(TransitionError {
+                from: self.status,
+                to: status,
+            });
+        }
+
+        if status == TaskStatus::Done && self.completed_at.is_none() {
+            self.completed_at = Some(now);
+        } else if status != TaskStatus::Done {
+            self.completed_at = None;
+        }
+        self.status = status;
+        Ok(())
+    }
+
+    /// Applies every field that is set in `patch` and `touch`es the task
+    pub fn apply(&mut self, patch: TaskPatch, now: DateTime<Utc>) -> Result<(), TransitionError> {
+        if let Some(status) = patch.status {
+            self.set_status(status, now)?;
+        }
+        if let Some(title) = patch.title {
+            self.title = title;
+        }
+        if let Some(description) = patch.description {
+            self.description = description;
+        }
+        if let Some(priority) = patch.priority {
+            self.priority = priority;
+        }
+        if let Some(due_date) = patch.due_date {
+            self.due_date = due_date;
+        }
+        if let Some(shared) = patch.shared {
+            self.shared = shared;
+        }
+        if let Some(recurrence) = patch.recurrence {
+            self.recurrence = recurrence;
+        }
+        if let Some(reminders) = patch.reminders {
+            self.reminders = reminders;
+        }
+        if let Some(project_id) = patch.project_id {
+            self.project_id = project_id;
+        }
+        if let Some(tags) = patch.tags {
+            self.tags = tags;
+        }
+        self.touch(now);
+        Ok(())
+    }
+}
+
+/// Body of `POST /tasks`
+///
+/// `id` is normally left out and assigned by the store; a client-chosen id
+/// that is or ever was taken is rejected. `owner_id` is never read from the body;
+/// the handler sets it to the signed-in user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewTask {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    #[serde(skip)]
+    pub owner_id: Option<u32>,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub shared: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reminders: Vec<Reminder>,
+    /// Must be one of your projects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl NewTask {
+    /// A task with `title` and every other field at its default
+    pub fn new(title: impl Into<String>) -> Self {
+        NewTask {
+            id: None,
+            owner_id: None,
+            title: title.into(),
+            description: String::new(),
+            status: TaskStatus::default(),
+            priority: Priority::default(),
+            due_date: None,
+            shared: false,
+            recurrence: None,
+            reminders: Vec::new(),
+            project_id: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Trims text fields and checks them against the length limits, and a
+    /// chosen id against the highest one
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if self.id == Some(u32::MAX) {
+            // The store numbers new tasks upwards from the highest id, so
+            // this one would leave no id for the next task.
+            errors.push(FieldError::new("id", format!("must be below {}", u32::MAX)));
+        }
+        check_title(&mut self.title, &mut errors);
+        check_description(&mut self.description, &mut errors);
+        check_recurrence(self.recurrence.as_ref(), &mut errors);
+        check_reminders(&self.reminders, &mut errors);
+        check_tags(&mut self.tags, &mut errors);
+        finish(errors)
+    }
+
+    /// Builds the stored task once an id has been chosen
+    pub fn into_task(self, id: u32, now: DateTime<Utc>) -> Task {
+        let mut task = Task::new(id, self.title, self.description, now);
+        task.priority = self.priority;
+        task.due_date = self.due_date;
+        task.status = self.status;
+        task.owner_id = self.owner_id;
+        task.shared = self.shared;
+        task.recurrence = self.recurrence;
+        task.reminders = self.reminders;
+        task.project_id = self.project_id;
+        task.tags = self.tags;
+        if task.is_done() {
+            task.completed_at = Some(now);
+        }
+        task
+    }
+}
+
+/// Body of `PUT /tasks/<id>`: every client-editable field is replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskUpdate {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub shared: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reminders: Vec<Reminder>,
+    /// Must be one of your projects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl TaskUpdate {
+    /// Trims text fields and checks them against the length limits
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        check_title(&mut self.title, &mut errors);
+        check_description(&mut self.description, &mut errors);
+        check_recurrence(self.recurrence.as_ref(), &mut errors);
+        check_reminders(&self.reminders, &mut errors);
+        check_tags(&mut self.tags, &mut errors);
+        finish(errors)
+    }
+
+    /// The patch that sets every field, so PUT and PATCH share one code path
+    pub fn into_patch(self) -> TaskPatch {
+        TaskPatch {
+            title: Some(self.title),
+            description: Some(self.description),
+            status: Some(self.status),
+            priority: Some(self.priority),
+            due_date: Some(self.due_date),
+            shared: Some(self.shared),
+            recurrence: Some(self.recurrence),
+            reminders: Some(self.reminders),
+            project_id: Some(self.project_id),
+            tags: Some(self.tags),
+        }
+    }
+}
+
+/// Body of `PATCH /tasks/<id>`: only the fields present are changed
+///
+/// `"due_date": null` clears the due date, `"recurrence": null` stops the
+/// task repeating and `"project_id": null` takes it out of its project;
+/// leaving any of them out keeps it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub due_date: Option<Option<NaiveDate>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recurrence: Option<Option<Recurrence>>,
+    /// Replaces every reminder; `[]` removes them all
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reminders: Option<Vec<Reminder>>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub project_id: Option<Option<u32>>,
+    /// Replaces every tag; `[]` removes them all
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+impl TaskPatch {
+    /// Trims and checks only the fields that are present
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if let Some(title) = &mut self.title {
+            check_title(title, &mut errors);
+        }
+        if let Some(description) = &mut self.description {
+            check_description(description, &mut errors);
+        }
+        if let Some(recurrence) = &self.recurrence {
+            check_recurrence(recurrence.as_ref(), &mut errors);
+        }
+        if let Some(reminders) = &self.reminders {
+            check_reminders(reminders, &mut errors);
+        }
+        if let Some(tags) = &mut self.tags {
+            check_tags(tags, &mut errors);
+        }
+        finish(errors)
+    }
+}
+
+/// Marks a field as present even when its value is `null`
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Trims `title` in place and checks it is non-empty and not too long
+fn check_title(title: &mut String, errors: &mut Vec<FieldError>) {
+    *title = title.trim().to_string();
+    if title.is_empty() {
+        errors.push(FieldError::new("title", "must not be empty"));
+    } else if title.chars().count() > MAX_TITLE_LEN {
+        errors.push(FieldError::new(
+            "title",
+            format!("must be at most {} characters", MAX_TITLE_LEN),
+        ));
+    }
+}
+
+/// Trims `description` in place and checks it is not too long
+fn check_description(description: &mut String, errors: &mut Vec<FieldError>) {
+    *description = description.trim().to_string();
+    if description.chars().count() > MAX_DESCRIPTION_LEN {
+        errors.push(FieldError::new(
+            "description",
+            format!("must be at most {} characters", MAX_DESCRIPTION_LEN),
+        ));
+    }
+}
+
+fn check_recurrence(recurrence: Option<&Recurrence>, errors: &mut Vec<FieldError>) {
+    if let Some(Err(error)) = recurrence.map(Recurrence::validate) {
+        errors.push(error);
+    }
+}
+
+fn check_reminders(reminders: &[Reminder], errors: &mut Vec<FieldError>) {
+    if let Err(error) = reminder::validate(reminders) {
+        errors.push(error);
+    }
+}
+
+fn check_tags(tags: &mut Vec<String>, errors: &mut Vec<FieldError>) {
+    if let Err(error) = tag::validate(tags) {
+        errors.push(error);
+    }
+}
+
+/// Turns the collected field errors into a result
+fn finish(errors: Vec<FieldError>) -> Result<(), Vec<FieldError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_transitions() {
+        let now = Utc::now();
+        let mut task = Task::new(1, "ship".into(), String::new(), now);
+
+        task.set_status(TaskStatus::InProgress, now).unwrap();
+        task.set_status(TaskStatus::Blocked, now).unwrap();
+        assert_eq!(
+            task.set_status(TaskStatus::Done, now),
+            Err(TransitionError {
+                from: TaskStatus::Blocked,
+                to: TaskStatus::Done
+            })
+        );
+
+        task.set_status(TaskStatus::InProgress, now).unwrap();
+        task.set_status(TaskStatus::Done, now).unwrap();
+        assert_eq!(task.completed_at, Some(now));
+        assert!(task.set_status(TaskStatus::InProgress, now).is_err());
+
+        task.set_status(TaskStatus::Todo, now).unwrap();
+        assert_eq!(task.completed_at, None);
+    }
+
+    #[test]
+    fn patch_tells_null_from_absent() {
+        let patch = TaskPatch {
+            due_date: Some(None),
+            ..TaskPatch::default()
+        };
+        let json = serde_json::to_string(&patch).unwrap();
+        assert_eq!(json, r#"{"due_date":null}"#);
+        let patch: TaskPatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(patch.due_date, Some(None));
+
+        let patch: TaskPatch = serde_json::from_str(r#"{"title":"ship"}"#).unwrap();
+        assert_eq!(patch.due_date, None);
+        assert_eq!(
+            serde_json::to_string(&patch).unwrap(),
+            r#"{"title":"ship"}"#
+        );
+    }
+
+    #[test]
+    fn ownerless_tasks_are_read_only() {
+        let mut task = Task::new(1, "legacy".into(), String::new(), Utc::now());
+        assert!(task.visible_to(2));
+        assert!(!task.editable_by(2));
+
+        task.owner_id = Some(2);
+        assert!(task.editable_by(2));
+        assert!(!task.visible_to(3));
+        task.shared = true;
+        assert!(task.visible_to(3));
+        assert!(!task.editable_by(3));
+    }
+
+    #[test]
+    fn overdue_only_while_open() {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let mut task = Task::new(1, "pay".into(), String::new(), now);
+        assert!(!task.is_overdue(today));
+
+        task.due_date = today.pred_opt();
+        assert!(task.is_overdue(today));
+
+        task.set_status(TaskStatus::Done, now).unwrap();
+        assert!(!task.is_overdue(today));
+    }
+}